@@ -0,0 +1,54 @@
+#[path = "../tests/common/reparsing.rs"]
+mod reparsing;
+use ccl_rs::key_val::KeyVal;
+use ccl_rs::options::ParseOptions;
+use criterion::{Criterion, criterion_group, criterion_main};
+use reparsing::parse_flat_to_tree_reparsing;
+use std::hint::black_box;
+
+/// `large_generated.ccl` from `cargo run --example generate_large_ccl`, or
+/// a deeply nested stand-in if it has not been generated
+fn input() -> String {
+    std::fs::read_to_string("large_generated.ccl").unwrap_or_else(|_| {
+        eprintln!(
+            "large_generated.ccl not found, run `cargo run --example \
+             generate_large_ccl` to benchmark it"
+        );
+        (0..2000)
+            .map(|i| {
+                (0..30)
+                    .map(|depth| {
+                        format!(
+                            "{}key{}_{} =\n",
+                            " ".repeat(2 * depth),
+                            i,
+                            depth
+                        )
+                    })
+                    .collect::<String>()
+            })
+            .collect()
+    })
+}
+
+fn bench_tree(c: &mut Criterion) {
+    let data = input();
+    let options = ParseOptions::default();
+    let mut group = c.benchmark_group("parse to tree");
+    group.sample_size(10);
+
+    group.bench_function("three-stage", |b| {
+        b.iter(|| {
+            let key_vals = KeyVal::parse(black_box(&data)).unwrap();
+            parse_flat_to_tree_reparsing(&key_vals, &options)
+        })
+    });
+    group.bench_function("single-pass", |b| {
+        b.iter(|| KeyVal::parse_tree(black_box(&data)).unwrap())
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, bench_tree);
+criterion_main!(benches);
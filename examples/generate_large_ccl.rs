@@ -54,12 +54,9 @@ fn main() -> std::io::Result<()> {
     let mut runner = TestRunner::default();
 
     // Generate a large CCL structure
-    let tree = large_ccl_strat().new_tree(&mut runner).map_err(|e| {
-        std::io::Error::new(
-            std::io::ErrorKind::Other,
-            format!("Strategy error: {}", e),
-        )
-    })?;
+    let tree = large_ccl_strat()
+        .new_tree(&mut runner)
+        .map_err(|e| std::io::Error::other(format!("Strategy error: {}", e)))?;
     let ccl = tree.current();
 
     // Convert to string representation
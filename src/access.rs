@@ -0,0 +1,98 @@
+use crate::error::AccessError;
+use crate::parser::CCL;
+use std::fmt::Display;
+use std::str::FromStr;
+
+fn owned(path: &[&str]) -> Vec<String> {
+    path.iter().map(|key| key.to_string()).collect()
+}
+
+/// Lookups by path, a list of keys from the outermost entry in. The values
+/// of an entry are the keys of the map under it, so `a = b` has the single
+/// value `b` at path `["a"]`.
+impl CCL {
+    /// The map under the entry at `path`; the whole CCL for an empty path
+    pub fn get(&self, path: &[&str]) -> Result<&CCL, AccessError> {
+        let mut ccl = self;
+        for (depth, key) in path.iter().enumerate() {
+            let CCL(map) = ccl;
+            ccl = map.get(*key).ok_or_else(|| AccessError::Missing {
+                path: owned(&path[..=depth]),
+            })?;
+        }
+        Ok(ccl)
+    }
+
+    pub fn contains(&self, path: &[&str]) -> bool {
+        self.get(path).is_ok()
+    }
+
+    /// Every value of the entry at `path`, in order
+    pub fn get_all(&self, path: &[&str]) -> Result<Vec<&str>, AccessError> {
+        let CCL(map) = self.get(path)?;
+        Ok(map.keys().map(String::as_str).collect())
+    }
+
+    /// The only value of the entry at `path`, whatever is under it. An
+    /// entry with no value, `key =`, has the empty string.
+    pub fn get_one(&self, path: &[&str]) -> Result<&str, AccessError> {
+        let values = self.get_all(path)?;
+        match values.as_slice() {
+            [] => Ok(""),
+            [value] => Ok(value),
+            _ => Err(AccessError::Multiple {
+                path: owned(path),
+                values: values.iter().map(|value| value.to_string()).collect(),
+            }),
+        }
+    }
+
+    /// Like `get_one`, but the value must have nothing under it
+    pub fn get_str(&self, path: &[&str]) -> Result<&str, AccessError> {
+        let value = self.get_one(path)?;
+        let CCL(nested) = self.get(path)?;
+        match nested.get(value) {
+            Some(CCL(under)) if !under.is_empty() => Err(AccessError::Nested {
+                path: owned(path),
+                value: value.to_string(),
+            }),
+            _ => Ok(value),
+        }
+    }
+
+    /// The value at `path`, parsed with `FromStr`
+    pub fn get_parsed<T>(&self, path: &[&str]) -> Result<T, AccessError>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        let value = self.get_str(path)?;
+        value.parse().map_err(|err: T::Err| AccessError::Invalid {
+            path: owned(path),
+            value: value.to_string(),
+            message: err.to_string(),
+        })
+    }
+
+    /// The flag at `path`: `true` or `false`, or an entry with no value,
+    /// `key =`, for `true`
+    pub fn get_bool(&self, path: &[&str]) -> Result<bool, AccessError> {
+        match self.get_all(path)?.as_slice() {
+            [] => Ok(true),
+            _ => self.get_parsed(path),
+        }
+    }
+
+    /// The items of the list at `path`, written with the `= item`
+    /// convention, or else every value of the entry
+    pub fn get_list(&self, path: &[&str]) -> Result<Vec<&str>, AccessError> {
+        match self.get_all(path)?.as_slice() {
+            [""] => {
+                let mut items = path.to_vec();
+                items.push("");
+                self.get_all(&items)
+            }
+            values => Ok(values.to_vec()),
+        }
+    }
+}
@@ -0,0 +1,65 @@
+use crate::key_val::{KeyVal, KeyVals};
+
+/// Key of comment entries, by the convention
+/// ```text
+/// /= This is a comment
+/// ```
+pub const COMMENT_KEY: &str = "/";
+
+/// Whether comment entries end up in the parsed result
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum CommentMode {
+    /// Treat comments as ordinary entries with key `/`
+    #[default]
+    Keep,
+    /// Drop comments at every level of nesting
+    Strip,
+}
+
+/// A comment taken out of a list of key-value pairs
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Comment {
+    pub text: String,
+    /// Index of the entry the comment precedes in the list left after
+    /// taking the comments out, or `None` for comments after the last entry
+    pub attached_to: Option<usize>,
+}
+
+impl KeyVal {
+    pub fn is_comment(&self) -> bool {
+        self.key == COMMENT_KEY
+    }
+
+    /// Separate the top-level comments from the other entries. Comments
+    /// are attached to the entry that follows them, so tooling can keep
+    /// them together.
+    ///
+    /// Only the comments of `key_vals` itself are taken out: one nested in
+    /// a value stays in its text, to be split from the pairs parsed from
+    /// that value in turn.
+    pub fn split_comments(key_vals: KeyVals) -> (KeyVals, Vec<Comment>) {
+        let mut entries = Vec::new();
+        let mut comments = Vec::new();
+        let mut pending = 0;
+
+        for key_val in key_vals {
+            if key_val.is_comment() {
+                comments.push(Comment {
+                    text: key_val.value,
+                    attached_to: None,
+                });
+                pending += 1;
+            } else {
+                let index = entries.len();
+                let start = comments.len() - pending;
+                for comment in &mut comments[start..] {
+                    comment.attached_to = Some(index);
+                }
+                pending = 0;
+                entries.push(key_val);
+            }
+        }
+
+        (entries, comments)
+    }
+}
@@ -0,0 +1,1067 @@
+//! Lossless concrete syntax tree.
+//!
+//! The green tree is immutable and position-independent: every node knows
+//! only its kind, its children and the length of its text, so unchanged
+//! subtrees are shared between versions of a document. The red tree
+//! (`SyntaxNode`) is a cheap view on top of it that adds absolute offsets
+//! and parent pointers.
+//!
+//! Every byte of the source is in exactly one token, so printing the tree
+//! gives back the source byte-for-byte, and edits only touch the tokens of
+//! the entries they change.
+use crate::comment::{COMMENT_KEY, CommentMode};
+use crate::error::ParseError;
+use crate::key_val::KeyVal;
+use crate::monoid::Monoid;
+use crate::options::ParseOptions;
+use crate::parser::CCL;
+use crate::span::Span;
+use crate::string_utils::trim_lines;
+use std::fmt::Display;
+use std::rc::Rc;
+use std::sync::Arc;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum SyntaxKind {
+    // Nodes
+    /// The whole file
+    Document,
+    /// `key = value`
+    Entry,
+    Key,
+    /// Either the tokens of a leaf value, or nested entries with the
+    /// trivia between them
+    Value,
+
+    // Tokens
+    Eq,
+    Text,
+    /// Spaces and tabs, including indentation
+    Whitespace,
+    /// `\n` or `\r\n`
+    Newline,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GreenToken {
+    kind: SyntaxKind,
+    text: String,
+}
+
+impl GreenToken {
+    fn new(kind: SyntaxKind, text: &str) -> Arc<Self> {
+        Arc::new(Self {
+            kind,
+            text: text.to_string(),
+        })
+    }
+
+    pub fn kind(&self) -> SyntaxKind {
+        self.kind
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GreenNode {
+    kind: SyntaxKind,
+    children: Vec<GreenElement>,
+    len: usize,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum GreenElement {
+    Node(Arc<GreenNode>),
+    Token(Arc<GreenToken>),
+}
+
+impl GreenElement {
+    pub fn kind(&self) -> SyntaxKind {
+        match self {
+            GreenElement::Node(node) => node.kind,
+            GreenElement::Token(token) => token.kind,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        match self {
+            GreenElement::Node(node) => node.len,
+            GreenElement::Token(token) => token.text.len(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl GreenNode {
+    fn new(kind: SyntaxKind, children: Vec<GreenElement>) -> Arc<Self> {
+        let len = children.iter().map(GreenElement::len).sum();
+        Arc::new(Self {
+            kind,
+            children,
+            len,
+        })
+    }
+
+    pub fn kind(&self) -> SyntaxKind {
+        self.kind
+    }
+
+    pub fn children(&self) -> &[GreenElement] {
+        &self.children
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Rebuild the nodes from `self` down the child `indices`, with the
+    /// children of the last one replaced by `edit`
+    fn edit(
+        self: &Arc<Self>,
+        indices: &[usize],
+        edit: &mut dyn FnMut(&mut Vec<GreenElement>),
+    ) -> Arc<Self> {
+        let mut children = self.children.clone();
+        match indices.split_first() {
+            None => edit(&mut children),
+            Some((&index, rest)) => {
+                let GreenElement::Node(child) = &children[index] else {
+                    panic!("Never: edit path goes through a token");
+                };
+                children[index] = GreenElement::Node(child.edit(rest, edit));
+            }
+        }
+        GreenNode::new(self.kind, children)
+    }
+
+    fn write(&self, buf: &mut String) {
+        for child in &self.children {
+            match child {
+                GreenElement::Node(node) => node.write(buf),
+                GreenElement::Token(token) => buf.push_str(&token.text),
+            }
+        }
+    }
+}
+
+impl Display for GreenNode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut buf = String::with_capacity(self.len);
+        self.write(&mut buf);
+        f.write_str(&buf)
+    }
+}
+
+/// Red node: a green node at a position in a tree
+#[derive(Clone, Debug)]
+pub struct SyntaxNode(Rc<NodeData>);
+
+#[derive(Debug)]
+struct NodeData {
+    green: Arc<GreenNode>,
+    offset: usize,
+    parent: Option<SyntaxNode>,
+}
+
+#[derive(Clone, Debug)]
+pub struct SyntaxToken {
+    green: Arc<GreenToken>,
+    offset: usize,
+}
+
+#[derive(Clone, Debug)]
+pub enum SyntaxElement {
+    Node(SyntaxNode),
+    Token(SyntaxToken),
+}
+
+impl SyntaxToken {
+    pub fn kind(&self) -> SyntaxKind {
+        self.green.kind
+    }
+
+    pub fn text(&self) -> &str {
+        &self.green.text
+    }
+
+    pub fn span(&self) -> Span {
+        Span::new(self.offset, self.offset + self.green.text.len())
+    }
+}
+
+impl SyntaxNode {
+    fn new_root(green: Arc<GreenNode>) -> Self {
+        SyntaxNode(Rc::new(NodeData {
+            green,
+            offset: 0,
+            parent: None,
+        }))
+    }
+
+    pub fn kind(&self) -> SyntaxKind {
+        self.0.green.kind
+    }
+
+    pub fn green(&self) -> &Arc<GreenNode> {
+        &self.0.green
+    }
+
+    pub fn span(&self) -> Span {
+        Span::new(self.0.offset, self.0.offset + self.0.green.len)
+    }
+
+    pub fn parent(&self) -> Option<&SyntaxNode> {
+        self.0.parent.as_ref()
+    }
+
+    pub fn text(&self) -> String {
+        self.0.green.to_string()
+    }
+
+    pub fn children(&self) -> Vec<SyntaxElement> {
+        let mut offset = self.0.offset;
+        self.0
+            .green
+            .children
+            .iter()
+            .map(|child| {
+                let element = match child {
+                    GreenElement::Node(green) => {
+                        SyntaxElement::Node(SyntaxNode(Rc::new(NodeData {
+                            green: green.clone(),
+                            offset,
+                            parent: Some(self.clone()),
+                        })))
+                    }
+                    GreenElement::Token(green) => {
+                        SyntaxElement::Token(SyntaxToken {
+                            green: green.clone(),
+                            offset,
+                        })
+                    }
+                };
+                offset += child.len();
+                element
+            })
+            .collect()
+    }
+
+    fn child_nodes(&self) -> impl Iterator<Item = SyntaxNode> {
+        self.children().into_iter().filter_map(|child| match child {
+            SyntaxElement::Node(node) => Some(node),
+            SyntaxElement::Token(_) => None,
+        })
+    }
+
+    /// Entries directly in a document or a nested value
+    pub fn entries(&self) -> Vec<SyntaxNode> {
+        self.child_nodes()
+            .filter(|node| node.kind() == SyntaxKind::Entry)
+            .collect()
+    }
+
+    /// Key of an entry, as `KeyVal::parse` would read it
+    pub fn key(&self) -> Option<String> {
+        self.child_nodes()
+            .find(|node| node.kind() == SyntaxKind::Key)
+            .map(|key| trim_lines(&key.text()).into_owned())
+    }
+
+    /// Value node of an entry
+    pub fn value(&self) -> Option<SyntaxNode> {
+        self.child_nodes()
+            .find(|node| node.kind() == SyntaxKind::Value)
+    }
+
+    /// Whether a value holds nested entries rather than leaf text
+    pub fn is_section(&self) -> bool {
+        self.kind() == SyntaxKind::Document
+            || self
+                .0
+                .green
+                .children
+                .iter()
+                .any(|child| child.kind() == SyntaxKind::Entry)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum EditError {
+    /// No entry at the path
+    NotFound { path: Vec<String> },
+    /// The entry at the path has a leaf value, so cannot hold entries
+    NotASection { path: Vec<String> },
+}
+
+impl Display for EditError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            EditError::NotFound { path } => {
+                write!(f, "No entry at path: {}", path.join("="))
+            }
+            EditError::NotASection { path } => {
+                write!(f, "Entry has a leaf value: {}", path.join("="))
+            }
+        }
+    }
+}
+
+impl std::error::Error for EditError {}
+
+/// A parsed document that remembers every byte of its source
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Cst {
+    root: Arc<GreenNode>,
+}
+
+impl Display for Cst {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.root.fmt(f)
+    }
+}
+
+impl Cst {
+    pub fn parse(data: &str) -> Result<Self, ParseError> {
+        let spans = KeyVal::scan(data)?;
+        Ok(Self {
+            root: GreenNode::new(SyntaxKind::Document, section(data, spans)),
+        })
+    }
+
+    pub fn root(&self) -> SyntaxNode {
+        SyntaxNode::new_root(self.root.clone())
+    }
+
+    pub fn green(&self) -> &Arc<GreenNode> {
+        &self.root
+    }
+
+    /// The first entry at `path`, following keys through nested values
+    pub fn find(&self, path: &[&str]) -> Option<SyntaxNode> {
+        let mut section = self.root();
+        let mut found = None;
+        for key in path {
+            if let Some(entry) = found.take() {
+                section = SyntaxNode::value(&entry)?;
+            }
+            found = section
+                .entries()
+                .into_iter()
+                .find(|entry| entry.key().as_deref() == Some(*key));
+            found.as_ref()?;
+        }
+        found
+    }
+
+    /// Replace the value of the entry at `path`, keeping the rest of the
+    /// file untouched. Lines after the first are indented under the entry.
+    pub fn set_value(
+        &mut self,
+        path: &[&str],
+        value: &str,
+    ) -> Result<(), EditError> {
+        let indices = self.entry_indices(path)?;
+        self.set_value_at(&indices, value);
+        Ok(())
+    }
+
+    /// Add `key = value` after the last entry of the document (empty
+    /// `section`) or of the nested value of the entry at `section`
+    pub fn insert(
+        &mut self,
+        section: &[&str],
+        key: &str,
+        value: &str,
+    ) -> Result<(), EditError> {
+        let entry = if section.is_empty() {
+            None
+        } else {
+            let indices = self.entry_indices(section)?;
+            let entry = self.find(section).expect("Never: found above");
+            let value = entry.value().expect("Never: entries have a value");
+            if !value.is_section() && !value.green().is_empty() {
+                return Err(EditError::NotASection {
+                    path: to_path(section),
+                });
+            }
+            Some(indices)
+        };
+        self.insert_at(entry.as_deref(), None, key, value);
+        Ok(())
+    }
+
+    /// Delete the entry at `path` along with its nested entries and the
+    /// line break that separated it from its neighbours
+    pub fn remove(&mut self, path: &[&str]) -> Result<(), EditError> {
+        let indices = self.entry_indices(path)?;
+        self.remove_at(indices);
+        Ok(())
+    }
+
+    /// Edit the document into one that `CCL::parse_with` reads as
+    /// `target`, changing only the entries it reads differently, so the
+    /// text of the others stays as it is. Comments are left alone if
+    /// `options` strips them.
+    ///
+    /// New lines are indented past those of the section they go in, so
+    /// where the first entries of a section are indented less than the
+    /// rest, removing them can make what follows read differently. Check
+    /// the result if the text may be laid out that way.
+    pub fn update(&mut self, target: &CCL, options: &ParseOptions) {
+        self.update_section(None, target, options);
+    }
+
+    /// `set_value` of the entry at `indices`
+    fn set_value_at(&mut self, indices: &[usize], value: &str) {
+        let indent = self.indent_of(indices);
+        let newline = self.newline();
+        let value = reindent(value.trim(), &format!("{}  ", indent), newline);
+
+        self.root = self.root.edit(indices, &mut |children| {
+            let eq = position(children, SyntaxKind::Eq);
+            let was_inline = children.len() > eq + 2
+                && children[eq + 1].kind() == SyntaxKind::Whitespace
+                && !children[eq + 2].is_empty()
+                && !is_section(&children[eq + 2]);
+            let space = match &children[eq + 1] {
+                GreenElement::Token(ws) if was_inline => ws.text.clone(),
+                _ => " ".to_string(),
+            };
+
+            children.truncate(eq + 1);
+            if !value.is_empty() {
+                children.push(token(SyntaxKind::Whitespace, &space));
+            }
+            children.push(value_node(&value));
+        });
+    }
+
+    /// `insert` into the document (no `entry`) or the value of the entry
+    /// at `entry`, after the child `after` of the node added to or its
+    /// last entry. Returns the indices of the new entry.
+    fn insert_at(
+        &mut self,
+        entry: Option<&[usize]>,
+        after: Option<usize>,
+        key: &str,
+        value: &str,
+    ) -> Vec<usize> {
+        let newline = self.newline();
+
+        // Child indices of the node to add the entry to
+        let (indices, new_section_indent): (Vec<usize>, _) = match entry {
+            None => (Vec::new(), None),
+            Some(entry) => {
+                let node = self.node_at(entry);
+                let value = position(&node.children, SyntaxKind::Value);
+                let new_section_indent = (!is_section(&node.children[value]))
+                    .then(|| format!("{}  ", self.threshold_indent(entry)));
+                ([entry, &[value]].concat(), new_section_indent)
+            }
+        };
+
+        let last = after.or_else(|| {
+            self.node_at(&indices)
+                .children
+                .iter()
+                .rposition(|child| child.kind() == SyntaxKind::Entry)
+        });
+        let indent = match (new_section_indent, last) {
+            (Some(indent), _) => indent,
+            (None, Some(last)) => {
+                self.indent_of(&[indices.as_slice(), &[last]].concat())
+            }
+            (None, None) => String::new(),
+        };
+        let needs_newline = match last {
+            None if entry.is_none() => {
+                let text = self.to_string();
+                !text.is_empty() && !text.ends_with('\n')
+            }
+            _ => true,
+        };
+
+        let value = reindent(value.trim(), &format!("{}  ", indent), newline);
+        let entry_text = match (key.trim(), value.is_empty()) {
+            (key, true) => format!("{} =", key),
+            ("", false) => format!("= {}", value),
+            (key, false) => format!("{} = {}", key, value),
+        };
+        let spans = KeyVal::scan(&entry_text).expect("Never: has an `=`");
+        let mut new = Vec::new();
+        if needs_newline {
+            new.push(token(SyntaxKind::Newline, newline));
+        }
+        if !indent.is_empty() {
+            new.push(token(SyntaxKind::Whitespace, &indent));
+        }
+        new.push(section_entry(&entry_text, spans[0]));
+
+        let start = match last {
+            Some(last) => last + 1,
+            None if entry.is_none() => self.root.children.len(),
+            None => 0,
+        };
+        self.root = self.root.edit(&indices, &mut |children| match last {
+            Some(_) => {
+                children.splice(start..start, new.clone());
+            }
+            None if entry.is_none() => children.extend(new.clone()),
+            None => *children = new.clone(),
+        });
+        if let (None, Some(entry)) = (last, entry) {
+            self.drop_space_after_eq(entry);
+        }
+        let mut new_entry = indices;
+        new_entry.push(start + new.len() - 1);
+        new_entry
+    }
+
+    /// `remove` of the entry at `indices`
+    fn remove_at(&mut self, mut indices: Vec<usize>) {
+        let index = indices.pop().expect("Never: path is not empty");
+
+        let mut emptied = false;
+        self.root = self.root.edit(&indices, &mut |children| {
+            let is = |i: usize, kind| {
+                children.get(i).is_some_and(|child| child.kind() == kind)
+            };
+            let (mut start, mut end) = (index, index + 1);
+            if is(end, SyntaxKind::Whitespace) {
+                end += 1;
+            }
+            if start > 0 && is(start - 1, SyntaxKind::Whitespace) {
+                start -= 1;
+            }
+            if start > 0 && is(start - 1, SyntaxKind::Newline) {
+                start -= 1;
+            } else {
+                // First line: take the line break after the entry instead,
+                // and its indentation unless something precedes it
+                if start > 0 {
+                    start = index;
+                }
+                if is(end, SyntaxKind::Newline) {
+                    end += 1;
+                }
+            }
+            children.drain(start..end);
+
+            emptied = !children
+                .iter()
+                .any(|child| child.kind() == SyntaxKind::Entry);
+        });
+
+        if emptied && !indices.is_empty() {
+            // Removed the last nested entry: leave `key =`
+            self.root = self.root.edit(&indices, &mut Vec::clear);
+            self.drop_space_after_eq(&indices[..indices.len() - 1]);
+        }
+    }
+
+    /// `update` of the document (no `entry`) or the value of the entry at
+    /// `entry`
+    fn update_section(
+        &mut self,
+        entry: Option<&[usize]>,
+        target: &CCL,
+        options: &ParseOptions,
+    ) {
+        let section = match entry {
+            None => Vec::new(),
+            Some(entry) => {
+                let value =
+                    position(&self.node_at(entry).children, SyntaxKind::Value);
+                [entry, &[value]].concat()
+            }
+        };
+
+        // The entries of each key, as child indices and what their values
+        // read as, in the order the keys first appear
+        let mut groups: Vec<(String, Vec<(usize, CCL)>)> = Vec::new();
+        for (index, child) in self.node_at(&section).children.iter().enumerate()
+        {
+            let GreenElement::Node(node) = child else {
+                continue;
+            };
+            if node.kind != SyntaxKind::Entry {
+                continue;
+            }
+            let key = entry_key(node);
+            if options.comments == CommentMode::Strip && key == COMMENT_KEY {
+                continue;
+            }
+            let value = read_value(&value_of(node).to_string(), options);
+            match groups.iter_mut().find(|(other, _)| *other == key) {
+                Some((_, entries)) => entries.push((index, value)),
+                None => groups.push((key, vec![(index, value)])),
+            }
+        }
+
+        let CCL(targets) = target;
+        let mut edits = Vec::new();
+        for (key, entries) in &groups {
+            let Some(target) = targets.get(key) else {
+                for (index, _) in entries {
+                    edits.push((*index, Edit::Remove));
+                }
+                continue;
+            };
+            let current = CCL::aggregate(
+                entries.iter().map(|(_, value)| value.clone()).collect(),
+            );
+            if current == *target {
+                continue;
+            }
+            let last = entries.last().expect("Never: empty group").0;
+            let written = entries
+                .iter()
+                .filter(|(_, CCL(value))| !value.is_empty())
+                .collect::<Vec<_>>();
+            let sections = written
+                .iter()
+                .filter(|(index, _)| {
+                    let GreenElement::Node(entry) =
+                        &self.node_at(&section).children[*index]
+                    else {
+                        unreachable!("Never: entries are nodes");
+                    };
+                    is_section(
+                        &entry.children
+                            [position(&entry.children, SyntaxKind::Value)],
+                    )
+                })
+                .count();
+            let (CCL(current), CCL(values)) = (current, target);
+
+            if sections == 0 && plain_values(target).is_some() {
+                // Leaf values: change or remove those that are not in the
+                // target, then add the rest
+                let mut added = values
+                    .keys()
+                    .filter(|value| !current.contains_key(*value))
+                    .collect::<Vec<_>>()
+                    .into_iter();
+                // An entry with no value still has to be left for the key
+                let mut kept = written.len() < entries.len();
+                for (index, value) in written {
+                    let CCL(value) = value;
+                    if value.keys().all(|value| values.contains_key(value)) {
+                        kept = true;
+                        continue;
+                    }
+                    match added.next() {
+                        Some(new) => edits.push((*index, Edit::Set(new))),
+                        None if !kept => edits.push((*index, Edit::Set(""))),
+                        None => edits.push((*index, Edit::Remove)),
+                    }
+                    kept = true;
+                }
+                let rest =
+                    added.map(|value| CCL::key(value)).collect::<Vec<_>>();
+                if !rest.is_empty() {
+                    edits.push((last, Edit::Insert(key, rest)));
+                }
+            } else if let [(index, _)] = written.as_slice()
+                && sections == 1
+                && !is_inline(
+                    self.node_at(&[section.as_slice(), &[*index]].concat()),
+                )
+            {
+                edits.push((*index, Edit::Update(target)));
+            } else {
+                let (first, _) = written.first().unwrap_or(&&entries[0]);
+                edits.push((*first, Edit::Rewrite(target)));
+                for (index, _) in written.iter().skip(1) {
+                    edits.push((*index, Edit::Remove));
+                }
+            }
+        }
+
+        let new = targets
+            .iter()
+            .filter(|(key, _)| groups.iter().all(|(other, _)| other != *key))
+            .collect::<Vec<_>>();
+
+        // From the last child back, so the indices of those not edited yet
+        // stay the same
+        edits.sort_by_key(|(index, _)| std::cmp::Reverse(*index));
+        for (key, value) in new {
+            self.insert_entries(entry, None, key, value, options);
+        }
+        for (index, edit) in edits {
+            let child = [section.as_slice(), &[index]].concat();
+            match edit {
+                Edit::Remove => self.remove_at(child),
+                Edit::Set(value) => self.set_value_at(&child, value),
+                Edit::Update(target) => {
+                    self.update_section(Some(&child), target, options)
+                }
+                Edit::Rewrite(target) => match plain_value(target) {
+                    Some(value) => self.set_value_at(&child, value),
+                    None => {
+                        self.set_value_at(&child, "");
+                        self.update_section(Some(&child), target, options);
+                    }
+                },
+                Edit::Insert(key, values) => {
+                    let mut after = index;
+                    for value in &values {
+                        after = self.insert_entries(
+                            entry,
+                            Some(after),
+                            key,
+                            value,
+                            options,
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    /// Add entries `key` that read as `value` to the document (no
+    /// `entry`) or the value of the entry at `entry`, after the child
+    /// `after` or the last entry: one per value if `value` is only plain
+    /// values. Returns the child index of the last one added.
+    fn insert_entries(
+        &mut self,
+        entry: Option<&[usize]>,
+        after: Option<usize>,
+        key: &str,
+        value: &CCL,
+        options: &ParseOptions,
+    ) -> usize {
+        let mut after = after;
+        let mut added = |cst: &mut Self, value: &str| {
+            let new = cst.insert_at(entry, after, key, value);
+            after = new.last().copied();
+            new
+        };
+        match plain_values(value) {
+            Some(values) if !values.is_empty() => {
+                for value in values {
+                    added(self, value);
+                }
+            }
+            _ => {
+                let new = added(self, "");
+                self.update_section(Some(&new), value, options);
+            }
+        }
+        after.expect("Never: added an entry")
+    }
+
+    fn drop_space_after_eq(&mut self, entry: &[usize]) {
+        self.root = self.root.edit(entry, &mut |children| {
+            let eq = position(children, SyntaxKind::Eq);
+            if children[eq + 1].kind() == SyntaxKind::Whitespace {
+                children.remove(eq + 1);
+            }
+        });
+    }
+
+    fn node_at(&self, indices: &[usize]) -> &Arc<GreenNode> {
+        let mut node = &self.root;
+        for &index in indices {
+            let GreenElement::Node(child) = &node.children[index] else {
+                panic!("Never: indices lead to a node");
+            };
+            node = child;
+        }
+        node
+    }
+
+    /// Child indices from the root down to the entry at `path`
+    fn entry_indices(&self, path: &[&str]) -> Result<Vec<usize>, EditError> {
+        let not_found = || EditError::NotFound {
+            path: to_path(path),
+        };
+        let mut indices = Vec::new();
+        let mut node = self.root.clone();
+        for (depth, key) in path.iter().enumerate() {
+            if depth > 0 {
+                let value = position(&node.children, SyntaxKind::Value);
+                indices.push(value);
+                let GreenElement::Node(value) = &node.children[value] else {
+                    unreachable!("Never: values are nodes");
+                };
+                node = value.clone();
+            }
+            let (index, entry) = node
+                .children
+                .iter()
+                .enumerate()
+                .find_map(|(i, child)| match child {
+                    GreenElement::Node(entry)
+                        if entry.kind == SyntaxKind::Entry
+                            && entry_key(entry) == *key =>
+                    {
+                        Some((i, entry.clone()))
+                    }
+                    _ => None,
+                })
+                .ok_or_else(not_found)?;
+            indices.push(index);
+            node = entry;
+        }
+        if indices.is_empty() {
+            return Err(not_found());
+        }
+        Ok(indices)
+    }
+
+    /// Whitespace between the start of the line and the node at `indices`
+    fn indent_of(&self, indices: &[usize]) -> String {
+        let mut offset = 0;
+        for depth in 0..indices.len() {
+            let node = self.node_at(&indices[..depth]);
+            offset += node.children[..indices[depth]]
+                .iter()
+                .map(GreenElement::len)
+                .sum::<usize>();
+        }
+        let text = self.to_string();
+        let line_start = text[..offset].rfind('\n').map_or(0, |i| i + 1);
+        let before = &text[line_start..offset];
+        before[..before.len() - before.trim_start().len()].to_string()
+    }
+
+    /// The widest indentation of the entry at `indices` and the first
+    /// lines of the sections it is in, which the lines of its nested
+    /// entries must be indented past
+    fn threshold_indent(&self, indices: &[usize]) -> String {
+        let mut widest = self.indent_of(indices);
+        for depth in (0..indices.len()).step_by(2) {
+            // The first line of a value that starts after the `=` is not
+            // indented at all
+            if depth > 0 && is_inline(self.node_at(&indices[..depth - 1])) {
+                continue;
+            }
+            let first = self
+                .node_at(&indices[..depth])
+                .children
+                .iter()
+                .position(|child| child.kind() == SyntaxKind::Entry)
+                .expect("Never: a section of the entry has no entries");
+            let indent =
+                self.indent_of(&[&indices[..depth], &[first]].concat());
+            if indent.len() > widest.len() {
+                widest = indent;
+            }
+        }
+        widest
+    }
+
+    /// Line ending to use for new lines: the file's own, if it has one
+    fn newline(&self) -> &'static str {
+        if self.to_string().contains("\r\n") {
+            "\r\n"
+        } else {
+            "\n"
+        }
+    }
+}
+
+fn to_path(path: &[&str]) -> Vec<String> {
+    path.iter().map(|key| key.to_string()).collect()
+}
+
+fn entry_key(entry: &GreenNode) -> String {
+    let key = entry
+        .children
+        .iter()
+        .find(|child| child.kind() == SyntaxKind::Key)
+        .expect("Never: entries have a key");
+    let GreenElement::Node(key) = key else {
+        panic!("Never: keys are nodes");
+    };
+    trim_lines(&key.to_string()).into_owned()
+}
+
+fn position(children: &[GreenElement], kind: SyntaxKind) -> usize {
+    children
+        .iter()
+        .position(|child| child.kind() == kind)
+        .expect("Never: entries have a key, `=` and a value")
+}
+
+fn is_section(element: &GreenElement) -> bool {
+    match element {
+        GreenElement::Node(node) => node
+            .children
+            .iter()
+            .any(|child| child.kind() == SyntaxKind::Entry),
+        GreenElement::Token(_) => false,
+    }
+}
+
+fn reindent(value: &str, indent: &str, newline: &str) -> String {
+    value
+        .lines()
+        .enumerate()
+        .map(|(i, line)| {
+            if i == 0 || line.trim().is_empty() {
+                line.trim_end().to_string()
+            } else {
+                format!("{}{}", indent, line.trim())
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(newline)
+}
+
+fn token(kind: SyntaxKind, text: &str) -> GreenElement {
+    GreenElement::Token(GreenToken::new(kind, text))
+}
+
+/// Tokens of whitespace, line breaks and any other text in between
+fn tokens(text: &str, out: &mut Vec<GreenElement>) {
+    for piece in text.split_inclusive('\n') {
+        let (line, newline) = match piece.strip_suffix('\n') {
+            Some(line) => match line.strip_suffix('\r') {
+                Some(line) => (line, "\r\n"),
+                None => (line, "\n"),
+            },
+            None => (piece, ""),
+        };
+        let content = line.trim();
+        if content.is_empty() {
+            if !line.is_empty() {
+                out.push(token(SyntaxKind::Whitespace, line));
+            }
+        } else {
+            let leading = line.len() - line.trim_start().len();
+            if leading > 0 {
+                out.push(token(SyntaxKind::Whitespace, &line[..leading]));
+            }
+            out.push(token(SyntaxKind::Text, content));
+            let trailing = &line[leading + content.len()..];
+            if !trailing.is_empty() {
+                out.push(token(SyntaxKind::Whitespace, trailing));
+            }
+        }
+        if !newline.is_empty() {
+            out.push(token(SyntaxKind::Newline, newline));
+        }
+    }
+}
+
+/// Entries of `text` at `spans` and the trivia around them
+fn section(text: &str, spans: Vec<(Span, Span)>) -> Vec<GreenElement> {
+    let mut children = Vec::new();
+    let mut pos = 0;
+    for span in spans {
+        tokens(&text[pos..span.0.start], &mut children);
+        children.push(section_entry(text, span));
+        pos = span.1.end;
+    }
+    tokens(&text[pos..], &mut children);
+    children
+}
+
+fn section_entry(text: &str, (key, value): (Span, Span)) -> GreenElement {
+    let mut key_tokens = Vec::new();
+    tokens(&text[key.start..key.end], &mut key_tokens);
+    let mut children = vec![GreenElement::Node(GreenNode::new(
+        SyntaxKind::Key,
+        key_tokens,
+    ))];
+
+    let between = &text[key.end..value.start];
+    let eq = between
+        .find('=')
+        .expect("Never: `=` separates key and value");
+    tokens(&between[..eq], &mut children);
+    children.push(token(SyntaxKind::Eq, "="));
+    tokens(&between[eq + 1..], &mut children);
+
+    children.push(value_node(&text[value.start..value.end]));
+    GreenElement::Node(GreenNode::new(SyntaxKind::Entry, children))
+}
+
+/// Nested entries if `text` parses as such, like `parse_flat_to_tree`
+fn value_node(text: &str) -> GreenElement {
+    let children = match KeyVal::scan(text) {
+        Ok(spans) if !spans.is_empty() => section(text, spans),
+        Err(_) | Ok(_) => {
+            let mut children = Vec::new();
+            tokens(text, &mut children);
+            children
+        }
+    };
+    GreenElement::Node(GreenNode::new(SyntaxKind::Value, children))
+}
+
+/// An edit of one entry of a section, for `Cst::update_section`
+enum Edit<'a> {
+    Remove,
+    /// Set the value to this text
+    Set(&'a str),
+    /// Update the nested entries of the value
+    Update(&'a CCL),
+    /// Replace the value with one that reads as this
+    Rewrite(&'a CCL),
+    /// Add entries with this key after the entry, each reading as one of
+    /// the values
+    Insert(&'a str, Vec<CCL>),
+}
+
+fn value_of(entry: &GreenNode) -> &GreenNode {
+    match &entry.children[position(&entry.children, SyntaxKind::Value)] {
+        GreenElement::Node(value) => value,
+        GreenElement::Token(_) => panic!("Never: values are nodes"),
+    }
+}
+
+/// What `CCL::parse_with` reads the value `text` of an entry as
+fn read_value(text: &str, options: &ParseOptions) -> CCL {
+    let key_vals = vec![KeyVal {
+        key: String::new(),
+        value: trim_lines(text).into_owned(),
+    }];
+    let CCL(mut map) = CCL::parse_with(key_vals, options);
+    map.remove("").unwrap_or_else(CCL::empty)
+}
+
+/// The values of `value` if they are all text on one line that reads
+/// back as itself when written after an `=`, with nothing under them
+fn plain_values(value: &CCL) -> Option<Vec<&str>> {
+    let CCL(map) = value;
+    map.iter()
+        .map(|(key, CCL(nested))| {
+            let plain = !key.is_empty()
+                && key.trim() == key
+                && !key.contains(['=', '\n', '\r']);
+            (plain && nested.is_empty()).then_some(key.as_str())
+        })
+        .collect()
+}
+
+/// The text of `value` if it is a single plain value
+fn plain_value(value: &CCL) -> Option<&str> {
+    match plain_values(value)?.as_slice() {
+        [value] => Some(value),
+        _ => None,
+    }
+}
+
+/// Whether the value of `entry` starts on the line of its key
+fn is_inline(entry: &GreenNode) -> bool {
+    let value = value_of(entry);
+    value
+        .children
+        .iter()
+        .find(|child| child.kind() != SyntaxKind::Whitespace)
+        .is_some_and(|child| child.kind() != SyntaxKind::Newline)
+}
@@ -0,0 +1,530 @@
+//! Deserialize typed values from CCL.
+//!
+//! The mapping from a `CCL` node, the map of keys under some entry:
+//! - structs and maps: one field or entry per key
+//! - scalars (strings, numbers, chars, ...): a single key with nothing
+//!   under it, parsed with `FromStr`. An empty node is the empty string.
+//! - bools: a scalar, or an empty node for `true`, so `debug =` turns a
+//!   flag on
+//! - options: `None` for an empty node or a missing key
+//! - sequences: the items under the `""` key, as written with the `= item`
+//!   convention, or else every key. As a CCL is a map, items come out
+//!   sorted and without duplicates.
+//! - enums: a scalar for unit variants, or a single key with the variant's
+//!   content under it
+//!
+//! ```
+//! use serde::Deserialize;
+//!
+//! #[derive(Deserialize)]
+//! struct Config {
+//!     name: String,
+//!     ports: Vec<u16>,
+//!     debug: bool,
+//! }
+//!
+//! let config: Config = ccl_rs::de::from_str(
+//!     "name = api\nports =\n  = 8000\n  = 8001\ndebug =\n",
+//! )
+//! .unwrap();
+//! assert_eq!(config.ports, vec![8000, 8001]);
+//! assert!(config.debug);
+//! ```
+
+use crate::error::ParseError;
+use crate::parser::CCL;
+use serde::de::{
+    self, DeserializeOwned, DeserializeSeed, EnumAccess, IntoDeserializer,
+    MapAccess, SeqAccess, VariantAccess, Visitor,
+};
+use std::fmt::Display;
+use std::str::FromStr;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Error {
+    /// The text is not valid CCL
+    Parse(ParseError),
+    /// A value that does not fit the type asked for, or cannot be written
+    /// as CCL
+    Custom {
+        /// Keys leading to the value, from the outermost entry in
+        path: Vec<String>,
+        message: String,
+    },
+}
+
+impl Error {
+    /// Attach `path` to an error raised without one
+    pub(crate) fn at(self, path: &[String]) -> Self {
+        match self {
+            Error::Custom {
+                path: inner,
+                message,
+            } if inner.is_empty() => Error::Custom {
+                path: path.to_vec(),
+                message,
+            },
+            err => err,
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Error::Parse(err) => write!(f, "{}", err),
+            Error::Custom { path, message } if path.is_empty() => {
+                write!(f, "{}", message)
+            }
+            Error::Custom { path, message } => {
+                write!(f, "{} (in the value of {})", message, path.join("="))
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl de::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Error::Custom {
+            path: Vec::new(),
+            message: msg.to_string(),
+        }
+    }
+}
+
+impl From<ParseError> for Error {
+    fn from(err: ParseError) -> Self {
+        Error::Parse(err)
+    }
+}
+
+/// Parse `data` as CCL and deserialize a `T` from it
+pub fn from_str<T: DeserializeOwned>(data: &str) -> Result<T, Error> {
+    from_ccl(&CCL::parse_str(data)?)
+}
+
+/// Deserialize a `T` from a parsed CCL
+pub fn from_ccl<'de, T: de::Deserialize<'de>>(
+    ccl: &'de CCL,
+) -> Result<T, Error> {
+    T::deserialize(Deserializer::new(ccl, Vec::new()))
+}
+
+/// What a `Deserializer` reads from
+#[derive(Clone, Copy)]
+enum Node<'de> {
+    /// The map of keys under some entry
+    Map(&'de CCL),
+    /// A map with a single key, and the map under it
+    Entry(&'de str, &'de CCL),
+}
+
+/// Deserializes from one node of a CCL
+pub struct Deserializer<'de> {
+    node: Node<'de>,
+    /// Keys leading to the node
+    path: Vec<String>,
+}
+
+impl<'de> Deserializer<'de> {
+    pub fn new(ccl: &'de CCL, path: Vec<String>) -> Self {
+        Self {
+            node: Node::Map(ccl),
+            path,
+        }
+    }
+
+    fn error(&self, message: String) -> Error {
+        Error::Custom {
+            path: self.path.clone(),
+            message,
+        }
+    }
+
+    fn entries(&self) -> Vec<(&'de str, &'de CCL)> {
+        match self.node {
+            Node::Map(CCL(map)) => {
+                map.iter().map(|(key, ccl)| (key.as_str(), ccl)).collect()
+            }
+            Node::Entry(key, ccl) => vec![(key, ccl)],
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        match self.node {
+            Node::Map(CCL(map)) => map.is_empty(),
+            Node::Entry(..) => false,
+        }
+    }
+
+    /// The single key of a node with nothing under it, or `""` for an
+    /// empty node
+    fn scalar(&self) -> Result<&'de str, Error> {
+        match self.entries().as_slice() {
+            [] => Ok(""),
+            [(key, CCL(nested))] if nested.is_empty() => Ok(key),
+            [(key, _)] => Err(self.error(format!(
+                "expected a single value, found entries under `{}`",
+                key
+            ))),
+            entries => Err(self.error(format!(
+                "expected a single value, found {} keys",
+                entries.len()
+            ))),
+        }
+    }
+
+    fn parse<T>(&self) -> Result<T, Error>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        let scalar = self.scalar()?;
+        scalar.parse().map_err(|err| {
+            self.error(format!("invalid value `{}`: {}", scalar, err))
+        })
+    }
+
+    /// The items of a sequence: the entries under the `""` key if that is
+    /// the only key, or else all entries
+    fn items(&self) -> Vec<(&'de str, &'de CCL)> {
+        match self.entries().as_slice() {
+            [("", items)] => Deserializer::new(items, Vec::new()).entries(),
+            entries => entries.to_vec(),
+        }
+    }
+}
+
+macro_rules! deserialize_from_str {
+    ($($method:ident => $visit:ident),* $(,)?) => {
+        $(
+            fn $method<V: Visitor<'de>>(
+                self,
+                visitor: V,
+            ) -> Result<V::Value, Error> {
+                visitor.$visit(self.parse()?)
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for Deserializer<'de> {
+    type Error = Error;
+
+    /// Guess the type from the shape of the node: empty is unit, a single
+    /// value is a string, only a `""` key is a sequence, anything else is
+    /// a map
+    fn deserialize_any<V: Visitor<'de>>(
+        self,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        if self.is_empty() {
+            visitor.visit_unit()
+        } else if let Ok(scalar) = self.scalar() {
+            visitor.visit_borrowed_str(scalar)
+        } else if let [("", _)] = self.entries().as_slice() {
+            self.deserialize_seq(visitor)
+        } else {
+            self.deserialize_map(visitor)
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(
+        self,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        if self.is_empty() {
+            visitor.visit_bool(true)
+        } else {
+            visitor.visit_bool(self.parse()?)
+        }
+    }
+
+    deserialize_from_str! {
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_i128 => visit_i128,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_u128 => visit_u128,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64,
+        deserialize_char => visit_char,
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(
+        self,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_borrowed_str(self.scalar()?)
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(
+        self,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(
+        self,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_borrowed_bytes(self.scalar()?.as_bytes())
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(
+        self,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(
+        self,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        if self.is_empty() {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(
+        self,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        if self.is_empty() {
+            visitor.visit_unit()
+        } else {
+            Err(self.error("expected no value".into()))
+        }
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(
+        self,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_seq(Items {
+            items: self.items().into_iter(),
+            path: self.path,
+        })
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(
+        self,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_map(Entries {
+            entries: self.entries().into_iter(),
+            value: None,
+            path: self.path,
+        })
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_map(visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        match self.entries().as_slice() {
+            &[(variant, content)] => {
+                let mut path = self.path.clone();
+                path.push(variant.to_string());
+                visitor.visit_enum(Variant {
+                    variant,
+                    content: Deserializer::new(content, path),
+                })
+            }
+            entries => Err(self.error(format!(
+                "expected a single enum variant, found {} keys",
+                entries.len()
+            ))),
+        }
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(
+        self,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(
+        self,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+}
+
+/// The items of a sequence, each deserialized from a node with just its
+/// key and whatever is under it
+struct Items<'de> {
+    items: std::vec::IntoIter<(&'de str, &'de CCL)>,
+    path: Vec<String>,
+}
+
+impl<'de> SeqAccess<'de> for Items<'de> {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, Error> {
+        let Some((key, nested)) = self.items.next() else {
+            return Ok(None);
+        };
+        // Items have no key of their own, so errors are reported at the
+        // sequence
+        let item = Deserializer {
+            node: Node::Entry(key, nested),
+            path: self.path.clone(),
+        };
+        seed.deserialize(item)
+            .map(Some)
+            .map_err(|err| err.at(&self.path))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.items.len())
+    }
+}
+
+struct Entries<'de> {
+    entries: std::vec::IntoIter<(&'de str, &'de CCL)>,
+    /// Key and node of the entry whose key was just deserialized
+    value: Option<(&'de str, &'de CCL)>,
+    path: Vec<String>,
+}
+
+impl<'de> MapAccess<'de> for Entries<'de> {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(
+        &mut self,
+        seed: K,
+    ) -> Result<Option<K::Value>, Error> {
+        let Some((key, nested)) = self.entries.next() else {
+            return Ok(None);
+        };
+        self.value = Some((key, nested));
+        seed.deserialize(key.into_deserializer()).map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(
+        &mut self,
+        seed: V,
+    ) -> Result<V::Value, Error> {
+        let (key, nested) = self.value.take().expect("Never: value before key");
+        let mut path = self.path.clone();
+        path.push(key.to_string());
+        seed.deserialize(Deserializer::new(nested, path.clone()))
+            .map_err(|err| err.at(&path))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.entries.len())
+    }
+}
+
+struct Variant<'de> {
+    variant: &'de str,
+    content: Deserializer<'de>,
+}
+
+impl<'de> EnumAccess<'de> for Variant<'de> {
+    type Error = Error;
+    type Variant = Deserializer<'de>;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, Self::Variant), Error> {
+        let variant = seed.deserialize(
+            IntoDeserializer::<Error>::into_deserializer(self.variant),
+        )?;
+        Ok((variant, self.content))
+    }
+}
+
+impl<'de> VariantAccess<'de> for Deserializer<'de> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        de::Deserializer::deserialize_unit(self, de::IgnoredAny).map(|_| ())
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(
+        self,
+        seed: T,
+    ) -> Result<T::Value, Error> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(
+        self,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_seq(self, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        de::Deserializer::deserialize_map(self, visitor)
+    }
+}
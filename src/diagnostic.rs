@@ -0,0 +1,71 @@
+use crate::error::{Location, ParseError};
+use crate::span::Span;
+use std::fmt::Display;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    /// Text that parsed, but probably not as intended
+    Warning,
+    /// Text that is not valid CCL as a whole: a key with no value, which
+    /// is left out of the result, or indentation the options reject, whose
+    /// lines are still read as if the options allowed it
+    Error,
+}
+
+/// A problem found by a recovering parse
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Keys of the entries whose value the problem is in, outermost first
+    pub path: Vec<String>,
+    pub error: ParseError,
+}
+
+impl Diagnostic {
+    pub fn error(error: ParseError) -> Self {
+        Self {
+            severity: Severity::Error,
+            path: Vec::new(),
+            error,
+        }
+    }
+
+    pub fn warning(path: Vec<String>, error: ParseError) -> Self {
+        Self {
+            severity: Severity::Warning,
+            path,
+            error,
+        }
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+
+    pub fn location(&self) -> &Location {
+        self.error.location()
+    }
+
+    pub fn span(&self) -> Span {
+        self.error.span()
+    }
+
+    /// Attach the name of the file the diagnostic comes from
+    pub fn with_file(mut self, file: &str) -> Self {
+        self.error = self.error.with_file(file);
+        self
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.severity == Severity::Warning {
+            write!(f, "warning: ")?;
+        }
+        write!(f, "{}", self.error)?;
+        if !self.path.is_empty() {
+            write!(f, " (in the value of {})", self.path.join("="))?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,220 @@
+//! Structural differences between two `CCL` documents.
+//!
+//! Entries only one side has are added or removed with everything under
+//! them. An entry both have is compared further down, and where each side
+//! has values the other does not, the entry has changed.
+//!
+//! ```
+//! use ccl_rs::parser::CCL;
+//!
+//! let old = CCL::parse_str("port = 8080\ndebug =").unwrap();
+//! let new = CCL::parse_str("port = 443\nhost = example.com").unwrap();
+//! assert_eq!(
+//!     old.diff(&new).to_string(),
+//!     "- debug\n+ host=example.com\n~ port: 8080 -> 443\n"
+//! );
+//! ```
+
+use crate::parser::CCL;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{self, Display};
+
+/// One difference, at the path of keys of the entry it is about
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Change {
+    /// An entry only the new document has, with what is under it
+    Added { path: Vec<String>, value: CCL },
+    /// An entry only the old document has, with what was under it
+    Removed { path: Vec<String>, value: CCL },
+    /// The values of an entry that both sides have values of that the
+    /// other does not. Values both have are left out.
+    Changed {
+        path: Vec<String>,
+        old: Vec<String>,
+        new: Vec<String>,
+    },
+}
+
+impl Change {
+    pub fn path(&self) -> &[String] {
+        match self {
+            Change::Added { path, .. }
+            | Change::Removed { path, .. }
+            | Change::Changed { path, .. } => path,
+        }
+    }
+}
+
+/// Every difference between two documents, in key order
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Diff {
+    pub changes: Vec<Change>,
+}
+
+impl Diff {
+    pub fn new(old: &CCL, new: &CCL) -> Self {
+        let mut changes = Vec::new();
+        diff_at(&mut Vec::new(), old, new, &mut changes);
+        Self { changes }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// One line per entry to remove or add, a `-` or `+` and the keys of
+    /// its path separated by tabs. Removing an entry removes everything
+    /// under it, adding one adds the entries along its path. A backslash,
+    /// tab or line break in a key is escaped as `\\`, `\t` or `\n`.
+    pub fn porcelain(&self) -> String {
+        let mut out = String::new();
+        for change in &self.changes {
+            match change {
+                Change::Added { path, value } => {
+                    for leaf in leaf_paths(path, value) {
+                        porcelain_line(&mut out, '+', &leaf);
+                    }
+                }
+                Change::Removed { path, .. } => {
+                    porcelain_line(&mut out, '-', path);
+                }
+                Change::Changed { path, old, new } => {
+                    for (sign, values) in [('-', old), ('+', new)] {
+                        for value in values {
+                            let mut path = path.clone();
+                            path.push(value.clone());
+                            porcelain_line(&mut out, sign, &path);
+                        }
+                    }
+                }
+            }
+        }
+        out
+    }
+}
+
+/// One line per value added or removed, with the path leading to it
+/// joined by `=`, and one per changed entry with its old and new values
+impl Display for Diff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for change in &self.changes {
+            match change {
+                Change::Added { path, value } => {
+                    for leaf in leaf_paths(path, value) {
+                        writeln!(f, "+ {}", leaf.join("="))?;
+                    }
+                }
+                Change::Removed { path, value } => {
+                    for leaf in leaf_paths(path, value) {
+                        writeln!(f, "- {}", leaf.join("="))?;
+                    }
+                }
+                Change::Changed { path, old, new } => writeln!(
+                    f,
+                    "~ {}: {} -> {}",
+                    path.join("="),
+                    old.join(", "),
+                    new.join(", ")
+                )?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl CCL {
+    /// What changes from `self` to `other`; see [`crate::diff`]
+    pub fn diff(&self, other: &CCL) -> Diff {
+        Diff::new(self, other)
+    }
+}
+
+fn diff_at(
+    path: &mut Vec<String>,
+    old: &CCL,
+    new: &CCL,
+    changes: &mut Vec<Change>,
+) {
+    let (CCL(old), CCL(new)) = (old, new);
+    let old_values = values_only(old, new);
+    let new_values = values_only(new, old);
+    // The top level is no entry to have changed
+    let changed =
+        !path.is_empty() && !old_values.is_empty() && !new_values.is_empty();
+    if changed {
+        changes.push(Change::Changed {
+            path: path.clone(),
+            old: old_values,
+            new: new_values,
+        });
+    }
+
+    let keys = old.keys().chain(new.keys()).collect::<BTreeSet<_>>();
+    for key in keys {
+        path.push(key.clone());
+        match (old.get(key), new.get(key)) {
+            (Some(old), Some(new)) => diff_at(path, old, new, changes),
+            (Some(CCL(map)), None) if changed && map.is_empty() => {}
+            (None, Some(CCL(map))) if changed && map.is_empty() => {}
+            (Some(old), None) => changes.push(Change::Removed {
+                path: path.clone(),
+                value: old.clone(),
+            }),
+            (None, Some(new)) => changes.push(Change::Added {
+                path: path.clone(),
+                value: new.clone(),
+            }),
+            (None, None) => unreachable!("key from neither side"),
+        }
+        path.pop();
+    }
+}
+
+/// The values, keys with nothing under them, of `map` that `other` does
+/// not have
+fn values_only(
+    map: &BTreeMap<String, CCL>,
+    other: &BTreeMap<String, CCL>,
+) -> Vec<String> {
+    map.iter()
+        .filter(|(key, CCL(nested))| {
+            nested.is_empty() && !other.contains_key(*key)
+        })
+        .map(|(key, _)| key.clone())
+        .collect()
+}
+
+/// The paths of the entries with nothing under them in `value`, the map
+/// under `path`
+fn leaf_paths(path: &[String], value: &CCL) -> Vec<Vec<String>> {
+    let leaves = value
+        .leaves()
+        .map(|leaf| {
+            path.iter()
+                .cloned()
+                .chain(leaf.into_iter().map(String::from))
+                .collect()
+        })
+        .collect::<Vec<_>>();
+    if leaves.is_empty() {
+        vec![path.to_vec()]
+    } else {
+        leaves
+    }
+}
+
+fn porcelain_line(out: &mut String, sign: char, path: &[String]) {
+    out.push(sign);
+    for key in path {
+        out.push('\t');
+        for c in key.chars() {
+            match c {
+                '\\' => out.push_str("\\\\"),
+                '\t' => out.push_str("\\t"),
+                '\n' => out.push_str("\\n"),
+                c => out.push(c),
+            }
+        }
+    }
+    out.push('\n');
+}
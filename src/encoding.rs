@@ -0,0 +1,104 @@
+use crate::error::{Location, ParseError};
+use crate::span::Span;
+use std::borrow::Cow;
+
+/// Byte order mark some Windows editors put at the start of UTF-8 files
+pub const BOM: &str = "\u{feff}";
+
+/// How lines are terminated in a source file
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    CrLf,
+}
+
+impl LineEnding {
+    /// The style of the first line break in `text`, `Lf` if there is none
+    pub fn detect(text: &str) -> Self {
+        match text.find('\n') {
+            Some(i) if text[..i].ends_with('\r') => LineEnding::CrLf,
+            _ => LineEnding::Lf,
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::CrLf => "\r\n",
+        }
+    }
+
+    /// Rewrite the `\n` line breaks of `text` in this style
+    pub fn apply<'a>(&self, text: &'a str) -> Cow<'a, str> {
+        match self {
+            LineEnding::Lf => Cow::Borrowed(text),
+            LineEnding::CrLf => Cow::Owned(text.replace('\n', "\r\n")),
+        }
+    }
+}
+
+/// Source text decoded from bytes, with what was normalised away
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Decoded<'a> {
+    /// The text without a BOM and with `\n` line breaks
+    pub text: Cow<'a, str>,
+    pub line_ending: LineEnding,
+    /// Whether the input started with a BOM
+    pub bom: bool,
+}
+
+/// Decode UTF-8 `bytes`, dropping a leading BOM and turning `\r\n` into
+/// `\n`. Borrows `bytes` when there is nothing to normalise.
+///
+/// Invalid UTF-8 is reported at its byte offset in `bytes`, BOM included.
+pub fn decode(bytes: &[u8]) -> Result<Decoded<'_>, ParseError> {
+    let text = std::str::from_utf8(bytes).map_err(|e| {
+        let start = e.valid_up_to();
+        let end = start + e.error_len().unwrap_or(bytes.len() - start);
+        // Everything before the error is valid, so it can be used for
+        // working out the line and column
+        let valid = std::str::from_utf8(&bytes[..start]).unwrap_or_default();
+        ParseError::InvalidUtf8 {
+            location: Location::new(valid, Span::new(start, end)),
+        }
+    })?;
+
+    let (text, bom) = match text.strip_prefix(BOM) {
+        Some(text) => (text, true),
+        None => (text, false),
+    };
+    let line_ending = LineEnding::detect(text);
+    let text = if text.contains("\r\n") {
+        Cow::Owned(text.replace("\r\n", "\n"))
+    } else {
+        Cow::Borrowed(text)
+    };
+
+    Ok(Decoded {
+        text,
+        line_ending,
+        bom,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_borrows_plain_text() {
+        let decoded = decode(b"a = b\nc = d\n").unwrap();
+        assert!(matches!(decoded.text, Cow::Borrowed("a = b\nc = d\n")));
+        assert_eq!(decoded.line_ending, LineEnding::Lf);
+        assert!(!decoded.bom);
+    }
+
+    #[test]
+    fn test_line_ending_roundtrip() {
+        let text = "a =\r\n  b = c\r\n";
+        let decoded = decode(text.as_bytes()).unwrap();
+        assert_eq!(decoded.line_ending, LineEnding::CrLf);
+        assert_eq!(decoded.line_ending.apply(&decoded.text), text);
+    }
+}
@@ -0,0 +1,418 @@
+use crate::span::{LineCol, Span};
+use std::fmt::Display;
+use std::path::PathBuf;
+
+/// Where in the source a problem was found
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Location {
+    /// Name of the file being parsed, if known
+    pub file: Option<String>,
+    /// 1-based line of `span.start`
+    pub line: usize,
+    /// 1-based column (in chars) of `span.start`
+    pub column: usize,
+    /// Byte range of the offending text
+    pub span: Span,
+}
+
+impl Location {
+    pub fn new(src: &str, span: Span) -> Self {
+        let LineCol { line, column } = LineCol::of_offset(src, span.start);
+        Self {
+            file: None,
+            line,
+            column,
+            span,
+        }
+    }
+}
+
+impl Display for Location {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let file = self.file.as_deref().unwrap_or("<input>");
+        write!(f, "{}:{}:{}", file, self.line, self.column)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ParseError {
+    /// A key with no `=` before the end of input
+    UnclosedKey { key: String, location: Location },
+    /// Indentation that cannot be interpreted
+    BadIndentation { message: String, location: Location },
+    /// Input bytes that are not valid UTF-8
+    InvalidUtf8 { location: Location },
+    /// Input that exceeds a configured limit
+    LimitExceeded {
+        limit: &'static str,
+        max: usize,
+        location: Location,
+    },
+    /// An error in the value of the entry at `path`, a list of keys from
+    /// the outermost entry in
+    InValue {
+        path: Vec<String>,
+        error: Box<ParseError>,
+    },
+}
+
+impl ParseError {
+    pub fn location(&self) -> &Location {
+        match self {
+            ParseError::UnclosedKey { location, .. }
+            | ParseError::BadIndentation { location, .. }
+            | ParseError::InvalidUtf8 { location }
+            | ParseError::LimitExceeded { location, .. } => location,
+            ParseError::InValue { error, .. } => error.location(),
+        }
+    }
+
+    fn location_mut(&mut self) -> &mut Location {
+        match self {
+            ParseError::UnclosedKey { location, .. }
+            | ParseError::BadIndentation { location, .. }
+            | ParseError::InvalidUtf8 { location }
+            | ParseError::LimitExceeded { location, .. } => location,
+            ParseError::InValue { error, .. } => error.location_mut(),
+        }
+    }
+
+    /// Place the error in the value of the entry with key `key`
+    pub fn in_value(self, key: &str) -> Self {
+        match self {
+            ParseError::InValue { mut path, error } => {
+                path.insert(0, key.to_string());
+                ParseError::InValue { path, error }
+            }
+            error => ParseError::InValue {
+                path: vec![key.to_string()],
+                error: Box::new(error),
+            },
+        }
+    }
+
+    pub fn span(&self) -> Span {
+        self.location().span
+    }
+
+    /// Move the error from a piece of `src` starting at `offset` onto
+    /// `src` itself
+    pub(crate) fn rebased(mut self, src: &str, offset: usize) -> Self {
+        let location = self.location_mut();
+        let file = location.file.take();
+        *location = Location {
+            file,
+            ..Location::new(src, location.span.shifted(offset))
+        };
+        self
+    }
+
+    /// Attach the name of the file the error comes from
+    pub fn with_file(mut self, file: &str) -> Self {
+        self.location_mut().file = Some(file.to_string());
+        self
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseError::UnclosedKey { key, .. } => {
+                write!(f, "No value found for key: {}", key)
+            }
+            ParseError::BadIndentation { message, .. } => {
+                write!(f, "Bad indentation: {}", message)
+            }
+            ParseError::InvalidUtf8 { location } => {
+                write!(f, "Invalid UTF-8 at byte {}", location.span.start)
+            }
+            ParseError::LimitExceeded { limit, max, .. } => {
+                write!(f, "Limit exceeded: {} (max {})", limit, max)
+            }
+            ParseError::InValue { path, error } => {
+                write!(f, "{} (in the value of {})", error, path.join("="))
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// A failed lookup in a `CCL`. Each variant carries the keys leading to
+/// the entry at fault, from the outermost entry in.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum AccessError {
+    /// No entry with the last key of `path`
+    Missing { path: Vec<String> },
+    /// More than one value where one was expected
+    Multiple {
+        path: Vec<String>,
+        values: Vec<String>,
+    },
+    /// A value with entries under it where a plain value was expected
+    Nested { path: Vec<String>, value: String },
+    /// A value that could not be converted to the type asked for
+    Invalid {
+        path: Vec<String>,
+        value: String,
+        message: String,
+    },
+}
+
+impl AccessError {
+    pub fn path(&self) -> &[String] {
+        match self {
+            AccessError::Missing { path }
+            | AccessError::Multiple { path, .. }
+            | AccessError::Nested { path, .. }
+            | AccessError::Invalid { path, .. } => path,
+        }
+    }
+}
+
+impl Display for AccessError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let path = self.path();
+        match self {
+            AccessError::Missing { .. } => {
+                let (key, parents) =
+                    path.split_last().expect("Never: missing empty path");
+                write!(f, "Key '{}' not found", key)?;
+                if !parents.is_empty() {
+                    write!(f, " (in the value of {})", parents.join("="))?;
+                }
+                return Ok(());
+            }
+            AccessError::Multiple { values, .. } => {
+                write!(
+                    f,
+                    "Expected one value, found {}: {}",
+                    values.len(),
+                    values.join(", ")
+                )?;
+            }
+            AccessError::Nested { value, .. } => {
+                write!(
+                    f,
+                    "Expected a plain value, found entries under '{}'",
+                    value
+                )?;
+            }
+            AccessError::Invalid { value, message, .. } => {
+                write!(f, "Invalid value '{}': {}", value, message)?;
+            }
+        }
+        if !path.is_empty() {
+            write!(f, " (in the value of {})", path.join("="))?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for AccessError {}
+
+/// A query that cannot be parsed
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum QueryError {
+    /// Malformed query text, at byte `position` of the query
+    Syntax { position: usize, message: String },
+    /// A `/regex/` segment that does not compile
+    InvalidRegex { pattern: String, message: String },
+}
+
+impl Display for QueryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            QueryError::Syntax { position, message } => {
+                write!(f, "Invalid query at byte {}: {}", position, message)
+            }
+            QueryError::InvalidRegex { pattern, message } => {
+                write!(f, "Invalid regex /{}/: {}", pattern, message)
+            }
+        }
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+/// A filter that cannot be parsed or fails on its input
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FilterError {
+    /// Malformed filter text, at byte `position` of the filter
+    Syntax { position: usize, message: String },
+    /// A filter applied to a value it does not work on
+    Eval { message: String },
+}
+
+impl Display for FilterError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FilterError::Syntax { position, message } => {
+                write!(f, "Invalid filter at byte {}: {}", position, message)
+            }
+            FilterError::Eval { message } => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for FilterError {}
+
+/// A patch that cannot be read, or does not apply to a `CCL`. Each variant
+/// but `Invalid` carries the keys leading to the entry at fault.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum PatchError {
+    /// A patch document not in the form `Patch::to_ccl` writes
+    Invalid { message: String },
+    /// An entry to remove that is not there
+    Missing { path: Vec<String> },
+    /// An entry to remove with other entries under it than in the patch
+    Changed { path: Vec<String> },
+    /// An entry to add that is there already
+    Exists { path: Vec<String> },
+    /// An entry to add under one that is not there
+    NoParent { path: Vec<String> },
+}
+
+impl Display for PatchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (path, action, reason) = match self {
+            PatchError::Invalid { message } => {
+                return write!(f, "Invalid patch: {}", message);
+            }
+            PatchError::Missing { path } => (path, "remove", "it is not there"),
+            PatchError::Changed { path } => {
+                (path, "remove", "it has other values than in the patch")
+            }
+            PatchError::Exists { path } => (path, "add", "it is there already"),
+            PatchError::NoParent { path } => {
+                (path, "add", "there is no entry to add it to")
+            }
+        };
+        let (key, parents) =
+            path.split_last().expect("Never: patch of an empty path");
+        write!(f, "Cannot {} '{}', {}", action, key, reason)?;
+        if !parents.is_empty() {
+            write!(f, " (in the value of {})", parents.join("="))?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for PatchError {}
+
+/// A schema document that does not describe a schema. Each variant carries
+/// the keys leading to the entry of the schema at fault.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum SchemaError {
+    /// A key, type or setting the schema language does not have
+    Invalid { path: Vec<String>, message: String },
+    /// A `pattern` that does not compile
+    InvalidRegex {
+        path: Vec<String>,
+        pattern: String,
+        message: String,
+    },
+}
+
+impl SchemaError {
+    pub fn path(&self) -> &[String] {
+        match self {
+            SchemaError::Invalid { path, .. }
+            | SchemaError::InvalidRegex { path, .. } => path,
+        }
+    }
+}
+
+impl Display for SchemaError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SchemaError::Invalid { message, .. } => {
+                write!(f, "Invalid schema: {}", message)?;
+            }
+            SchemaError::InvalidRegex {
+                pattern, message, ..
+            } => {
+                write!(f, "Invalid regex /{}/: {}", pattern, message)?;
+            }
+        }
+        let path = self.path();
+        if !path.is_empty() {
+            write!(f, " (in the value of {})", path.join("="))?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for SchemaError {}
+
+/// A file that cannot be loaded with the files it includes. `chain` is the
+/// files from the one loaded first to the one at fault, each included by
+/// the one before.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum IncludeError {
+    /// A file that cannot be read
+    Io {
+        chain: Vec<PathBuf>,
+        message: String,
+    },
+    /// A file that cannot be parsed, with the file set in its location
+    Parse {
+        chain: Vec<PathBuf>,
+        error: ParseError,
+    },
+    /// An `@include` entry that is not a list of paths
+    Invalid {
+        chain: Vec<PathBuf>,
+        message: String,
+    },
+    /// A file that includes itself, the last of `chain`, directly or not
+    Cycle { chain: Vec<PathBuf> },
+    /// Includes nested deeper than `max`
+    TooDeep { chain: Vec<PathBuf>, max: usize },
+}
+
+impl IncludeError {
+    pub fn chain(&self) -> &[PathBuf] {
+        match self {
+            IncludeError::Io { chain, .. }
+            | IncludeError::Parse { chain, .. }
+            | IncludeError::Invalid { chain, .. }
+            | IncludeError::Cycle { chain }
+            | IncludeError::TooDeep { chain, .. } => chain,
+        }
+    }
+}
+
+impl Display for IncludeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let chain = self
+            .chain()
+            .iter()
+            .map(|path| path.display().to_string())
+            .collect::<Vec<_>>();
+        let file = chain.last().map_or("<input>", String::as_str);
+        match self {
+            IncludeError::Io { message, .. } => {
+                write!(f, "Failed to read file '{}': {}", file, message)?;
+            }
+            IncludeError::Parse { error, .. } => {
+                write!(f, "{}: {}", error.location(), error)?;
+            }
+            IncludeError::Invalid { message, .. } => {
+                write!(f, "Invalid include in '{}': {}", file, message)?;
+            }
+            IncludeError::Cycle { .. } => {
+                return write!(f, "Include cycle: {}", chain.join(" -> "));
+            }
+            IncludeError::TooDeep { max, .. } => {
+                write!(f, "Includes nested deeper than {}", max)?;
+            }
+        }
+        if chain.len() > 1 {
+            write!(f, " (include chain: {})", chain.join(" -> "))?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for IncludeError {}
@@ -0,0 +1,533 @@
+//! A small jq-like language for transforming a `CCL`.
+//!
+//! A filter takes one value and produces any number of values, which are
+//! maps (`CCL`s), text, numbers or booleans. Filters:
+//! - `.`: the input
+//! - `.key`, `."any key"`: the map under `key`, or nothing if it is missing.
+//!   Chains, as in `.database.port`.
+//! - `f | g`: `g` applied to every output of `f`
+//! - `keys`: every key of a map, as text
+//! - `values`: the map under every key
+//! - `length`: the number of keys of a map, or of characters of text
+//! - `first`: the first entry of a map, or `first(f)`: the first output of
+//!   `f`
+//! - `raw`: the value of a map with a single key and nothing under it, as
+//!   text
+//! - `select(f)`: the input if `f` has an output that is not `false`
+//! - `map(f)`: the input with `f` applied under every key, dropping keys
+//!   where it has no output
+//! - `f == g`, `f != g`: compare outputs; a map with a single key and
+//!   nothing under it equals its key as text
+//! - `not`, `"text"`, `42`, `(f)`
+//!
+//! ```
+//! use ccl_rs::filter::Filter;
+//! use ccl_rs::parser::CCL;
+//!
+//! let ccl = CCL::parse_str(
+//!     "db =\n  main =\n    on = true\n  cache =\n    on = false\n",
+//! )
+//! .unwrap();
+//! let filter = Filter::parse(".db | map(select(.on == \"true\")) | keys")
+//!     .unwrap();
+//! let outputs = filter.eval(&ccl).unwrap();
+//! assert_eq!(outputs[0].to_string(), "main");
+//! ```
+
+use crate::error::FilterError;
+use crate::monoid::Monoid;
+use crate::parser::CCL;
+use std::collections::BTreeMap;
+use std::fmt::Display;
+
+/// A value passed between filters
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Value {
+    Ccl(CCL),
+    Text(String),
+    Number(usize),
+    Bool(bool),
+}
+
+impl Value {
+    /// The text a value stands for, if it is a single value
+    fn scalar(&self) -> Option<String> {
+        match self {
+            Value::Ccl(ccl) => ccl.get_str(&[]).ok().map(str::to_string),
+            Value::Text(text) => Some(text.clone()),
+            Value::Number(n) => Some(n.to_string()),
+            Value::Bool(b) => Some(b.to_string()),
+        }
+    }
+
+    fn loosely_equals(&self, other: &Value) -> bool {
+        match (self, other) {
+            (Value::Ccl(lhs), Value::Ccl(rhs)) => lhs == rhs,
+            _ => self.scalar().is_some() && self.scalar() == other.scalar(),
+        }
+    }
+
+    fn is_truthy(&self) -> bool {
+        !matches!(self, Value::Bool(false))
+    }
+
+    fn kind(&self) -> &'static str {
+        match self {
+            Value::Ccl(_) => "a map",
+            Value::Text(_) => "text",
+            Value::Number(_) => "a number",
+            Value::Bool(_) => "a boolean",
+        }
+    }
+
+    fn into_ccl(self) -> CCL {
+        match self {
+            Value::Ccl(ccl) => ccl,
+            value => CCL::key(&value.to_string()),
+        }
+    }
+}
+
+/// Maps as CCL text, other values as they are
+impl Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Ccl(ccl) => write!(f, "{}", ccl.pretty()),
+            Value::Text(text) => write!(f, "{}", text),
+            Value::Number(n) => write!(f, "{}", n),
+            Value::Bool(b) => write!(f, "{}", b),
+        }
+    }
+}
+
+/// A parsed filter
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Filter {
+    Identity,
+    Field(String),
+    Pipe(Box<Filter>, Box<Filter>),
+    Literal(Value),
+    Compare {
+        lhs: Box<Filter>,
+        rhs: Box<Filter>,
+        equal: bool,
+    },
+    Keys,
+    Values,
+    Length,
+    First,
+    FirstOf(Box<Filter>),
+    Raw,
+    Select(Box<Filter>),
+    Map(Box<Filter>),
+    Not,
+}
+
+impl Filter {
+    pub fn parse(filter: &str) -> Result<Self, FilterError> {
+        let mut parser = Parser {
+            tokens: tokenize(filter)?,
+            position: 0,
+            end: filter.len(),
+        };
+        let parsed = parser.pipe()?;
+        match parser.tokens.get(parser.position) {
+            None => Ok(parsed),
+            Some((offset, token)) => Err(syntax_error(
+                *offset,
+                format!("unexpected {}", token.describe()),
+            )),
+        }
+    }
+
+    /// Apply the filter to `ccl`
+    pub fn eval(&self, ccl: &CCL) -> Result<Vec<Value>, FilterError> {
+        self.apply(&Value::Ccl(ccl.clone()))
+    }
+
+    /// Apply the filter to `input`
+    pub fn apply(&self, input: &Value) -> Result<Vec<Value>, FilterError> {
+        match self {
+            Filter::Identity => Ok(vec![input.clone()]),
+            Filter::Field(key) => {
+                let CCL(map) = expect_ccl(input, &format!(".{}", key))?;
+                Ok(map.get(key).cloned().map(Value::Ccl).into_iter().collect())
+            }
+            Filter::Pipe(lhs, rhs) => {
+                let mut outputs = Vec::new();
+                for value in lhs.apply(input)? {
+                    outputs.extend(rhs.apply(&value)?);
+                }
+                Ok(outputs)
+            }
+            Filter::Literal(value) => Ok(vec![value.clone()]),
+            Filter::Compare { lhs, rhs, equal } => {
+                let rhs = rhs.apply(input)?;
+                let mut outputs = Vec::new();
+                for l in lhs.apply(input)? {
+                    for r in &rhs {
+                        outputs
+                            .push(Value::Bool(l.loosely_equals(r) == *equal));
+                    }
+                }
+                Ok(outputs)
+            }
+            Filter::Keys => {
+                let CCL(map) = expect_ccl(input, "keys")?;
+                Ok(map.keys().cloned().map(Value::Text).collect())
+            }
+            Filter::Values => {
+                let CCL(map) = expect_ccl(input, "values")?;
+                Ok(map.values().cloned().map(Value::Ccl).collect())
+            }
+            Filter::Length => match input {
+                Value::Ccl(CCL(map)) => Ok(vec![Value::Number(map.len())]),
+                Value::Text(text) => {
+                    Ok(vec![Value::Number(text.chars().count())])
+                }
+                value => Err(eval_error("length", value)),
+            },
+            Filter::First => {
+                let CCL(map) = expect_ccl(input, "first")?;
+                Ok(map
+                    .iter()
+                    .next()
+                    .map(|(key, nested)| {
+                        Value::Ccl(CCL(BTreeMap::from([(
+                            key.clone(),
+                            nested.clone(),
+                        )])))
+                    })
+                    .into_iter()
+                    .collect())
+            }
+            Filter::FirstOf(filter) => {
+                Ok(filter.apply(input)?.into_iter().take(1).collect())
+            }
+            Filter::Raw => match input {
+                Value::Ccl(ccl) => match ccl.get_str(&[]) {
+                    Ok(text) => Ok(vec![Value::Text(text.to_string())]),
+                    Err(err) => Err(FilterError::Eval {
+                        message: format!("raw: {}", err),
+                    }),
+                },
+                value => Ok(vec![Value::Text(value.to_string())]),
+            },
+            Filter::Select(filter) => {
+                let selected =
+                    filter.apply(input)?.iter().any(Value::is_truthy);
+                Ok(selected.then(|| input.clone()).into_iter().collect())
+            }
+            Filter::Map(filter) => {
+                let CCL(map) = expect_ccl(input, "map")?;
+                let mut mapped = BTreeMap::new();
+                for (key, nested) in map {
+                    let outputs = filter.apply(&Value::Ccl(nested.clone()))?;
+                    if !outputs.is_empty() {
+                        let merged = CCL::aggregate(
+                            outputs.into_iter().map(Value::into_ccl).collect(),
+                        );
+                        mapped.insert(key.clone(), merged);
+                    }
+                }
+                Ok(vec![Value::Ccl(CCL(mapped))])
+            }
+            Filter::Not => Ok(vec![Value::Bool(!input.is_truthy())]),
+        }
+    }
+}
+
+impl std::str::FromStr for Filter {
+    type Err = FilterError;
+
+    fn from_str(filter: &str) -> Result<Self, FilterError> {
+        Filter::parse(filter)
+    }
+}
+
+fn expect_ccl<'a>(
+    input: &'a Value,
+    filter: &str,
+) -> Result<&'a CCL, FilterError> {
+    match input {
+        Value::Ccl(ccl) => Ok(ccl),
+        value => Err(eval_error(filter, value)),
+    }
+}
+
+fn eval_error(filter: &str, value: &Value) -> FilterError {
+    FilterError::Eval {
+        message: format!(
+            "{}: cannot apply to {} ({})",
+            filter,
+            value.kind(),
+            value
+        ),
+    }
+}
+
+fn syntax_error(position: usize, message: String) -> FilterError {
+    FilterError::Syntax { position, message }
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Token {
+    Dot,
+    /// `.key` or `."key"`
+    Field(String),
+    Ident(String),
+    Str(String),
+    Number(usize),
+    Pipe,
+    Equal,
+    NotEqual,
+    LParen,
+    RParen,
+}
+
+impl Token {
+    fn describe(&self) -> String {
+        match self {
+            Token::Dot => "`.`".into(),
+            Token::Field(key) => format!("`.{}`", key),
+            Token::Ident(ident) => format!("`{}`", ident),
+            Token::Str(text) => format!("\"{}\"", text),
+            Token::Number(n) => format!("`{}`", n),
+            Token::Pipe => "`|`".into(),
+            Token::Equal => "`==`".into(),
+            Token::NotEqual => "`!=`".into(),
+            Token::LParen => "`(`".into(),
+            Token::RParen => "`)`".into(),
+        }
+    }
+}
+
+fn is_ident(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '-'
+}
+
+fn tokenize(filter: &str) -> Result<Vec<(usize, Token)>, FilterError> {
+    let mut tokens = Vec::new();
+    let mut chars = filter.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        let token = match c {
+            c if c.is_whitespace() => continue,
+            '|' => Token::Pipe,
+            '(' => Token::LParen,
+            ')' => Token::RParen,
+            '=' | '!' if chars.next_if(|&(_, c)| c == '=').is_some() => {
+                if c == '=' {
+                    Token::Equal
+                } else {
+                    Token::NotEqual
+                }
+            }
+            '"' => Token::Str(string(i, &mut chars)?),
+            '.' => match chars.peek() {
+                Some(&(j, '"')) => {
+                    chars.next();
+                    Token::Field(string(j, &mut chars)?)
+                }
+                Some(&(_, c)) if is_ident(c) => {
+                    Token::Field(ident(filter, &mut chars))
+                }
+                _ => Token::Dot,
+            },
+            c if c.is_ascii_digit() => {
+                let digits = format!("{}{}", c, ident(filter, &mut chars));
+                let n = digits.parse().map_err(|_| {
+                    syntax_error(i, format!("invalid number `{}`", digits))
+                })?;
+                Token::Number(n)
+            }
+            c if is_ident(c) => {
+                Token::Ident(format!("{}{}", c, ident(filter, &mut chars)))
+            }
+            c => {
+                return Err(syntax_error(
+                    i,
+                    format!("unexpected character `{}`", c),
+                ));
+            }
+        };
+        tokens.push((i, token));
+    }
+    Ok(tokens)
+}
+
+type Chars<'a> = std::iter::Peekable<std::str::CharIndices<'a>>;
+
+fn ident(filter: &str, chars: &mut Chars) -> String {
+    let start = chars.peek().map_or(filter.len(), |&(i, _)| i);
+    let mut end = start;
+    while let Some((i, c)) = chars.next_if(|&(_, c)| is_ident(c)) {
+        end = i + c.len_utf8();
+    }
+    filter[start..end].to_string()
+}
+
+/// The rest of a string whose opening quote is at `start`, with `\"` and
+/// `\\` unescaped
+fn string(start: usize, chars: &mut Chars) -> Result<String, FilterError> {
+    let mut text = String::new();
+    while let Some((_, c)) = chars.next() {
+        match c {
+            '"' => return Ok(text),
+            '\\' => match chars.next() {
+                Some((_, c @ ('"' | '\\'))) => text.push(c),
+                Some((_, c)) => text.extend(['\\', c]),
+                None => break,
+            },
+            c => text.push(c),
+        }
+    }
+    Err(syntax_error(start, "unclosed string".into()))
+}
+
+struct Parser {
+    tokens: Vec<(usize, Token)>,
+    position: usize,
+    /// Length of the filter, where errors at the end are reported
+    end: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position).map(|(_, token)| token)
+    }
+
+    fn offset(&self) -> usize {
+        self.tokens
+            .get(self.position)
+            .map_or(self.end, |(offset, _)| *offset)
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        if self.peek() == Some(token) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, token: Token) -> Result<(), FilterError> {
+        if self.eat(&token) {
+            return Ok(());
+        }
+        let found = self
+            .peek()
+            .map_or("the end of the filter".into(), Token::describe);
+        Err(syntax_error(
+            self.offset(),
+            format!("expected {}, found {}", token.describe(), found),
+        ))
+    }
+
+    /// `comparison ('|' comparison)*`
+    fn pipe(&mut self) -> Result<Filter, FilterError> {
+        let mut filter = self.comparison()?;
+        while self.eat(&Token::Pipe) {
+            let rhs = self.comparison()?;
+            filter = Filter::Pipe(Box::new(filter), Box::new(rhs));
+        }
+        Ok(filter)
+    }
+
+    /// `term (('==' | '!=') term)?`
+    fn comparison(&mut self) -> Result<Filter, FilterError> {
+        let lhs = self.term()?;
+        let equal = if self.eat(&Token::Equal) {
+            true
+        } else if self.eat(&Token::NotEqual) {
+            false
+        } else {
+            return Ok(lhs);
+        };
+        let rhs = self.term()?;
+        Ok(Filter::Compare {
+            lhs: Box::new(lhs),
+            rhs: Box::new(rhs),
+            equal,
+        })
+    }
+
+    /// A primary filter followed by any number of `.key` lookups
+    fn term(&mut self) -> Result<Filter, FilterError> {
+        let mut filter = self.primary()?;
+        while let Some(Token::Field(key)) = self.peek() {
+            let field = Filter::Field(key.clone());
+            self.position += 1;
+            filter = Filter::Pipe(Box::new(filter), Box::new(field));
+        }
+        Ok(filter)
+    }
+
+    fn primary(&mut self) -> Result<Filter, FilterError> {
+        let offset = self.offset();
+        let Some((_, token)) = self.tokens.get(self.position).cloned() else {
+            return Err(syntax_error(offset, "expected a filter".into()));
+        };
+        self.position += 1;
+        let filter = match token {
+            Token::Dot => Filter::Identity,
+            Token::Field(key) => Filter::Field(key),
+            Token::Str(text) => Filter::Literal(Value::Text(text)),
+            Token::Number(n) => Filter::Literal(Value::Number(n)),
+            Token::LParen => {
+                let filter = self.pipe()?;
+                self.expect(Token::RParen)?;
+                filter
+            }
+            Token::Ident(name) => self.builtin(offset, &name)?,
+            token => {
+                return Err(syntax_error(
+                    offset,
+                    format!("expected a filter, found {}", token.describe()),
+                ));
+            }
+        };
+        Ok(filter)
+    }
+
+    fn builtin(
+        &mut self,
+        offset: usize,
+        name: &str,
+    ) -> Result<Filter, FilterError> {
+        let filter = match name {
+            "keys" => Filter::Keys,
+            "values" => Filter::Values,
+            "length" => Filter::Length,
+            "raw" => Filter::Raw,
+            "not" => Filter::Not,
+            "true" => Filter::Literal(Value::Bool(true)),
+            "false" => Filter::Literal(Value::Bool(false)),
+            "first" if !self.eat(&Token::LParen) => Filter::First,
+            "first" => Filter::FirstOf(Box::new(self.argument()?)),
+            "select" | "map" => {
+                self.expect(Token::LParen)?;
+                let argument = Box::new(self.argument()?);
+                if name == "select" {
+                    Filter::Select(argument)
+                } else {
+                    Filter::Map(argument)
+                }
+            }
+            name => {
+                return Err(syntax_error(
+                    offset,
+                    format!("unknown filter `{}`", name),
+                ));
+            }
+        };
+        Ok(filter)
+    }
+
+    /// The rest of an argument list, after the `(`
+    fn argument(&mut self) -> Result<Filter, FilterError> {
+        let filter = self.pipe()?;
+        self.expect(Token::RParen)?;
+        Ok(filter)
+    }
+}
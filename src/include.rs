@@ -0,0 +1,289 @@
+//! Files that include other files with an `@include` entry.
+//!
+//! Including is opt in: a [`Loader`] reads a file, takes the `@include`
+//! entries at its top level out, and merges the files they name into it
+//! with `Monoid::merge`. A path is relative to the directory of the file
+//! including it, and may have `*` and `?` globs in any component, matching
+//! in name order and leaving out hidden files. Several files are included
+//! with several entries, or with the `= item` list convention:
+//!
+//! ```text
+//! @include = common.ccl
+//! @include =
+//!   = services/*.ccl
+//!   = local.ccl
+//! ```
+//!
+//! Included files are resolved the same way, up to `max_depth` levels
+//! down. A file including itself, directly or not, is an error, and so is
+//! a path naming no file, but not a glob matching none.
+
+use crate::comment::COMMENT_KEY;
+use crate::encoding::decode;
+use crate::error::{IncludeError, ParseError};
+use crate::monoid::Monoid;
+use crate::options::ParseOptions;
+use crate::parser::CCL;
+use regex::Regex;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+/// Key of include entries
+pub const INCLUDE_KEY: &str = "@include";
+
+pub const DEFAULT_MAX_DEPTH: usize = 16;
+
+/// Reads files with the files they include
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Loader {
+    pub options: ParseOptions,
+    /// How many levels of includes to follow, the file loaded first being
+    /// at level 0
+    pub max_depth: usize,
+}
+
+impl Default for Loader {
+    fn default() -> Self {
+        Self::new(ParseOptions::default())
+    }
+}
+
+/// The files being loaded, each included by the one before
+#[derive(Default)]
+struct Chain {
+    /// The paths as opened, which includes are relative to
+    paths: Vec<PathBuf>,
+    /// The same files, canonicalized where they exist, to find cycles
+    canonical: Vec<PathBuf>,
+}
+
+impl Chain {
+    fn push(&mut self, path: &Path) {
+        self.paths.push(path.to_path_buf());
+        self.canonical.push(canonical(path));
+    }
+
+    fn pop(&mut self) {
+        self.paths.pop();
+        self.canonical.pop();
+    }
+
+    /// The paths to show in errors
+    fn shown(&self) -> Vec<PathBuf> {
+        self.paths.iter().map(|path| normalize(path)).collect()
+    }
+
+    /// The chain to show with `path` at the end
+    fn with(&self, path: &Path) -> Vec<PathBuf> {
+        let mut paths = self.shown();
+        paths.push(normalize(path));
+        paths
+    }
+}
+
+fn canonical(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+impl Loader {
+    pub fn new(options: ParseOptions) -> Self {
+        Self {
+            options,
+            max_depth: DEFAULT_MAX_DEPTH,
+        }
+    }
+
+    pub fn with_max_depth(mut self, max_depth: usize) -> Self {
+        self.max_depth = max_depth;
+        self
+    }
+
+    /// Read the file at `path`, with every file it includes merged in
+    pub fn load(&self, path: impl AsRef<Path>) -> Result<CCL, IncludeError> {
+        self.load_at(path.as_ref(), &mut Chain::default())
+    }
+
+    /// Merge in the files `ccl` includes, as if it was read from `path`.
+    /// For text from elsewhere, a `path` that is not there resolves the
+    /// includes against its directory all the same.
+    pub fn resolve(
+        &self,
+        ccl: CCL,
+        path: impl AsRef<Path>,
+    ) -> Result<CCL, IncludeError> {
+        let mut chain = Chain::default();
+        chain.push(path.as_ref());
+        self.resolve_at(ccl, &mut chain)
+    }
+
+    fn load_at(
+        &self,
+        path: &Path,
+        chain: &mut Chain,
+    ) -> Result<CCL, IncludeError> {
+        chain.push(path);
+        let result = self
+            .read(path, chain)
+            .and_then(|ccl| self.resolve_at(ccl, chain));
+        chain.pop();
+        result
+    }
+
+    fn read(&self, path: &Path, chain: &Chain) -> Result<CCL, IncludeError> {
+        let bytes = fs::read(path).map_err(|e| IncludeError::Io {
+            chain: chain.shown(),
+            message: e.to_string(),
+        })?;
+        let parse_error = |e: ParseError| IncludeError::Parse {
+            chain: chain.shown(),
+            error: e.with_file(&normalize(path).display().to_string()),
+        };
+        let decoded = decode(&bytes).map_err(parse_error)?;
+        CCL::parse_str_with(&decoded.text, &self.options).map_err(parse_error)
+    }
+
+    /// `ccl`, read from the last file of `chain`, with its includes
+    fn resolve_at(
+        &self,
+        ccl: CCL,
+        chain: &mut Chain,
+    ) -> Result<CCL, IncludeError> {
+        let CCL(mut map) = ccl;
+        let Some(includes) = map.remove(INCLUDE_KEY) else {
+            return Ok(CCL(map));
+        };
+        let mut ccl = CCL(map);
+        let path = chain.paths.last().expect("Never: empty include chain");
+        let dir = path.parent().unwrap_or(Path::new("")).to_path_buf();
+
+        for pattern in include_paths(&includes, chain)? {
+            for target in expand(&dir, &pattern) {
+                if chain.canonical.contains(&canonical(&target)) {
+                    return Err(IncludeError::Cycle {
+                        chain: chain.with(&target),
+                    });
+                }
+                if chain.paths.len() > self.max_depth {
+                    return Err(IncludeError::TooDeep {
+                        chain: chain.with(&target),
+                        max: self.max_depth,
+                    });
+                }
+                ccl = ccl.merge(self.load_at(&target, chain)?);
+            }
+        }
+        Ok(ccl)
+    }
+}
+
+/// The paths of an `@include` entry, its values and the items of its list
+fn include_paths(
+    includes: &CCL,
+    chain: &Chain,
+) -> Result<Vec<String>, IncludeError> {
+    let CCL(map) = includes;
+    let list = map.get("").map(|CCL(items)| items.iter());
+    let values = map.iter().filter(|(key, _)| !key.is_empty());
+    let mut paths = Vec::new();
+    for (item, CCL(nested)) in values.chain(list.into_iter().flatten()) {
+        if item == COMMENT_KEY {
+            continue;
+        }
+        if !nested.is_empty() {
+            return Err(IncludeError::Invalid {
+                chain: chain.shown(),
+                message: format!(
+                    "expected a path, found entries under '{}'",
+                    item
+                ),
+            });
+        }
+        paths.push(item.clone());
+    }
+    Ok(paths)
+}
+
+/// The files `pattern` names relative to `dir`: the one path if it has no
+/// glob, or else every file it matches
+fn expand(dir: &Path, pattern: &str) -> Vec<PathBuf> {
+    let path = dir.join(pattern);
+    if !pattern.contains(['*', '?']) {
+        return vec![path];
+    }
+    let mut matches = vec![PathBuf::new()];
+    for component in path.components() {
+        let glob = match component {
+            Component::Normal(name) => {
+                name.to_str().filter(|name| name.contains(['*', '?']))
+            }
+            _ => None,
+        };
+        let Some(glob) = glob else {
+            for path in &mut matches {
+                path.push(component);
+            }
+            continue;
+        };
+        let regex = glob_regex(glob);
+        let mut next = Vec::new();
+        for dir in &matches {
+            let listed = if dir.as_os_str().is_empty() {
+                Path::new(".")
+            } else {
+                dir
+            };
+            let Ok(entries) = fs::read_dir(listed) else {
+                continue;
+            };
+            let mut names = entries
+                .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+                .filter(|name| !name.starts_with('.') || glob.starts_with('.'))
+                .filter(|name| regex.is_match(name))
+                .collect::<Vec<_>>();
+            names.sort();
+            next.extend(names.into_iter().map(|name| dir.join(name)));
+        }
+        matches = next;
+    }
+    matches.retain(|path| path.is_file());
+    matches
+}
+
+/// `path` without `.` components, and with each `..` taking out the
+/// component before it. Done on the text alone, which is only right
+/// without symlinks, so only for showing paths: files are opened by the
+/// paths as written, which the OS resolves `..` in after symlinks.
+fn normalize(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir
+                if matches!(
+                    normalized.components().next_back(),
+                    Some(Component::Normal(_))
+                ) =>
+            {
+                normalized.pop();
+            }
+            // Nothing above the root
+            Component::ParentDir if normalized.has_root() => {}
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+/// A regex for the glob `glob`, `*` standing for any characters and `?`
+/// for one
+fn glob_regex(glob: &str) -> Regex {
+    let pattern = glob
+        .chars()
+        .map(|c| match c {
+            '*' => ".*".to_string(),
+            '?' => ".".to_string(),
+            c => regex::escape(&c.to_string()),
+        })
+        .collect::<String>();
+    Regex::new(&format!("^(?s:{})$", pattern)).expect("Never: escaped glob")
+}
@@ -1,4 +1,13 @@
+use crate::comment::CommentMode;
+use crate::diagnostic::Diagnostic;
+use crate::encoding::decode;
+use crate::error::{Location, ParseError};
 use crate::monoid::Monoid;
+use crate::options::ParseOptions;
+use crate::span::{LineIndex, LineRange, Span, lines_with_offsets};
+use crate::string_utils::trim_lines;
+use crate::tree::{try_value_node, value_node};
+use std::borrow::Cow;
 use std::collections::BTreeMap;
 use std::fmt::Display;
 
@@ -8,9 +17,40 @@ pub struct KeyVal {
     pub value: String,
 }
 
+/// The entry as CCL text, which `KeyVal::parse` reads back as the same
+/// entry
 impl Display for KeyVal {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{} = {:?}", self.key, self.value)
+        if self.key.is_empty() {
+            write!(f, "=")?;
+        } else {
+            write!(f, "{} =", self.key)?;
+        }
+
+        let mut lines = self.value.split('\n');
+        let first = lines.next().unwrap_or_default();
+        if !first.is_empty() {
+            write!(f, " {}", first)?;
+        }
+        // Continuation lines stay part of the value only when indented past
+        // the key, so shift them all if any is not
+        let rest = lines.collect::<Vec<&str>>();
+        let shift = if rest
+            .iter()
+            .any(|line| !line.trim().is_empty() && get_indent(line) == 0)
+        {
+            "  "
+        } else {
+            ""
+        };
+        for line in rest {
+            if line.trim().is_empty() {
+                writeln!(f)?;
+            } else {
+                write!(f, "\n{}{}", shift, line)?;
+            }
+        }
+        Ok(())
     }
 }
 
@@ -19,6 +59,66 @@ impl Display for KeyVal {
 ///    - the concatenation of two lists as the merge operation
 pub type KeyVals = Vec<KeyVal>;
 
+/// Where a key-value pair is in the source text
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KeyValSpan {
+    pub key: Span,
+    pub value: Span,
+    pub key_lines: LineRange,
+    pub value_lines: LineRange,
+}
+
+impl KeyValSpan {
+    pub(crate) fn new(index: &LineIndex, key: Span, value: Span) -> Self {
+        Self {
+            key,
+            value,
+            key_lines: index.lines(key),
+            value_lines: index.lines(value),
+        }
+    }
+
+    /// The whole entry, from the start of the key to the end of the value
+    pub fn entry(&self) -> Span {
+        Span::new(self.key.start, self.value.end)
+    }
+
+    pub fn lines(&self) -> LineRange {
+        LineRange {
+            start: self.key_lines.start,
+            end: self.value_lines.end,
+        }
+    }
+}
+
+pub type SpannedKeyVals = Vec<(KeyVal, KeyValSpan)>;
+
+/// A key-value pair borrowing from the text it was parsed from. Keys and
+/// values spanning several lines are only copied when some line had
+/// trailing whitespace to trim.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KeyValRef<'a> {
+    pub key: Cow<'a, str>,
+    pub value: Cow<'a, str>,
+}
+
+impl KeyValRef<'_> {
+    pub fn into_owned(self) -> KeyVal {
+        KeyVal {
+            key: self.key.into_owned(),
+            value: self.value.into_owned(),
+        }
+    }
+}
+
+impl From<KeyValRef<'_>> for KeyVal {
+    fn from(key_val: KeyValRef<'_>) -> Self {
+        key_val.into_owned()
+    }
+}
+
+pub type KeyValRefs<'a> = Vec<KeyValRef<'a>>;
+
 fn get_indent(line: &str) -> usize {
     line.len() - line.trim_start().len()
 }
@@ -44,139 +144,354 @@ impl KeyVal {
     ///     - the 'concatenation of two strings as the merge operation
     /// Note: to handle intentation, cat will trim the leading whitespace (or
     /// indentation?).
+    pub fn parse(data: &str) -> Result<KeyVals, ParseError> {
+        Ok(KeyVal::scan(data)?
+            .into_iter()
+            .map(|(key, value)| KeyVal {
+                key: trim_lines(&data[key.start..key.end]).into_owned(),
+                value: trim_lines(&data[value.start..value.end]).into_owned(),
+            })
+            .collect())
+    }
+
+    /// Like `parse`, but reads indentation according to `options`
+    pub fn parse_with(
+        data: &str,
+        options: &ParseOptions,
+    ) -> Result<KeyVals, ParseError> {
+        options.check_indentation(data)?;
+        Ok(KeyVal::scan_with(data, options)?
+            .into_iter()
+            .map(|(key, value)| KeyVal::from_spans(data, key, value))
+            .collect())
+    }
+
+    /// Like `parse`, but for raw file contents: a UTF-8 BOM is skipped,
+    /// `\r\n` line endings are read as `\n` and invalid UTF-8 is an error.
+    /// Use `encoding::decode` directly to also learn the line-ending style.
+    pub fn parse_bytes(bytes: &[u8]) -> Result<KeyVals, ParseError> {
+        KeyVal::parse(&decode(bytes)?.text)
+    }
+
+    /// Like `parse`, but borrows keys and values from `data` wherever they
+    /// appear there verbatim
+    pub fn parse_borrowed(data: &str) -> Result<KeyValRefs<'_>, ParseError> {
+        Ok(KeyVal::scan(data)?
+            .into_iter()
+            .map(|(key, value)| KeyValRef {
+                key: trim_lines(&data[key.start..key.end]),
+                value: trim_lines(&data[value.start..value.end]),
+            })
+            .collect())
+    }
+
+    /// Like `parse`, but also records where each key and value is in `data`
+    pub fn parse_spanned(data: &str) -> Result<SpannedKeyVals, ParseError> {
+        let index = LineIndex::new(data);
+        Ok(KeyVal::scan(data)?
+            .into_iter()
+            .map(|(key, value)| {
+                (
+                    KeyVal::from_spans(data, key, value),
+                    KeyValSpan::new(&index, key, value),
+                )
+            })
+            .collect())
+    }
+
+    fn from_spans(data: &str, key: Span, value: Span) -> KeyVal {
+        KeyVal {
+            key: trim_lines(&data[key.start..key.end]).into_owned(),
+            value: trim_lines(&data[value.start..value.end]).into_owned(),
+        }
+    }
+
+    /// Find the byte ranges of the keys and values in `data`. Each range
+    /// starts and ends with non-whitespace; the key or value itself is the
+    /// range with trailing whitespace trimmed from every line.
     ///
     /// 4 variables to consider when parsing a line:
     /// 1. is line empty?
-    /// 2. is key_buf empty?
+    /// 2. is a key pending (seen, but its `=` not yet)?
     /// 3. indent > fst_indent?
     /// 4. line contains "="?
-    pub fn parse(data: &str) -> Result<KeyVals, String> {
-        let mut key_vals = Vec::new();
-
-        let lines = data.lines().collect::<Vec<&str>>();
-
-        // Delete empty lines at the beginning
-        let mut i = 0;
-        while i < lines.len() && lines[i].trim().is_empty() {
-            i += 1;
-        }
-        let lines = &lines[i..];
+    pub(crate) fn scan(data: &str) -> Result<Vec<(Span, Span)>, ParseError> {
+        KeyVal::scan_with(data, &ParseOptions::default())
+    }
 
-        // If there are no lines, return an empty list
-        if lines.len() == 0 {
-            return Ok(key_vals);
+    fn scan_with(
+        data: &str,
+        options: &ParseOptions,
+    ) -> Result<Vec<(Span, Span)>, ParseError> {
+        match KeyVal::scan_partial(data, options) {
+            (spans, None) => Ok(spans),
+            (_, Some(err)) => Err(err),
         }
+    }
 
-        let fst_indent = get_indent(lines[0]);
+    /// Like `scan_with`, but on an unclosed key also returns the ranges of
+    /// the entries before it
+    fn scan_partial(
+        data: &str,
+        options: &ParseOptions,
+    ) -> (Vec<(Span, Span)>, Option<ParseError>) {
+        let mut spans: Vec<(Span, Span)> = Vec::new();
 
-        let mut key_buf = String::new();
+        let mut lines = lines_with_offsets(data)
+            .skip_while(|(_, line)| line.trim().is_empty())
+            .peekable();
 
-        fn continue_key_buf(key_buf: &mut String, line: &str) {
-            key_buf.push_str(&format!("\n{}", line.trim_end()));
+        // If there are no lines, return an empty list
+        let Some(&(_, fst_line)) = lines.peek() else {
+            return (spans, None);
+        };
+        let fst_indent = options.indent_width(fst_line);
+
+        // Source range of the non-blank text of the pending key
+        let mut key_span: Option<Span> = None;
+
+        fn extend_key_span(
+            key_span: &mut Option<Span>,
+            offset: usize,
+            text: &str,
+        ) {
+            if text.trim().is_empty() {
+                return;
+            }
+            let start = offset + get_indent(text);
+            let end = offset + text.trim_end().len();
+            let span = key_span.get_or_insert(Span::new(start, end));
+            span.end = end;
         }
 
-        fn continue_last_value(key_vals: &mut KeyVals, line: &str) {
-            let last_key_val = key_vals
+        fn continue_last_value(
+            spans: &mut [(Span, Span)],
+            offset: usize,
+            line: &str,
+        ) {
+            let (_, value_span) = spans
                 .last_mut()
-                .expect("Never: empty line before any key-value pair");
-            last_key_val
-                .value
-                .push_str(&format!("\n{}", line.trim_end()));
+                .expect("Never: line before any key-value pair");
+            value_span.end = offset + line.trim_end().len();
         }
 
         fn add_new_key_val(
-            key_buf: &mut String,
+            key_span: &mut Option<Span>,
+            offset: usize,
             line: &str,
-            key_vals: &mut KeyVals,
+            spans: &mut Vec<(Span, Span)>,
         ) {
             let (curr_key, curr_value) =
                 line.split_once("=").expect("Never");
 
-            key_buf.push_str(&format!("\n{}", curr_key.trim_end()));
-            key_vals.push(KeyVal::new(
-                key_buf.trim().to_string(),
-                curr_value.trim().to_string(),
-            ));
-            *key_buf = String::new();
+            extend_key_span(key_span, offset, curr_key);
+            let eq = offset + curr_key.len();
+            let key = key_span.take().unwrap_or(Span::new(eq, eq));
+            let value_start =
+                eq + 1 + curr_value.len() - curr_value.trim_start().len();
+            let value_end = value_start + curr_value.trim().len();
+            spans.push((key, Span::new(value_start, value_end)));
         }
-        for line in lines.iter() {
-            let indent = get_indent(line);
+
+        for (offset, line) in lines {
+            let indent = options.indent_width(line);
 
             if line.trim().is_empty() {
-                if !key_buf.is_empty() {
-                    continue_key_buf(&mut key_buf, line);
+                // Blank lines only matter once followed by more text
+                continue;
+            } else if key_span.is_some() {
+                if !line.contains("=") {
+                    extend_key_span(&mut key_span, offset, line);
                 } else {
-                    continue_last_value(&mut key_vals, line);
+                    add_new_key_val(&mut key_span, offset, line, &mut spans);
                 }
+            } else if indent > fst_indent {
+                continue_last_value(&mut spans, offset, line);
+            } else if !line.contains("=") {
+                extend_key_span(&mut key_span, offset, line);
             } else {
-                if !key_buf.is_empty() {
-                    if !line.contains("=") {
-                        continue_key_buf(&mut key_buf, line);
-                    } else {
-                        add_new_key_val(&mut key_buf, line, &mut key_vals);
-                    }
-                } else {
-                    if indent > fst_indent {
-                        continue_last_value(&mut key_vals, line);
-                    } else {
-                        if !line.contains("=") {
-                            continue_key_buf(&mut key_buf, line);
-                        } else {
-                            add_new_key_val(
-                                &mut key_buf,
-                                line,
-                                &mut key_vals,
-                            );
-                        }
-                    }
-                }
+                add_new_key_val(&mut key_span, offset, line, &mut spans);
             }
         }
 
         // Check non-closed key-value pairs
-        if !key_buf.is_empty() {
-            return Err(format!(
-                "No value found for key: {}",
-                key_buf.trim()
-            ));
-        }
-
-        // Trim all values again to avoid trailing newlines
-        for key_val in key_vals.iter_mut() {
-            key_val.value = key_val.value.trim_end().to_string();
-        }
+        let err = key_span.map(|span| ParseError::UnclosedKey {
+            key: trim_lines(&data[span.start..span.end]).into_owned(),
+            location: Location::new(data, span),
+        });
 
-        Ok(key_vals)
+        (spans, err)
     }
 
-    /// pretty and parse are monoid isomorphisms
+    /// pretty and parse are monoid isomorphisms:
+    /// parse (pretty key_vals) ≡ key_vals
+    /// for key-value pairs as `parse` returns them. Every entry ends with a
+    /// newline, so pretty (key_vals1 @ key_vals2) ≡
+    /// cat (pretty key_vals1) (pretty key_vals2).
     pub fn pretty(key_vals: &KeyVals) -> String {
         key_vals
             .iter()
-            .map(|key_val| key_val.to_string())
+            .map(|key_val| format!("{}\n", key_val))
+            .collect()
+    }
+
+    /// One `key = "value"` line per pair, with the value debug-quoted
+    pub fn pretty_debug(key_vals: &KeyVals) -> String {
+        key_vals
+            .iter()
+            .map(|key_val| format!("{} = {:?}", key_val.key, key_val.value))
             .collect::<Vec<String>>()
             .join("\n")
     }
 
     pub fn parse_flat_to_tree(key_vals: &KeyVals) -> KeyValTree {
+        KeyVal::parse_flat_to_tree_with(key_vals, &ParseOptions::default())
+    }
+
+    /// Like `parse_flat_to_tree`, but with nested values parsed according
+    /// to `options`, and comment entries at every level kept or stripped
+    /// according to `options.comments`. Each value is parsed in a single
+    /// pass, however deeply nested.
+    pub fn parse_flat_to_tree_with(
+        key_vals: &KeyVals,
+        options: &ParseOptions,
+    ) -> KeyValTree {
         let mut tree = KeyValTree::new();
 
         for key_val in key_vals {
-            let KeyVal { key, value } = key_val;
-            let node = match KeyVal::parse(value) {
-                Ok(new_key_vals) if !new_key_vals.is_empty() => {
-                    KeyValNode::Tree(KeyVal::parse_flat_to_tree(
-                        &new_key_vals,
-                    ))
-                }
-                Err(_) | Ok(_) => KeyValNode::Leaf(value.to_string()),
-            };
-
-            insert_map(&mut tree, key, node);
+            if options.comments == CommentMode::Strip && key_val.is_comment() {
+                continue;
+            }
+            let node = value_node(&key_val.value, options);
+            insert_map(&mut tree, &key_val.key, node);
         }
 
         tree
     }
+
+    /// Like `parse_flat_to_tree`, but a nested value that has an `=` yet is
+    /// not valid CCL, say because of a missing `=` deep inside a section,
+    /// is an error rather than a leaf. The error is a
+    /// `ParseError::InValue` with the keys leading to the failing section;
+    /// its location is in the value of the outermost of them.
+    pub fn try_parse_flat_to_tree(
+        key_vals: &KeyVals,
+    ) -> Result<KeyValTree, ParseError> {
+        KeyVal::try_parse_flat_to_tree_with(key_vals, &ParseOptions::default())
+    }
+
+    /// Like `try_parse_flat_to_tree`, but with nested values parsed
+    /// according to `options`
+    pub fn try_parse_flat_to_tree_with(
+        key_vals: &KeyVals,
+        options: &ParseOptions,
+    ) -> Result<KeyValTree, ParseError> {
+        let mut tree = KeyValTree::new();
+
+        for key_val in key_vals {
+            if options.comments == CommentMode::Strip && key_val.is_comment() {
+                continue;
+            }
+            let node = try_value_node(&key_val.value, options)
+                .map_err(|err| err.in_value(&key_val.key))?;
+            insert_map(&mut tree, &key_val.key, node);
+        }
+
+        Ok(tree)
+    }
+}
+
+impl KeyVal {
+    /// Like `parse_with`, but carries on past errors. Returns the entries
+    /// before an unclosed key together with every problem found, including
+    /// all lines with indentation `options` rejects.
+    pub fn parse_recovering(
+        data: &str,
+        options: &ParseOptions,
+    ) -> (KeyVals, Vec<Diagnostic>) {
+        let mut diagnostics = options
+            .indentation_errors(data)
+            .into_iter()
+            .map(Diagnostic::error)
+            .collect::<Vec<Diagnostic>>();
+        let (spans, err) = KeyVal::scan_partial(data, options);
+        diagnostics.extend(err.map(Diagnostic::error));
+
+        let key_vals = spans
+            .into_iter()
+            .map(|(key, value)| KeyVal::from_spans(data, key, value))
+            .collect();
+        (key_vals, diagnostics)
+    }
+}
+
+impl KeyVal {
+    /// Split `data` into at most `max_chunks` consecutive pieces such that
+    /// parse data ≡ parse chunk1 @ parse chunk2 @ ...
+    ///
+    /// A chunk may only start at a line that begins a new top-level entry
+    /// no matter how the text before it is parsed: a line with an `=`, at
+    /// exactly the first entry's indentation, right after a non-blank line
+    /// with an `=` (which cannot leave a key pending).
+    pub fn split_entries(data: &str, max_chunks: usize) -> Vec<&str> {
+        let mut lines = lines_with_offsets(data)
+            .skip_while(|(_, line)| line.trim().is_empty())
+            .peekable();
+        let Some(&(_, fst_line)) = lines.peek() else {
+            return vec![data];
+        };
+        let fst_indent = get_indent(fst_line);
+        let target = data.len().div_ceil(max_chunks.max(1)).max(1);
+
+        let mut splits = vec![0];
+        let mut prev_has_eq = false;
+        for (offset, line) in lines {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let has_eq = line.contains("=");
+            if has_eq
+                && prev_has_eq
+                && get_indent(line) == fst_indent
+                && offset >= splits.last().expect("Never") + target
+            {
+                splits.push(offset);
+            }
+            prev_has_eq = has_eq;
+        }
+        splits.push(data.len());
+
+        splits.windows(2).map(|w| &data[w[0]..w[1]]).collect()
+    }
+
+    /// Parse `data` on up to `threads` threads. The result is the same as
+    /// `parse`, since parse is a monoid homomorphism and `data` is only
+    /// split where concatenation commutes with parsing (see
+    /// `split_entries`).
+    pub fn parse_parallel(
+        data: &str,
+        threads: usize,
+    ) -> Result<KeyVals, ParseError> {
+        let chunks = KeyVal::split_entries(data, threads);
+        let results = std::thread::scope(|scope| {
+            let handles = chunks
+                .iter()
+                .map(|chunk| scope.spawn(|| KeyVal::parse(chunk)))
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().expect("parser thread panicked"))
+                .collect::<Vec<_>>()
+        });
+
+        let mut offset = 0;
+        let mut key_vals = Vec::with_capacity(results.len());
+        for (chunk, result) in chunks.iter().zip(results) {
+            key_vals.push(result.map_err(|e| e.rebased(data, offset))?);
+            offset += chunk.len();
+        }
+        Ok(KeyVals::aggregate(key_vals))
+    }
 }
 
 impl Monoid for KeyVals {
@@ -191,7 +506,7 @@ impl Monoid for KeyVals {
     }
 }
 
-fn insert_map<'a, T>(
+pub(crate) fn insert_map<'a, T>(
     map: &'a mut BTreeMap<String, Vec<T>>,
     key: &str,
     value: T,
@@ -207,12 +522,23 @@ fn insert_map<'a, T>(
 
 pub type KeyValTree = BTreeMap<String, Vec<KeyValNode>>;
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum KeyValNode {
     Leaf(String),
     Tree(KeyValTree),
 }
 
+/// Spans of the nodes of a `KeyValTree`, mirroring its shape
+pub type KeyValSpanTree = BTreeMap<String, Vec<KeyValNodeSpan>>;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct KeyValNodeSpan {
+    /// Span of the key-value pair the node was built from
+    pub span: KeyValSpan,
+    /// Spans of the node's children; empty for a leaf
+    pub children: KeyValSpanTree,
+}
+
 fn _leave_to_key_val(key: &str, node: KeyValNode) -> Option<KeyVal> {
     match node {
         KeyValNode::Leaf(value) => Some(KeyVal::new(key.to_string(), value)),
@@ -259,7 +585,7 @@ j = k
     fn test_key_val_parse() {
         let data = data();
         let key_vals = KeyVal::parse(&data).unwrap();
-        let pretty_str = KeyVal::pretty(&key_vals);
+        let pretty_str = KeyVal::pretty_debug(&key_vals);
 
         insta::assert_snapshot!(pretty_str, @r#"
         a = "b"
@@ -270,6 +596,43 @@ j = k
         "#);
     }
 
+    #[test]
+    fn test_key_val_pretty() {
+        let data = data();
+        let key_vals = KeyVal::parse(&data).unwrap();
+        let pretty_str = KeyVal::pretty(&key_vals);
+
+        insta::assert_snapshot!(pretty_str, @r"
+        a = b
+        b =
+          c = d
+          d =
+            e = f
+            f = g
+          g = h
+        h = i
+        i = j
+        j = k
+          k = l
+        ");
+        assert_eq!(KeyVal::parse(&pretty_str).unwrap(), key_vals);
+    }
+
+    #[test]
+    fn test_key_val_pretty_reindents() {
+        let key_vals = vec![
+            KeyVal::new("a".into(), "b\nc = d\n\n  e".into()),
+            KeyVal::new("".into(), "f".into()),
+        ];
+        insta::assert_snapshot!(KeyVal::pretty(&key_vals), @r"
+        a = b
+          c = d
+
+            e
+        = f
+        ");
+    }
+
     #[test]
     #[should_panic]
     fn test_key_val_parse_3() {
@@ -316,7 +679,7 @@ j = k
             },
         ]
         "#);
-        insta::assert_snapshot!(KeyVal::pretty(&key_vals), @r#"
+        insta::assert_snapshot!(KeyVal::pretty_debug(&key_vals), @r#"
         a = "b"
         c = "d"
         "#);
@@ -458,7 +821,7 @@ j = k
   k = l
 "#;
 
-        let key_vals = KeyVal::parse(&data).unwrap();
+        let key_vals = KeyVal::parse(data).unwrap();
         let tree = KeyVal::parse_flat_to_tree(&key_vals);
 
         insta::assert_debug_snapshot!(tree, @r#"
@@ -1,4 +1,32 @@
+pub mod access;
+pub mod comment;
+pub mod cst;
+#[cfg(feature = "serde")]
+pub mod de;
+pub mod diagnostic;
+pub mod diff;
+pub mod encoding;
+pub mod error;
+pub mod filter;
+pub mod include;
 pub mod key_val;
+pub mod merge;
 pub mod monoid;
+pub mod mutation;
+pub mod options;
 pub mod parser;
+pub mod patch;
+pub mod query;
+pub mod schema;
+#[cfg(feature = "serde")]
+pub mod ser;
+pub mod span;
 pub mod string_utils;
+pub mod tree;
+pub mod walk;
+
+#[cfg(feature = "serde")]
+pub use de::from_str;
+pub use merge::merge3;
+#[cfg(feature = "serde")]
+pub use ser::to_string;
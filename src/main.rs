@@ -1,7 +1,16 @@
-use ccl_rs::key_val::KeyVal;
+use ccl_rs::comment::CommentMode;
+use ccl_rs::encoding::{LineEnding, decode};
+use ccl_rs::filter::{Filter, Value};
+use ccl_rs::include::Loader;
+use ccl_rs::merge::{MergeOptions, MergeStrategy};
+use ccl_rs::merge3;
 use ccl_rs::monoid::Monoid;
+use ccl_rs::options::ParseOptions;
 use ccl_rs::parser::CCL;
-use clap::Parser;
+use ccl_rs::query::Query;
+use ccl_rs::schema::Schema;
+use ccl_rs::string_utils::underline;
+use clap::{Parser, Subcommand};
 use std::fs;
 use std::io::{self, Read};
 use std::process;
@@ -9,17 +18,114 @@ use std::process;
 #[derive(Parser)]
 #[command(name = "cclq")]
 #[command(about = "Merge CCL files and query")]
-#[command(
-    long_about = "Merge CCL files and query. Queries are single keys."
-)]
+#[command(long_about = "Merge CCL files and query. A query is a path of keys \
+                        separated by `=`, where `*` matches any key, `**` any \
+                        number of levels, `db_*` is a glob, `/re/` a regex and \
+                        `\\` escapes the next character.")]
 struct Args {
+    #[command(subcommand)]
+    command: Option<Command>,
+
     /// Input files to query (default: read from stdin)
-    #[arg(short, long, num_args = 1..)]
+    #[arg(short, long, num_args = 1.., global = true)]
     file: Vec<String>,
 
-    /// Query key (empty for print all)
+    /// Query path, such as `database=*=port` (empty for print all)
     #[arg(short, long, num_args = 1..)]
     query: Vec<String>,
+
+    /// Keep `/=` comment entries instead of dropping them
+    #[arg(long, global = true)]
+    keep_comments: bool,
+
+    /// Merge in the files named by `@include = path` entries at the top
+    /// level of each input file, relative to it; globs like `conf.d/*.ccl`
+    /// match files in name order
+    #[arg(long, global = true)]
+    includes: bool,
+
+    /// How entries defined in more than one file combine: `union`,
+    /// `last-wins`, `first-wins` or `replace`
+    #[arg(long, default_value_t, global = true)]
+    merge: MergeStrategy,
+
+    /// Merge strategy for one path and everything under it, such as
+    /// `database=hosts=replace`
+    #[arg(
+        long,
+        num_args = 1..,
+        value_parser = parse_merge_at,
+        global = true
+    )]
+    merge_at: Vec<(Vec<String>, MergeStrategy)>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Transform the merged input with a jq-like filter
+    #[command(
+        long_about = "Transform the merged input with a jq-like filter, \
+                      such as `.database | map(select(.enabled == \"true\")) \
+                      | keys`. Filters: `.key`, `|`, `keys`, `values`, \
+                      `length`, `first`, `first(f)`, `raw`, `select(f)`, \
+                      `map(f)`, `not`, `==` and `!=`."
+    )]
+    Eval {
+        /// The filter to apply
+        filter: String,
+    },
+    /// Show what changes from one CCL file to another, exiting with 1 if
+    /// anything does
+    Diff {
+        /// The file to compare from
+        old: String,
+        /// The file to compare to
+        new: String,
+        /// Print one tab-separated line per entry removed or added, for
+        /// scripts
+        #[arg(long)]
+        porcelain: bool,
+    },
+    /// Merge the changes two CCL files made to a common base, exiting
+    /// with 1 on conflicts
+    #[command(long_about = "Merge the changes two CCL files made to a common \
+                            base, exiting with 1 on conflicts, which are \
+                            reported on stderr.\n\n\
+                            The output is our file with the entries the merge \
+                            changed edited in place, and the rest of its text, \
+                            comments and layout kept as they are. With \
+                            --includes, or where the layout of our file cannot \
+                            take the edits, it is the merged document \
+                            rewritten through `CCL::pretty` instead, with keys \
+                            sorted and no comments. Conflicting values are all \
+                            kept in it, with no markers around them, for \
+                            resolving by hand; a `/= Conflict: ...` comment \
+                            line at the top names each one.\n\n\
+                            To use it as a git merge driver, set \
+                            `merge.ccl.driver` to \
+                            `cclq merge3 %O %A %B --output %A` and add \
+                            `*.ccl merge=ccl` to `.gitattributes`.")]
+    Merge3 {
+        /// The common ancestor of both files
+        base: String,
+        /// Our version
+        ours: String,
+        /// Their version
+        theirs: String,
+        /// Write the merged file here instead of to stdout
+        #[arg(short, long)]
+        output: Option<String>,
+    },
+    /// Check CCL files against a schema written in CCL, printing every
+    /// violation and exiting with 1 if there is any
+    Validate {
+        /// The schema to check against
+        #[arg(long)]
+        schema: String,
+        /// The files to check, each on its own
+        #[arg(required = true)]
+        files: Vec<String>,
+    },
 }
 
 fn main() {
@@ -31,18 +137,166 @@ fn main() {
         args.file
     };
 
-    // Load and merge all CCL files
-    let ccl = load_files(file);
+    let options = ParseOptions {
+        comments: if args.keep_comments {
+            CommentMode::Keep
+        } else {
+            CommentMode::Strip
+        },
+        ..ParseOptions::default()
+    };
+
+    let merge = MergeOptions {
+        default: args.merge,
+        overrides: args.merge_at.into_iter().collect(),
+    };
+    let loader = args.includes.then(|| Loader::new(options.clone()));
+    let input = Input {
+        options: &options,
+        merge: &merge,
+        includes: loader.as_ref(),
+    };
+
+    if let Some(Command::Diff {
+        old,
+        new,
+        porcelain,
+    }) = &args.command
+    {
+        let (old, line_ending) = load_files(vec![old.clone()], &input);
+        let (new, _) = load_files(vec![new.clone()], &input);
+        let diff = old.diff(&new);
+        let text = if *porcelain {
+            diff.porcelain()
+        } else {
+            diff.to_string()
+        };
+        print!("{}", line_ending.apply(&text));
+        process::exit(if diff.is_empty() { 0 } else { 1 });
+    }
+
+    if let Some(Command::Merge3 {
+        base,
+        ours,
+        theirs,
+        output,
+    }) = &args.command
+    {
+        let load = |file: &String| load_files(vec![file.clone()], &input);
+        let (base, _) = load(base);
+        let (ours_ccl, line_ending) = load(ours);
+        let (theirs, _) = load(theirs);
+        let result = merge3(&base, &ours_ccl, &theirs);
+        for conflict in &result.conflicts {
+            eprintln!("Conflict: {}", conflict);
+        }
+        // What ours includes is in the merge too, so the merge only
+        // applies to its own text without includes
+        let applied = match input.includes {
+            Some(_) => None,
+            None => {
+                let applied = result.apply(&read_file(ours).0, &options);
+                if applied.is_none() {
+                    eprintln!(
+                        "Could not edit '{}' into the merge, writing it \
+                         in full instead",
+                        ours
+                    );
+                }
+                applied
+            }
+        };
+        let merged = applied.unwrap_or_else(|| result.pretty());
+        let text = line_ending.apply(&merged);
+        match output {
+            Some(path) => {
+                fs::write(path, text.as_bytes()).unwrap_or_else(|e| {
+                    eprintln!("Failed to write file '{}': {}", path, e);
+                    process::exit(1);
+                })
+            }
+            None => print!("{}", text),
+        }
+        process::exit(if result.is_clean() { 0 } else { 1 });
+    }
+
+    if let Some(Command::Validate { schema, files }) = &args.command {
+        let (spec, _) = load_files(vec![schema.clone()], &input);
+        let schema = Schema::from_ccl(&spec).unwrap_or_else(|e| {
+            eprintln!("{}: {}", schema, e);
+            process::exit(1);
+        });
+        let mut valid = true;
+        for file_path in files {
+            let violations = if input.includes.is_some() {
+                // With what the file includes merged in, entries are not
+                // all in the file any more, so violations go unlocated
+                let (ccl, _) = load_files(vec![file_path.clone()], &input);
+                schema.validate(&ccl)
+            } else {
+                let (content, _) = read_file(file_path);
+                schema.validate_str(&content).unwrap_or_else(|e| {
+                    let e = e.with_file(file_path);
+                    eprintln!("{}: {}", e.location(), e);
+                    eprintln!("{}", underline(&content, e.location().span));
+                    process::exit(1);
+                })
+            };
+            for violation in &violations {
+                match &violation.location {
+                    Some(location) => {
+                        let mut location = location.clone();
+                        location.file = Some(file_path.clone());
+                        println!("{}: {}", location, violation);
+                    }
+                    None => println!("{}: {}", file_path, violation),
+                }
+            }
+            valid &= violations.is_empty();
+        }
+        process::exit(if valid { 0 } else { 1 });
+    }
+
+    // Check the filter before reading any input
+    let filter = match &args.command {
+        Some(Command::Eval { filter }) => {
+            Some(Filter::parse(filter).unwrap_or_else(|e| {
+                eprintln!("{}", e);
+                process::exit(1);
+            }))
+        }
+        _ => None,
+    };
+
+    // Load and merge all CCL files, printing in the line-ending style of
+    // the first one
+    let (ccl, line_ending) = load_files(file, &input);
+
+    if let Some(filter) = filter {
+        let outputs = filter.eval(&ccl).unwrap_or_else(|e| {
+            eprintln!("Filter failed: {}", e);
+            process::exit(1);
+        });
+        for output in outputs {
+            match output {
+                Value::Ccl(ccl) => {
+                    print!("{}", line_ending.apply(&ccl.pretty()))
+                }
+                value => print!("{}{}", value, line_ending.as_str()),
+            }
+        }
+        return;
+    }
 
     // Execute query
     if args.query.is_empty() {
-        print!("{}", ccl.pretty());
+        print!("{}", line_ending.apply(&ccl.pretty()));
     } else {
         for query in args.query {
             match execute_query(&query, &ccl) {
                 Ok(result) => {
-                    print!("{}", result.pretty());
-                    println!();
+                    print!("{}", line_ending.apply(&result.pretty()));
+                    print!("{}", line_ending.as_str());
                 }
                 Err(e) => {
                     eprintln!("Query failed: {}", e);
@@ -53,52 +307,99 @@ fn main() {
     }
 }
 
-fn load_files(files: Vec<String>) -> CCL {
+/// How to read and combine input files
+struct Input<'a> {
+    options: &'a ParseOptions,
+    merge: &'a MergeOptions,
+    /// Resolves includes, if enabled
+    includes: Option<&'a Loader>,
+}
+
+fn load_files(files: Vec<String>, input: &Input) -> (CCL, LineEnding) {
     let mut ccls = Vec::new();
+    let mut line_ending = None;
 
     for file_path in files {
-        let content = if file_path == "/dev/stdin" {
-            let mut buffer = String::new();
-            io::stdin().read_to_string(&mut buffer).unwrap_or_else(|e| {
-                eprintln!("Failed to read from stdin: {}", e);
-                process::exit(1);
-            });
-            buffer
-        } else {
-            fs::read_to_string(&file_path).unwrap_or_else(|e| {
-                eprintln!("Failed to read file '{}': {}", file_path, e);
-                process::exit(1);
-            })
-        };
+        let (content, file_line_ending) = read_file(&file_path);
+        line_ending.get_or_insert(file_line_ending);
 
-        match KeyVal::parse(&content) {
-            Ok(key_vals) => {
-                let ccl = CCL::parse(key_vals);
-                ccls.push(ccl);
-            }
-            Err(e) => {
-                eprintln!("Failed to parse file '{}': {}", file_path, e);
-                process::exit(1);
-            }
+        // Report every problem in the file before giving up on it
+        let (ccl, diagnostics) = CCL::parse_recovering(&content, input.options);
+        for diagnostic in &diagnostics {
+            let diagnostic = diagnostic.clone().with_file(&file_path);
+            eprintln!("{}: {}", diagnostic.location(), diagnostic);
+            eprintln!("{}", underline(&content, diagnostic.span()));
         }
+        if diagnostics.iter().any(|diagnostic| diagnostic.is_error()) {
+            process::exit(1);
+        }
+        let ccl = match input.includes {
+            Some(loader) => {
+                // What stdin includes is relative to the working directory
+                let from = if file_path == "/dev/stdin" {
+                    "<stdin>"
+                } else {
+                    &file_path
+                };
+                loader.resolve(ccl, from).unwrap_or_else(|e| {
+                    eprintln!("{}", e);
+                    process::exit(1);
+                })
+            }
+            None => ccl,
+        };
+        ccls.push(ccl);
     }
 
-    CCL::aggregate(ccls)
+    (
+        CCL::aggregate_with(ccls, input.merge),
+        line_ending.unwrap_or_default(),
+    )
 }
 
-fn execute_query(query_key: &str, ccl: &CCL) -> Result<CCL, String> {
-    let nested_keys = query_key.split('=').collect::<Vec<&str>>();
-    let mut ccl = ccl.clone();
-    for key in nested_keys {
-        ccl = query_single_key(key, &ccl)?;
-    }
-    Ok(ccl)
+/// The text of a file, or of stdin for `/dev/stdin`, decoded
+fn read_file(file_path: &str) -> (String, LineEnding) {
+    let bytes = if file_path == "/dev/stdin" {
+        let mut buffer = Vec::new();
+        io::stdin().read_to_end(&mut buffer).unwrap_or_else(|e| {
+            eprintln!("Failed to read from stdin: {}", e);
+            process::exit(1);
+        });
+        buffer
+    } else {
+        fs::read(file_path).unwrap_or_else(|e| {
+            eprintln!("Failed to read file '{}': {}", file_path, e);
+            process::exit(1);
+        })
+    };
+
+    let decoded = decode(&bytes).unwrap_or_else(|e| {
+        let e = e.with_file(file_path);
+        eprintln!("{}: {}", e.location(), e);
+        process::exit(1);
+    });
+    (decoded.text.into_owned(), decoded.line_ending)
 }
 
-fn query_single_key(key: &str, ccl: &CCL) -> Result<CCL, String> {
-    let CCL(map) = ccl;
-    match map.get(key) {
-        Some(ccl) => Ok(ccl.clone()),
-        None => Err(format!("Key '{}' not found", key)),
+/// The value at the path of a plain query, or else every matched value
+/// nested under its path
+fn execute_query(query_text: &str, ccl: &CCL) -> Result<CCL, String> {
+    let query = Query::parse(query_text).map_err(|e| e.to_string())?;
+    if let Some(path) = query.literal_path() {
+        return ccl.get(&path).cloned().map_err(|e| e.to_string());
     }
+    let matches = query.matches(ccl);
+    if matches.is_empty() {
+        return Err(format!("No match for '{}'", query_text));
+    }
+    Ok(CCL::aggregate(matches.iter().map(|m| m.to_ccl()).collect()))
+}
+
+/// A path and its merge strategy, from `path=strategy`
+fn parse_merge_at(text: &str) -> Result<(Vec<String>, MergeStrategy), String> {
+    let (path, strategy) = text
+        .rsplit_once('=')
+        .ok_or_else(|| format!("expected `path=strategy`, found `{}`", text))?;
+    let path = path.split('=').map(|key| key.trim().to_string()).collect();
+    Ok((path, strategy.trim().parse()?))
 }
@@ -0,0 +1,383 @@
+//! Merges other than the union of `impl Monoid for CCL`.
+//!
+//! A strategy says how the two definitions of an entry combine when both
+//! sides of a merge have it. The top-level entries of both sides are
+//! always kept. Like the union, every strategy, and every mix of them by
+//! path, is associative with the empty `CCL` as identity.
+//!
+//! [`merge3`] instead applies the changes two documents made to a common
+//! base, and reports where they conflict. It merges documents, not text:
+//! [`Merge3::apply`] edits the text of ours into the result, keeping the
+//! entries it did not change as they were, while [`Merge3::pretty`] writes
+//! it the way `CCL::pretty` does. A conflict leaves what both sides have
+//! in the merge, with no markers in between, so both start with a comment
+//! line naming each conflict.
+//!
+//! ```
+//! use ccl_rs::merge::{MergeOptions, MergeStrategy};
+//! use ccl_rs::parser::CCL;
+//!
+//! let defaults = CCL::parse_str("port = 8080\nhost = localhost").unwrap();
+//! let prod = CCL::parse_str("port = 443").unwrap();
+//! let options = MergeOptions::new(MergeStrategy::LastWins);
+//! let merged = defaults.merge_with(prod, &options);
+//! assert_eq!(merged.get_str(&["port"]), Ok("443"));
+//! assert_eq!(merged.get_str(&["host"]), Ok("localhost"));
+//! ```
+
+use crate::comment::COMMENT_KEY;
+use crate::cst::Cst;
+use crate::key_val::KeyVal;
+use crate::monoid::Monoid;
+use crate::options::ParseOptions;
+use crate::parser::CCL;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+/// How an entry defined on both sides of a merge combines
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum MergeStrategy {
+    /// Keep the entries under both, as `Monoid::merge` does
+    #[default]
+    Union,
+    /// The values under the entry, keys with nothing under them, are those
+    /// of the right side, unless it has nothing under the entry. Entries
+    /// with entries under them are merged further down.
+    LastWins,
+    /// Like `LastWins`, keeping the values of the left side instead
+    FirstWins,
+    /// The right side replaces everything under the entry, unless it has
+    /// nothing under it
+    Replace,
+}
+
+impl MergeStrategy {
+    pub const ALL: [MergeStrategy; 4] = [
+        MergeStrategy::Union,
+        MergeStrategy::LastWins,
+        MergeStrategy::FirstWins,
+        MergeStrategy::Replace,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            MergeStrategy::Union => "union",
+            MergeStrategy::LastWins => "last-wins",
+            MergeStrategy::FirstWins => "first-wins",
+            MergeStrategy::Replace => "replace",
+        }
+    }
+}
+
+impl fmt::Display for MergeStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+impl std::str::FromStr for MergeStrategy {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, String> {
+        Self::ALL
+            .into_iter()
+            .find(|strategy| strategy.name() == name)
+            .ok_or_else(|| {
+                let names = Self::ALL.map(|strategy| strategy.name());
+                format!(
+                    "unknown merge strategy `{}`, expected one of: {}",
+                    name,
+                    names.join(", ")
+                )
+            })
+    }
+}
+
+/// The strategy of every entry: `default`, unless the entry is at or
+/// under a path in `overrides`, where the longest such path decides
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MergeOptions {
+    pub default: MergeStrategy,
+    pub overrides: BTreeMap<Vec<String>, MergeStrategy>,
+}
+
+impl MergeOptions {
+    pub fn new(default: MergeStrategy) -> Self {
+        Self {
+            default,
+            overrides: BTreeMap::new(),
+        }
+    }
+
+    /// Use `strategy` for the entry at `path` and those under it
+    pub fn with_override(
+        mut self,
+        path: &[&str],
+        strategy: MergeStrategy,
+    ) -> Self {
+        let path = path.iter().map(|key| key.to_string()).collect();
+        self.overrides.insert(path, strategy);
+        self
+    }
+
+    /// The strategy of the entry at `path`
+    pub fn strategy_at(&self, path: &[String]) -> MergeStrategy {
+        (0..=path.len())
+            .rev()
+            .find_map(|len| self.overrides.get(&path[..len]))
+            .copied()
+            .unwrap_or(self.default)
+    }
+
+    /// Merge the maps under the entry at `path`
+    fn merge_at(&self, path: &mut Vec<String>, left: CCL, right: CCL) -> CCL {
+        let CCL(mut lmap) = left;
+        let CCL(rmap) = right;
+
+        for (key, rvalues) in rmap {
+            let merged = match lmap.remove(&key) {
+                Some(lvalues) => {
+                    path.push(key.clone());
+                    let merged = self.combine(path, lvalues, rvalues);
+                    path.pop();
+                    merged
+                }
+                None => rvalues,
+            };
+            lmap.insert(key, merged);
+        }
+
+        CCL(lmap)
+    }
+
+    /// The map under the entry at `path`, which both sides have
+    fn combine(&self, path: &mut Vec<String>, left: CCL, right: CCL) -> CCL {
+        match self.strategy_at(path) {
+            MergeStrategy::Union => self.merge_at(path, left, right),
+            MergeStrategy::LastWins => {
+                let left = replace_values(left, &right);
+                self.merge_at(path, left, right)
+            }
+            MergeStrategy::FirstWins => {
+                let right = replace_values(right, &left);
+                self.merge_at(path, left, right)
+            }
+            MergeStrategy::Replace => {
+                let CCL(rmap) = &right;
+                if rmap.is_empty() { left } else { right }
+            }
+        }
+    }
+}
+
+/// `ccl` without the values that `by` does not have, unless `by` is empty.
+/// Only dropping values, and never for an empty `by`, keeps the merges
+/// associative: what is empty after a merge was empty on both sides.
+fn replace_values(ccl: CCL, by: &CCL) -> CCL {
+    let CCL(by) = by;
+    if by.is_empty() {
+        return ccl;
+    }
+    let CCL(mut map) = ccl;
+    map.retain(|key, CCL(nested)| !nested.is_empty() || by.contains_key(key));
+    CCL(map)
+}
+
+impl CCL {
+    /// Merge with `other` using the strategies of `options`. The entries
+    /// of `self` come first, so `LastWins` prefers `other`.
+    pub fn merge_with(self, other: CCL, options: &MergeOptions) -> CCL {
+        options.merge_at(&mut Vec::new(), self, other)
+    }
+
+    /// Merge `items` in order using the strategies of `options`
+    pub fn aggregate_with(items: Vec<CCL>, options: &MergeOptions) -> CCL {
+        items
+            .into_iter()
+            .fold(CCL::empty(), |acc, item| acc.merge_with(item, options))
+    }
+}
+
+/// A change both sides of a three-way merge made differently
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Conflict {
+    pub path: Vec<String>,
+    /// What our side has at `path`, or `None` if it removed the entry.
+    /// Where both sides changed the values of the entry, only the values.
+    pub ours: Option<CCL>,
+    pub theirs: Option<CCL>,
+}
+
+impl fmt::Display for Conflict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = self.path.join("=");
+        match (&self.ours, &self.theirs) {
+            (Some(ours), Some(theirs)) => write!(
+                f,
+                "both sides changed the values of '{}': {} and {}",
+                path,
+                values_text(ours),
+                values_text(theirs)
+            ),
+            (None, _) => {
+                write!(f, "ours removed '{}', theirs changed it", path)
+            }
+            (_, None) => {
+                write!(f, "theirs removed '{}', ours changed it", path)
+            }
+        }
+    }
+}
+
+fn values_text(ccl: &CCL) -> String {
+    let CCL(map) = ccl;
+    if map.is_empty() {
+        "nothing".to_string()
+    } else {
+        map.keys().cloned().collect::<Vec<_>>().join(", ")
+    }
+}
+
+/// The outcome of `merge3`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Merge3 {
+    pub merged: CCL,
+    pub conflicts: Vec<Conflict>,
+}
+
+impl Merge3 {
+    pub fn is_clean(&self) -> bool {
+        self.conflicts.is_empty()
+    }
+
+    /// `merged` as `CCL::pretty` writes it, after a `/= Conflict: ...`
+    /// comment line for each conflict
+    pub fn pretty(&self) -> String {
+        self.conflict_lines() + &self.merged.pretty()
+    }
+
+    /// The text `ours` was read from with `options`, edited through a
+    /// [`Cst`] into one that reads as `merged`, after a `/= Conflict: ...`
+    /// comment line for each conflict. Only the entries that read
+    /// differently change, so the rest of the text is kept as it was.
+    ///
+    /// `None` if `ours` does not parse, or if its layout cannot be edited
+    /// into `merged` (see [`Cst::update`]).
+    pub fn apply(&self, ours: &str, options: &ParseOptions) -> Option<String> {
+        let mut cst = Cst::parse(ours).ok()?;
+        cst.update(&self.merged, options);
+        let text = cst.to_string();
+        let key_vals = KeyVal::parse(&text).ok()?;
+        (CCL::parse_with(key_vals, options) == self.merged)
+            .then(|| self.conflict_lines() + &text)
+    }
+
+    fn conflict_lines(&self) -> String {
+        let mut text = String::new();
+        for conflict in &self.conflicts {
+            // Lines after the first are indented to stay in the comment
+            let message = conflict.to_string().replace('\n', "\n  ");
+            text.push_str(&format!("{}= Conflict: {}\n", COMMENT_KEY, message));
+        }
+        text
+    }
+}
+
+/// Apply the changes both `ours` and `theirs` made to `base`.
+///
+/// The values of an entry are merged as sets: a value either side removed
+/// is gone, and one either side added is there. If both sides replaced
+/// the values, removing some or setting some where there were none, and
+/// differently, that is a conflict. So is one side removing an entry the
+/// other changed. A conflict leaves what both sides have in the merge,
+/// for someone to resolve, and nothing in `merged` itself tells where.
+pub fn merge3(base: &CCL, ours: &CCL, theirs: &CCL) -> Merge3 {
+    let mut conflicts = Vec::new();
+    let merged = merge3_at(&mut Vec::new(), base, ours, theirs, &mut conflicts);
+    Merge3 { merged, conflicts }
+}
+
+fn merge3_at(
+    path: &mut Vec<String>,
+    base: &CCL,
+    ours: &CCL,
+    theirs: &CCL,
+    conflicts: &mut Vec<Conflict>,
+) -> CCL {
+    let (CCL(base), CCL(ours), CCL(theirs)) = (base, ours, theirs);
+    let values = |map: &'_ BTreeMap<String, CCL>| {
+        map.iter()
+            .filter(|(_, CCL(nested))| nested.is_empty())
+            .map(|(key, _)| key.clone())
+            .collect::<BTreeSet<_>>()
+    };
+    let (base_values, our_values, their_values) =
+        (values(base), values(ours), values(theirs));
+    let replaces = |values: &BTreeSet<String>| {
+        *values != base_values
+            && (base_values.is_empty() || !base_values.is_subset(values))
+    };
+    // The top level is no entry to have values
+    if !path.is_empty()
+        && our_values != their_values
+        && replaces(&our_values)
+        && replaces(&their_values)
+    {
+        let as_ccl = |values: &BTreeSet<String>| {
+            CCL(values.iter().map(|v| (v.clone(), CCL::empty())).collect())
+        };
+        conflicts.push(Conflict {
+            path: path.clone(),
+            ours: Some(as_ccl(&our_values)),
+            theirs: Some(as_ccl(&their_values)),
+        });
+    }
+    let kept = base_values
+        .iter()
+        .filter(|v| our_values.contains(*v) && their_values.contains(*v));
+    let added = our_values
+        .union(&their_values)
+        .filter(|v| !base_values.contains(*v));
+    let mut merged = kept
+        .chain(added)
+        .map(|v| (v.clone(), CCL::empty()))
+        .collect::<BTreeMap<_, _>>();
+
+    // Entries with entries under them, compared as a whole first
+    let keys = [base, ours, theirs]
+        .into_iter()
+        .flat_map(|map| map.keys())
+        .collect::<BTreeSet<_>>();
+    let empty = CCL::empty();
+    for key in keys {
+        let base = nested(base, key);
+        let (ours, theirs) = (nested(ours, key), nested(theirs, key));
+        path.push(key.clone());
+        let entry = if ours == theirs || theirs == base {
+            ours.cloned()
+        } else if ours == base {
+            theirs.cloned()
+        } else if let (Some(ours), Some(theirs)) = (ours, theirs) {
+            let base = base.unwrap_or(&empty);
+            Some(merge3_at(path, base, ours, theirs, conflicts))
+        } else {
+            conflicts.push(Conflict {
+                path: path.clone(),
+                ours: ours.cloned(),
+                theirs: theirs.cloned(),
+            });
+            ours.or(theirs).cloned()
+        };
+        path.pop();
+        if let Some(entry) = entry {
+            merged.insert(key.clone(), entry);
+        }
+    }
+
+    CCL(merged)
+}
+
+/// The entry at `key` of `map`, if it has entries under it
+fn nested<'a>(map: &'a BTreeMap<String, CCL>, key: &str) -> Option<&'a CCL> {
+    map.get(key).filter(|CCL(nested)| !nested.is_empty())
+}
@@ -11,4 +11,24 @@ pub trait Monoid {
             .into_iter()
             .fold(Self::empty(), |acc, item| acc.merge(item))
     }
+
+    /// Like `aggregate`, but merges as a balanced tree whose two halves are
+    /// reduced on separate threads. Equal to `aggregate` only if `merge` is
+    /// associative.
+    fn aggregate_parallel(items: Vec<Self>) -> Self
+    where
+        Self: Sized + Send,
+    {
+        if items.len() <= 1 {
+            return Self::aggregate(items);
+        }
+        let mut left = items;
+        let right = left.split_off(left.len() / 2);
+        let (left, right) = std::thread::scope(|scope| {
+            let left = scope.spawn(|| Self::aggregate_parallel(left));
+            let right = Self::aggregate_parallel(right);
+            (left.join().expect("merge thread panicked"), right)
+        });
+        left.merge(right)
+    }
 }
@@ -0,0 +1,129 @@
+use crate::error::AccessError;
+use crate::monoid::Monoid;
+use crate::parser::CCL;
+
+/// Changes by path, a list of keys from the outermost entry in. A value
+/// is a key with an empty map under it, so `a = b` is the path
+/// `["a", "b"]`, and these keep it that way.
+impl CCL {
+    /// The map under the entry at `path`, to change in place
+    pub fn get_mut(&mut self, path: &[&str]) -> Result<&mut CCL, AccessError> {
+        let mut ccl = self;
+        for (depth, key) in path.iter().enumerate() {
+            let CCL(map) = ccl;
+            ccl = map.get_mut(*key).ok_or_else(|| AccessError::Missing {
+                path: path[..=depth]
+                    .iter()
+                    .map(|key| key.to_string())
+                    .collect(),
+            })?;
+        }
+        Ok(ccl)
+    }
+
+    /// Add the entries along `path` that are missing, as `CCL::parse`
+    /// would for `a = b = c`, and return the map under the last one
+    pub fn insert_path(&mut self, path: &[&str]) -> &mut CCL {
+        path.iter().fold(self, |CCL(map), key| {
+            map.entry(key.to_string()).or_insert_with(CCL::empty)
+        })
+    }
+
+    /// Remove the entry at `path`, and everything under it. The entry it
+    /// was in stays, with one value less.
+    pub fn remove_path(&mut self, path: &[&str]) -> Option<CCL> {
+        let (key, parents) = path.split_last()?;
+        let CCL(map) = self.get_mut(parents).ok()?;
+        map.remove(*key)
+    }
+
+    /// Make `value` the only value of the entry at `path`, adding the
+    /// entry if it is missing. An empty `value` leaves it with no value,
+    /// as `key =` does.
+    pub fn set_path(&mut self, path: &[&str], value: &str) {
+        let ccl = self.insert_path(path);
+        *ccl = if value.is_empty() {
+            CCL::empty()
+        } else {
+            CCL::key(value)
+        };
+    }
+
+    /// The entry at `path`, for changes that depend on whether it is there
+    pub fn entry<'a>(&'a mut self, path: &[&str]) -> Entry<'a> {
+        Entry {
+            ccl: self,
+            path: path.iter().map(|key| key.to_string()).collect(),
+        }
+    }
+
+    /// Keep only the entries for which `keep` returns `true`, given the
+    /// path of the entry and the map under it. Entries are visited
+    /// outermost first, and not at all under one that is removed.
+    pub fn retain<F>(&mut self, mut keep: F)
+    where
+        F: FnMut(&[String], &CCL) -> bool,
+    {
+        self.retain_impl(&mut Vec::new(), &mut keep);
+    }
+
+    fn retain_impl<F>(&mut self, path: &mut Vec<String>, keep: &mut F)
+    where
+        F: FnMut(&[String], &CCL) -> bool,
+    {
+        let CCL(map) = self;
+        map.retain(|key, nested| {
+            path.push(key.clone());
+            let kept = keep(path, nested);
+            if kept {
+                nested.retain_impl(path, keep);
+            }
+            path.pop();
+            kept
+        });
+    }
+}
+
+/// An entry of a `CCL` that may or may not be there, from `CCL::entry`
+pub struct Entry<'a> {
+    ccl: &'a mut CCL,
+    path: Vec<String>,
+}
+
+fn as_strs(path: &[String]) -> Vec<&str> {
+    path.iter().map(String::as_str).collect()
+}
+
+impl<'a> Entry<'a> {
+    pub fn exists(&self) -> bool {
+        self.ccl.contains(&as_strs(&self.path))
+    }
+
+    /// The map under the entry, with `value` under it if it was missing
+    pub fn or_insert(self, value: CCL) -> &'a mut CCL {
+        self.or_insert_with(|| value)
+    }
+
+    pub fn or_insert_with<F: FnOnce() -> CCL>(self, value: F) -> &'a mut CCL {
+        let path = as_strs(&self.path);
+        let exists = self.ccl.contains(&path);
+        let ccl = self.ccl.insert_path(&path);
+        if !exists {
+            *ccl = value();
+        }
+        ccl
+    }
+
+    /// The map under the entry, empty if it was missing
+    pub fn or_default(self) -> &'a mut CCL {
+        self.or_insert_with(CCL::empty)
+    }
+
+    /// Change the map under the entry if it is there
+    pub fn and_modify<F: FnOnce(&mut CCL)>(self, f: F) -> Self {
+        if let Ok(ccl) = self.ccl.get_mut(&as_strs(&self.path)) {
+            f(ccl);
+        }
+        self
+    }
+}
@@ -0,0 +1,194 @@
+use crate::comment::CommentMode;
+use crate::error::{Location, ParseError};
+use crate::span::{Span, lines_with_offsets};
+
+/// How to read indentation and what to do with comments.
+///
+/// The default reproduces `KeyVal::parse`: every leading whitespace
+/// character counts as one column per byte, so a tab is as wide as a
+/// space and a multi-byte Unicode space such as U+3000 is three columns.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseOptions {
+    /// Tabs advance indentation to the next multiple of this many columns
+    pub tab_width: usize,
+    /// Fail on any tab in indentation
+    pub reject_tabs: bool,
+    /// Fail on indentation that is probably a mistake: mixing tabs and
+    /// spaces, non-ASCII whitespace, and dedenting to a column that no
+    /// enclosing line is at
+    pub strict_indentation: bool,
+    pub comments: CommentMode,
+    /// Fail on entries nested more than this many levels deep, counting
+    /// those at the top level as one, when parsing text straight into a
+    /// tree
+    pub max_depth: Option<usize>,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self {
+            tab_width: 1,
+            reject_tabs: false,
+            strict_indentation: false,
+            comments: CommentMode::Keep,
+            max_depth: None,
+        }
+    }
+}
+
+impl ParseOptions {
+    /// Strict indentation with 4-column tabs
+    pub fn strict() -> Self {
+        Self {
+            tab_width: 4,
+            strict_indentation: true,
+            ..Self::default()
+        }
+    }
+
+    /// Width in columns of the indentation of `line`
+    pub fn indent_width(&self, line: &str) -> usize {
+        let tab_width = self.tab_width.max(1);
+        line.chars()
+            .take_while(|c| c.is_whitespace())
+            .fold(0, |width, c| match c {
+                '\t' => (width / tab_width + 1) * tab_width,
+                _ => width + c.len_utf8(),
+            })
+    }
+
+    /// Check the indentation of every line of `data` against the options
+    pub(crate) fn check_indentation(
+        &self,
+        data: &str,
+    ) -> Result<(), ParseError> {
+        match self.indentation_errors(data).into_iter().next() {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+
+    /// Every line of `data` whose indentation the options reject, at most
+    /// one error per line
+    pub(crate) fn indentation_errors(&self, data: &str) -> Vec<ParseError> {
+        if !self.checks_indentation() {
+            return Vec::new();
+        }
+        let mut checker = IndentChecker::new(self);
+        lines_with_offsets(data)
+            .filter_map(|(offset, line)| checker.check(offset, line))
+            .map(|(message, span)| ParseError::BadIndentation {
+                message,
+                location: Location::new(data, span),
+            })
+            .collect()
+    }
+
+    /// Whether any option restricts indentation
+    pub(crate) fn checks_indentation(&self) -> bool {
+        self.reject_tabs || self.strict_indentation
+    }
+}
+
+/// The indentation checks of some `ParseOptions` on one piece of text, fed
+/// a line at a time
+#[derive(Clone, Debug)]
+pub(crate) struct IndentChecker<'a> {
+    options: &'a ParseOptions,
+    /// Whether the text indents with tabs, once known
+    uses_tabs: Option<bool>,
+    /// Columns of the enclosing lines, innermost last
+    levels: Vec<usize>,
+}
+
+impl<'a> IndentChecker<'a> {
+    pub(crate) fn new(options: &'a ParseOptions) -> Self {
+        Self {
+            options,
+            uses_tabs: None,
+            levels: Vec::new(),
+        }
+    }
+
+    /// Check the next line, starting at `offset`. Returns what is wrong
+    /// with its indentation and where.
+    pub(crate) fn check(
+        &mut self,
+        offset: usize,
+        line: &str,
+    ) -> Option<(String, Span)> {
+        if line.trim().is_empty() {
+            return None;
+        }
+        let indent = &line[..line.len() - line.trim_start().len()];
+        let error = |message: String, start: usize, len: usize| {
+            Some((message, Span::new(start, start + len)))
+        };
+
+        for (i, c) in indent.char_indices() {
+            if c == '\t' && self.options.reject_tabs {
+                return error("tab in indentation".into(), offset + i, 1);
+            }
+            if self.options.strict_indentation && c != ' ' && c != '\t' {
+                return error(
+                    format!(
+                        "non-ASCII whitespace U+{:04X} in indentation",
+                        c as u32
+                    ),
+                    offset + i,
+                    c.len_utf8(),
+                );
+            }
+        }
+        if !self.options.strict_indentation {
+            return None;
+        }
+
+        if indent.contains(' ') && indent.contains('\t') {
+            return error("mixed tabs and spaces".into(), offset, indent.len());
+        }
+        if !indent.is_empty() {
+            let tabs = indent.starts_with('\t');
+            match self.uses_tabs {
+                Some(expected) if expected != tabs => {
+                    let (this, other) = if tabs {
+                        ("tabs", "spaces")
+                    } else {
+                        ("spaces", "tabs")
+                    };
+                    return error(
+                        format!(
+                            "indented with {}, but earlier lines use {}",
+                            this, other
+                        ),
+                        offset,
+                        indent.len(),
+                    );
+                }
+                _ => self.uses_tabs = Some(tabs),
+            }
+        }
+
+        let width = self.options.indent_width(line);
+        match self.levels.last() {
+            Some(&level) if width <= level => {
+                while self.levels.last().is_some_and(|&level| level > width) {
+                    self.levels.pop();
+                }
+                if self.levels.last() != Some(&width) {
+                    // Carry on as if this were a new level
+                    self.levels.push(width);
+                    return error(
+                        "dedent does not match any outer indentation level"
+                            .into(),
+                        offset,
+                        indent.len(),
+                    );
+                }
+            }
+            _ => self.levels.push(width),
+        }
+
+        None
+    }
+}
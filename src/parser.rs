@@ -1,5 +1,11 @@
-use crate::key_val::{KeyVal, KeyValNode, KeyValTree, KeyVals};
+use crate::diagnostic::Diagnostic;
+use crate::error::ParseError;
+use crate::key_val::{
+    KeyVal, KeyValNode, KeyValSpan, KeyValSpanTree, KeyValTree, KeyVals,
+};
 use crate::monoid::Monoid;
+use crate::options::ParseOptions;
+use crate::walk::Visitor;
 use std::collections::BTreeMap;
 
 type KeyMap<T> = BTreeMap<String, T>;
@@ -34,6 +40,51 @@ impl Monoid for CCL {
     }
 }
 
+/// Spans of the key-value pairs that produced each path of a `CCL`.
+/// A leaf value's path ends with the value itself, so its span is the
+/// `value` part of the pair. A path defined more than once has one span per
+/// definition.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SourceMap(pub BTreeMap<Vec<String>, Vec<KeyValSpan>>);
+
+impl SourceMap {
+    pub fn get(&self, path: &[&str]) -> &[KeyValSpan] {
+        let path = path.iter().map(|key| key.to_string()).collect::<Vec<_>>();
+        self.0.get(&path).map_or(&[], |spans| spans.as_slice())
+    }
+
+    fn insert(&mut self, path: &[String], span: KeyValSpan) {
+        self.0.entry(path.to_vec()).or_default().push(span);
+    }
+
+    fn collect(
+        &mut self,
+        tree: &KeyValTree,
+        span_tree: &KeyValSpanTree,
+        path: &mut Vec<String>,
+    ) {
+        for (key, nodes) in tree {
+            path.push(key.clone());
+            for (node, node_span) in nodes.iter().zip(&span_tree[key]) {
+                self.insert(path, node_span.span);
+                match node {
+                    KeyValNode::Leaf(leaf) => {
+                        if !leaf.is_empty() {
+                            path.push(leaf.clone());
+                            self.insert(path, node_span.span);
+                            path.pop();
+                        }
+                    }
+                    KeyValNode::Tree(tree) => {
+                        self.collect(tree, &node_span.children, path)
+                    }
+                }
+            }
+            path.pop();
+        }
+    }
+}
+
 /// Helpers
 impl CCL {
     /// ```text
@@ -67,21 +118,20 @@ impl CCL {
     }
 
     pub fn pretty(&self) -> String {
-        let mut buf = String::new();
-        self.pretty_impl(0, &mut buf);
-        buf
-    }
+        struct Pretty(String);
 
-    /// terminal case is empty map (no key-value pairs to iterate over)
-    fn pretty_impl(&self, indent: usize, buf: &mut String) {
-        let CCL(map) = self;
-        for (key, value) in map {
-            let prefix = " ".repeat(indent);
-            buf.push_str(&prefix);
-            buf.push_str(key);
-            buf.push_str(" =\n");
-            value.pretty_impl(indent + 2, buf);
+        impl Visitor for Pretty {
+            fn enter(&mut self, path: &[&str], _ccl: &CCL) {
+                let Pretty(buf) = self;
+                buf.push_str(&" ".repeat(2 * (path.len() - 1)));
+                buf.push_str(path[path.len() - 1]);
+                buf.push_str(" =\n");
+            }
         }
+
+        let mut pretty = Pretty(String::new());
+        self.walk(&mut pretty);
+        pretty.0
     }
 }
 
@@ -115,6 +165,100 @@ impl CCL {
     pub fn parse(key_vals: KeyVals) -> CCL {
         CCL::parse_tree_to_fix(KeyVal::parse_flat_to_tree(&key_vals))
     }
+
+    /// Like `parse`, but with nested values parsed according to `options`
+    /// and comment entries kept or stripped according to `options.comments`
+    pub fn parse_with(key_vals: KeyVals, options: &ParseOptions) -> CCL {
+        CCL::parse_tree_to_fix(KeyVal::parse_flat_to_tree_with(
+            &key_vals, options,
+        ))
+    }
+
+    /// Like `parse`, but fails on a nested value that has an `=` yet is not
+    /// valid CCL instead of keeping it as a leaf. See
+    /// `KeyVal::try_parse_flat_to_tree`.
+    pub fn try_parse(key_vals: KeyVals) -> Result<CCL, ParseError> {
+        Ok(CCL::parse_tree_to_fix(KeyVal::try_parse_flat_to_tree(
+            &key_vals,
+        )?))
+    }
+
+    /// Like `parse_str`, but fails on a nested value that has an `=` yet is
+    /// not valid CCL, with the error located in `data`
+    pub fn try_parse_str(data: &str) -> Result<CCL, ParseError> {
+        Ok(CCL::parse_tree_to_fix(KeyVal::try_parse_tree(data)?))
+    }
+
+    /// Parse the text `data` all the way to a CCL, building the tree of
+    /// key-value pairs in a single pass:
+    /// parse_str data ≡ parse (KeyVal::parse data)
+    pub fn parse_str(data: &str) -> Result<CCL, ParseError> {
+        CCL::parse_str_with(data, &ParseOptions::default())
+    }
+
+    /// Like `parse_str`, but reads indentation and comments according to
+    /// `options`
+    pub fn parse_str_with(
+        data: &str,
+        options: &ParseOptions,
+    ) -> Result<CCL, ParseError> {
+        Ok(CCL::parse_tree_to_fix(KeyVal::parse_tree_with(
+            data, options,
+        )?))
+    }
+
+    /// Parse the text `data` all the way to a CCL, carrying on past
+    /// errors. The CCL holds everything that could be parsed; see
+    /// `KeyVal::parse_tree_recovering` for what is reported.
+    pub fn parse_recovering(
+        data: &str,
+        options: &ParseOptions,
+    ) -> (CCL, Vec<Diagnostic>) {
+        let (tree, diagnostics) = KeyVal::parse_tree_recovering(data, options);
+        (CCL::parse_tree_to_fix(tree), diagnostics)
+    }
+
+    /// Parse `key_vals` in up to `threads` chunks, one thread each, and
+    /// merge the per-chunk results as a tree. Since CCL's `merge` is
+    /// associative, the result is the same as `parse`.
+    pub fn parse_parallel(key_vals: KeyVals, threads: usize) -> CCL {
+        let chunk_size = key_vals.len().div_ceil(threads.max(1)).max(1);
+        let mut chunks = Vec::new();
+        let mut key_vals = key_vals.into_iter().peekable();
+        while key_vals.peek().is_some() {
+            chunks.push(key_vals.by_ref().take(chunk_size).collect::<Vec<_>>());
+        }
+
+        let ccls = std::thread::scope(|scope| {
+            let handles = chunks
+                .into_iter()
+                .map(|chunk| scope.spawn(|| CCL::parse(chunk)))
+                .collect::<Vec<_>>();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().expect("parser thread panicked"))
+                .collect::<Vec<_>>()
+        });
+        CCL::aggregate_parallel(ccls)
+    }
+
+    /// Like `parse_str`, but also returns where every path of the result
+    /// came from in `data`
+    pub fn parse_spanned(data: &str) -> Result<(CCL, SourceMap), ParseError> {
+        CCL::parse_spanned_with(data, &ParseOptions::default())
+    }
+
+    /// Like `parse_spanned`, but reads indentation and comments according
+    /// to `options`
+    pub fn parse_spanned_with(
+        data: &str,
+        options: &ParseOptions,
+    ) -> Result<(CCL, SourceMap), ParseError> {
+        let (tree, span_tree) = KeyVal::parse_tree_spanned_with(data, options)?;
+        let mut source_map = SourceMap::default();
+        source_map.collect(&tree, &span_tree, &mut Vec::new());
+        Ok((CCL::parse_tree_to_fix(tree), source_map))
+    }
 }
 #[cfg(test)]
 mod tests {
@@ -0,0 +1,230 @@
+//! Patches: entries to remove from a `CCL` and entries to add to it, as
+//! data that can be written as CCL, checked against the document it is
+//! applied to, and inverted.
+//!
+//! A patch is written as numbered removals and additions, each with the
+//! path of the entry as a chain of keys and what is under the entry:
+//!
+//! ```text
+//! remove =
+//!   1 =
+//!     path = database = pool
+//!     value =
+//!       size = 10
+//! add =
+//!   1 =
+//!     path = port = 443
+//! ```
+//!
+//! ```
+//! use ccl_rs::parser::CCL;
+//! use ccl_rs::patch::Patch;
+//!
+//! let old = CCL::parse_str("port = 8080").unwrap();
+//! let new = CCL::parse_str("port = 443\ndebug =").unwrap();
+//! let patch = Patch::from_diff(&old.diff(&new));
+//!
+//! let mut ccl = old.clone();
+//! ccl.apply_patch(&patch).unwrap();
+//! assert_eq!(ccl, new);
+//! ccl.apply_patch(&patch.invert()).unwrap();
+//! assert_eq!(ccl, old);
+//! ```
+
+use crate::diff::{Change, Diff};
+use crate::error::PatchError;
+use crate::monoid::Monoid;
+use crate::parser::CCL;
+use std::collections::BTreeMap;
+
+/// An entry of a patch: the path of keys to it and everything under it
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Edit {
+    pub path: Vec<String>,
+    pub value: CCL,
+}
+
+impl Edit {
+    pub fn new(path: &[&str], value: CCL) -> Self {
+        Self {
+            path: path.iter().map(|key| key.to_string()).collect(),
+            value,
+        }
+    }
+}
+
+/// Entries to remove and then entries to add. A removed entry must be
+/// there with exactly its value, and an added one must not be there yet,
+/// so that applying `invert` undoes applying the patch.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Patch {
+    pub remove: Vec<Edit>,
+    pub add: Vec<Edit>,
+}
+
+impl Patch {
+    /// The patch that turns the old document of `diff` into the new one
+    pub fn from_diff(diff: &Diff) -> Self {
+        let mut patch = Patch::default();
+        let with_key = |path: &[String], key: &str| Edit {
+            path: path.iter().cloned().chain([key.to_string()]).collect(),
+            value: CCL::empty(),
+        };
+        for change in &diff.changes {
+            match change {
+                Change::Added { path, value } => patch.add.push(Edit {
+                    path: path.clone(),
+                    value: value.clone(),
+                }),
+                Change::Removed { path, value } => patch.remove.push(Edit {
+                    path: path.clone(),
+                    value: value.clone(),
+                }),
+                Change::Changed { path, old, new } => {
+                    patch.remove.extend(old.iter().map(|v| with_key(path, v)));
+                    patch.add.extend(new.iter().map(|v| with_key(path, v)));
+                }
+            }
+        }
+        patch
+    }
+
+    /// The patch that undoes this one
+    pub fn invert(&self) -> Self {
+        Self {
+            remove: self.add.clone(),
+            add: self.remove.clone(),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.remove.is_empty() && self.add.is_empty()
+    }
+
+    /// The patch as a document; see [`crate::patch`]
+    pub fn to_ccl(&self) -> CCL {
+        let section = |edits: &[Edit]| {
+            let records = edits.iter().enumerate().map(|(i, edit)| {
+                let path =
+                    edit.path.iter().rev().fold(CCL::empty(), |nested, key| {
+                        CCL(BTreeMap::from([(key.clone(), nested)]))
+                    });
+                let mut record = BTreeMap::from([("path".to_string(), path)]);
+                let CCL(value) = &edit.value;
+                if !value.is_empty() {
+                    record.insert("value".to_string(), edit.value.clone());
+                }
+                // Padded, to read back in the same order
+                let width = edits.len().to_string().len();
+                (format!("{:0width$}", i + 1), CCL(record))
+            });
+            CCL(records.collect())
+        };
+        let mut sections = BTreeMap::new();
+        for (name, edits) in [("remove", &self.remove), ("add", &self.add)] {
+            if !edits.is_empty() {
+                sections.insert(name.to_string(), section(edits));
+            }
+        }
+        CCL(sections)
+    }
+
+    /// Read a patch in the form `to_ccl` writes
+    pub fn from_ccl(ccl: &CCL) -> Result<Self, PatchError> {
+        let invalid = |message: String| PatchError::Invalid { message };
+        let mut patch = Patch::default();
+        let CCL(sections) = ccl;
+        for (name, CCL(records)) in sections {
+            let edits = match name.as_str() {
+                "remove" => &mut patch.remove,
+                "add" => &mut patch.add,
+                _ => {
+                    return Err(invalid(format!("unknown section `{}`", name)));
+                }
+            };
+            for (number, CCL(record)) in records {
+                let at = || format!("{} {}", name, number);
+                if let Some(key) =
+                    record.keys().find(|key| *key != "path" && *key != "value")
+                {
+                    return Err(invalid(format!(
+                        "unknown key `{}` in {}",
+                        key,
+                        at()
+                    )));
+                }
+                let path = record
+                    .get("path")
+                    .ok_or_else(|| invalid(format!("no path in {}", at())))?;
+                let path = chain_keys(path).ok_or_else(|| {
+                    invalid(format!("the path of {} branches", at()))
+                })?;
+                if path.is_empty() {
+                    return Err(invalid(format!("empty path in {}", at())));
+                }
+                let value =
+                    record.get("value").cloned().unwrap_or_else(CCL::empty);
+                edits.push(Edit { path, value });
+            }
+        }
+        Ok(patch)
+    }
+}
+
+impl CCL {
+    /// Apply `patch`, or leave `self` as it is if any of it does not apply
+    pub fn apply_patch(&mut self, patch: &Patch) -> Result<(), PatchError> {
+        let mut ccl = self.clone();
+        for Edit { path, value } in &patch.remove {
+            let keys = path.iter().map(String::as_str).collect::<Vec<_>>();
+            if keys.is_empty() {
+                return Err(empty_path());
+            }
+            match ccl.get(&keys) {
+                Err(_) => {
+                    return Err(PatchError::Missing { path: path.clone() });
+                }
+                Ok(there) if there != value => {
+                    return Err(PatchError::Changed { path: path.clone() });
+                }
+                Ok(_) => ccl.remove_path(&keys),
+            };
+        }
+        for Edit { path, value } in &patch.add {
+            let keys = path.iter().map(String::as_str).collect::<Vec<_>>();
+            let (key, parents) = keys.split_last().ok_or_else(empty_path)?;
+            let CCL(map) = ccl
+                .get_mut(parents)
+                .map_err(|_| PatchError::NoParent { path: path.clone() })?;
+            if map.contains_key(*key) {
+                return Err(PatchError::Exists { path: path.clone() });
+            }
+            map.insert(key.to_string(), value.clone());
+        }
+        *self = ccl;
+        Ok(())
+    }
+}
+
+/// The keys of `ccl` if it is a chain of single keys, as `a = b = c` is
+fn chain_keys(mut ccl: &CCL) -> Option<Vec<String>> {
+    let mut keys = Vec::new();
+    loop {
+        let CCL(map) = ccl;
+        let mut entries = map.iter();
+        match (entries.next(), entries.next()) {
+            (None, _) => return Some(keys),
+            (Some((key, nested)), None) => {
+                keys.push(key.clone());
+                ccl = nested;
+            }
+            (Some(_), Some(_)) => return None,
+        }
+    }
+}
+
+fn empty_path() -> PatchError {
+    PatchError::Invalid {
+        message: "an entry with an empty path".into(),
+    }
+}
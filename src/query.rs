@@ -0,0 +1,310 @@
+//! Select entries of a `CCL` by path.
+//!
+//! A query is a list of segments separated by `=`, each matching keys one
+//! level down:
+//! - `key`: exactly that key. Surrounding whitespace is ignored.
+//! - `*`: any key
+//! - `**`: any number of levels, none included
+//! - a glob such as `db_*` or `host?`: keys it matches, `*` standing for
+//!   any text and `?` for any one character
+//! - `/regex/`: keys the regex matches as a whole
+//! - `"quoted key"`: exactly the text between the quotes
+//!
+//! A backslash makes the next character literal, so `a\=b` is the single
+//! key `a=b` and `\*` is the key `*`. In quotes, only `\"` and `\\` are
+//! escapes.
+//!
+//! ```
+//! use ccl_rs::parser::CCL;
+//! use ccl_rs::query::Query;
+//!
+//! let ccl = CCL::parse_str(
+//!     "db =\n  main =\n    port = 5432\n  cache =\n    port = 6379\n",
+//! )
+//! .unwrap();
+//! let query = Query::parse("db=*=port").unwrap();
+//! let ports = query
+//!     .matches(&ccl)
+//!     .iter()
+//!     .map(|m| m.value.get_str(&[]).unwrap())
+//!     .collect::<Vec<_>>();
+//! assert_eq!(ports, ["6379", "5432"]);
+//! ```
+
+use crate::error::QueryError;
+use crate::parser::CCL;
+use regex::Regex;
+use std::collections::BTreeMap;
+
+#[derive(Clone, Debug)]
+enum Segment {
+    Key(String),
+    AnyKey,
+    AnyDepth,
+    /// A glob or regex, anchored at both ends
+    Pattern(Regex),
+}
+
+impl Segment {
+    fn matches(&self, key: &str) -> bool {
+        match self {
+            Segment::Key(expected) => key == expected,
+            Segment::AnyKey => true,
+            Segment::AnyDepth => {
+                unreachable!("Never: matched level by level")
+            }
+            Segment::Pattern(regex) => regex.is_match(key),
+        }
+    }
+}
+
+/// A parsed query
+#[derive(Clone, Debug)]
+pub struct Query {
+    segments: Vec<Segment>,
+}
+
+/// An entry selected by a query
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Match<'a> {
+    /// Keys leading to the entry, from the outermost entry in
+    pub path: Vec<String>,
+    /// The map under the entry
+    pub value: &'a CCL,
+}
+
+impl Match<'_> {
+    /// The value nested under the keys of its path
+    pub fn to_ccl(&self) -> CCL {
+        self.path.iter().rev().fold(self.value.clone(), |ccl, key| {
+            CCL(BTreeMap::from([(key.clone(), ccl)]))
+        })
+    }
+}
+
+impl Query {
+    pub fn parse(query: &str) -> Result<Self, QueryError> {
+        let segments = split_segments(query)?
+            .into_iter()
+            .map(|(offset, raw)| parse_segment(offset, raw))
+            .collect::<Result<_, _>>()?;
+        Ok(Self { segments })
+    }
+
+    /// The keys of a query that has no wildcards or patterns, which
+    /// selects at most one entry
+    pub fn literal_path(&self) -> Option<Vec<&str>> {
+        self.segments
+            .iter()
+            .map(|segment| match segment {
+                Segment::Key(key) => Some(key.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Every entry of `ccl` the query selects, once each, ordered by path
+    pub fn matches<'a>(&self, ccl: &'a CCL) -> Vec<Match<'a>> {
+        let mut found = BTreeMap::new();
+        collect(ccl, &self.segments, &mut Vec::new(), &mut found);
+        found
+            .into_iter()
+            .map(|(path, value)| Match { path, value })
+            .collect()
+    }
+}
+
+impl std::str::FromStr for Query {
+    type Err = QueryError;
+
+    fn from_str(query: &str) -> Result<Self, QueryError> {
+        Query::parse(query)
+    }
+}
+
+impl CCL {
+    /// The entries selected by `query`; see [`crate::query`]
+    pub fn query(&self, query: &str) -> Result<Vec<Match<'_>>, QueryError> {
+        Ok(Query::parse(query)?.matches(self))
+    }
+}
+
+fn collect<'a>(
+    ccl: &'a CCL,
+    segments: &[Segment],
+    path: &mut Vec<String>,
+    found: &mut BTreeMap<Vec<String>, &'a CCL>,
+) {
+    let CCL(map) = ccl;
+    match segments {
+        [] => {
+            found.entry(path.clone()).or_insert(ccl);
+        }
+        [Segment::AnyDepth, rest @ ..] => {
+            collect(ccl, rest, path, found);
+            for (key, nested) in map {
+                path.push(key.clone());
+                collect(nested, segments, path, found);
+                path.pop();
+            }
+        }
+        [Segment::Key(key), rest @ ..] => {
+            if let Some(nested) = map.get(key) {
+                path.push(key.clone());
+                collect(nested, rest, path, found);
+                path.pop();
+            }
+        }
+        [segment, rest @ ..] => {
+            for (key, nested) in map {
+                if segment.matches(key) {
+                    path.push(key.clone());
+                    collect(nested, rest, path, found);
+                    path.pop();
+                }
+            }
+        }
+    }
+}
+
+fn syntax_error(position: usize, message: &str) -> QueryError {
+    QueryError::Syntax {
+        position,
+        message: message.to_string(),
+    }
+}
+
+/// Split `query` at the `=` outside quotes and not escaped, keeping the
+/// offset of each piece
+fn split_segments(query: &str) -> Result<Vec<(usize, &str)>, QueryError> {
+    let mut segments = Vec::new();
+    let mut start = 0;
+    let mut quote = None;
+    let mut chars = query.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            // Skips the escaped character
+            '\\' if chars.next().is_none() => {
+                return Err(syntax_error(i, "trailing backslash"));
+            }
+            '"' if quote.is_some() => quote = None,
+            // Quotes only open at the start of a segment
+            '"' if query[start..i].trim().is_empty() => quote = Some(i),
+            '=' if quote.is_none() => {
+                segments.push((start, &query[start..i]));
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    if let Some(i) = quote {
+        return Err(syntax_error(i, "unclosed quote"));
+    }
+    segments.push((start, &query[start..]));
+    Ok(segments)
+}
+
+fn parse_segment(offset: usize, raw: &str) -> Result<Segment, QueryError> {
+    let offset = offset + raw.len() - raw.trim_start().len();
+    let raw = trim_unescaped(raw);
+    match raw {
+        "*" => return Ok(Segment::AnyKey),
+        "**" => return Ok(Segment::AnyDepth),
+        _ => {}
+    }
+
+    if let Some(quoted) = raw.strip_prefix('"') {
+        let mut key = String::new();
+        let mut chars = quoted.char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '\\' => match chars.next() {
+                    Some((_, c @ ('"' | '\\'))) => key.push(c),
+                    Some((_, c)) => key.extend(['\\', c]),
+                    None => unreachable!("Never: checked when splitting"),
+                },
+                '"' if i + 1 == quoted.len() => {
+                    return Ok(Segment::Key(key));
+                }
+                '"' => {
+                    return Err(syntax_error(
+                        offset + 1 + i + 1,
+                        "text after closing quote",
+                    ));
+                }
+                c => key.push(c),
+            }
+        }
+        unreachable!("Never: checked when splitting")
+    }
+
+    if raw.len() >= 2 && raw.starts_with('/') && ends_unescaped(raw, '/') {
+        let pattern = raw[1..raw.len() - 1]
+            .replace("\\=", "=")
+            .replace("\\/", "/");
+        // Compiled on its own first, for errors that show the pattern as
+        // written
+        return Regex::new(&pattern)
+            .and_then(|_| Regex::new(&format!("^(?:{})$", pattern)))
+            .map(Segment::Pattern)
+            .map_err(|err| QueryError::InvalidRegex {
+                pattern,
+                message: err.to_string(),
+            });
+    }
+
+    let mut key = String::new();
+    let mut glob = String::new();
+    let mut is_glob = false;
+    let mut chars = raw.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                let c = chars.next().expect("Never: checked when splitting");
+                key.push(c);
+                glob.push_str(&regex::escape(&c.to_string()));
+            }
+            '*' => {
+                is_glob = true;
+                glob.push_str(".*");
+            }
+            '?' => {
+                is_glob = true;
+                glob.push('.');
+            }
+            c => {
+                key.push(c);
+                glob.push_str(&regex::escape(&c.to_string()));
+            }
+        }
+    }
+    if is_glob {
+        let regex = Regex::new(&format!("^(?s:{})$", glob))
+            .expect("Never: escaped glob");
+        Ok(Segment::Pattern(regex))
+    } else {
+        Ok(Segment::Key(key))
+    }
+}
+
+/// Whether `text` ends with `c` not preceded by an escaping backslash
+fn ends_unescaped(text: &str, c: char) -> bool {
+    let Some(rest) = text.strip_suffix(c) else {
+        return false;
+    };
+    let backslashes = rest.len() - rest.trim_end_matches('\\').len();
+    backslashes.is_multiple_of(2)
+}
+
+/// `text` without surrounding whitespace, except whitespace escaped with a
+/// backslash
+fn trim_unescaped(text: &str) -> &str {
+    let text = text.trim_start();
+    let trimmed = text.trim_end();
+    let backslashes = trimmed.len() - trimmed.trim_end_matches('\\').len();
+    if backslashes.is_multiple_of(2) {
+        return trimmed;
+    }
+    let escaped = text[trimmed.len()..].chars().next();
+    &text[..trimmed.len() + escaped.map_or(0, char::len_utf8)]
+}
@@ -0,0 +1,581 @@
+//! Schemas for CCL documents, themselves written in CCL.
+//!
+//! A schema describes a section: the `fields` it has, each with the spec
+//! of its entry, a spec for `entries` under any other key, and whether the
+//! section is `open` to keys it does not declare. Sections are closed
+//! unless they say otherwise, so a misspelt key is reported.
+//!
+//! The spec of an entry has a `type`, one of `string`, `int`, `bool`,
+//! `enum`, `regex` and `section`, and says whether it is `required`. Its
+//! `count` is how many values it takes: `1`, a range like `0..2`, or an
+//! open one like `1..`. Values are the keys under the entry, or the items
+//! of a list written with the `= item` convention. A `bool` is `true` or
+//! `false`, or a flag with no value, and takes one value at most; other
+//! types take exactly one by default. An `enum` lists its `values`, a
+//! `regex` has a `pattern` every value must match as a whole, and a
+//! `section` has the `fields`, `entries` and `open` of a schema. An entry
+//! with `fields` or `entries` and no `type` is a section, and one with
+//! neither a string.
+//!
+//! ```
+//! use ccl_rs::parser::CCL;
+//! use ccl_rs::schema::Schema;
+//!
+//! let schema = CCL::parse_str(
+//!     r#"
+//! fields =
+//!   port =
+//!     type = int
+//!     required = true
+//!   mode =
+//!     type = enum
+//!     values =
+//!       = ro
+//!       = rw
+//! "#,
+//! )
+//! .unwrap();
+//! let schema = Schema::from_ccl(&schema).unwrap();
+//!
+//! let config = CCL::parse_str("port = http\nmdoe = ro").unwrap();
+//! let violations = schema
+//!     .validate(&config)
+//!     .iter()
+//!     .map(|violation| violation.to_string())
+//!     .collect::<Vec<_>>();
+//! assert_eq!(
+//!     violations,
+//!     [
+//!         "Invalid value 'http' of 'port': expected an int",
+//!         "Unknown key 'mdoe'",
+//!     ]
+//! );
+//! ```
+
+use crate::comment::COMMENT_KEY;
+use crate::error::{Location, ParseError, SchemaError};
+use crate::parser::{CCL, SourceMap};
+use regex::Regex;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+/// A schema, which is the spec of the top-level section
+#[derive(Clone, Debug)]
+pub struct Schema {
+    pub root: Section,
+}
+
+/// The keys a section may have and what is under them
+#[derive(Clone, Debug, Default)]
+pub struct Section {
+    pub fields: BTreeMap<String, Field>,
+    /// The spec of every key not in `fields`
+    pub entries: Option<Box<Field>>,
+    /// Whether keys not in `fields` are allowed without `entries`
+    pub open: bool,
+}
+
+/// The spec of an entry
+#[derive(Clone, Debug)]
+pub struct Field {
+    pub required: bool,
+    /// How many values the entry takes; not checked for sections
+    pub count: Count,
+    pub kind: Kind,
+}
+
+/// What the values of an entry are
+#[derive(Clone, Debug)]
+pub enum Kind {
+    String,
+    Int,
+    Bool,
+    Enum(BTreeSet<String>),
+    /// Values matching `regex`, compiled from `pattern` anchored at both
+    /// ends
+    Regex {
+        pattern: String,
+        regex: Regex,
+    },
+    Section(Section),
+}
+
+/// A range of numbers of values, with no upper bound for a `max` of `None`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Count {
+    pub min: usize,
+    pub max: Option<usize>,
+}
+
+impl Count {
+    pub fn exactly(count: usize) -> Self {
+        Self {
+            min: count,
+            max: Some(count),
+        }
+    }
+
+    pub fn contains(&self, count: usize) -> bool {
+        self.min <= count && self.max.is_none_or(|max| count <= max)
+    }
+}
+
+impl std::str::FromStr for Count {
+    type Err = String;
+
+    /// `N`, `N..M` or `N..`
+    fn from_str(text: &str) -> Result<Self, String> {
+        let invalid =
+            || format!("invalid count `{}`, expected N, N..M or N..", text);
+        let number = |text: &str| text.trim().parse().map_err(|_| invalid());
+        let count = match text.split_once("..") {
+            None => Count::exactly(number(text)?),
+            Some((min, max)) if max.trim().is_empty() => Count {
+                min: number(min)?,
+                max: None,
+            },
+            Some((min, max)) => Count {
+                min: number(min)?,
+                max: Some(number(max)?),
+            },
+        };
+        match count.max {
+            Some(max) if max < count.min => Err(invalid()),
+            _ => Ok(count),
+        }
+    }
+}
+
+impl fmt::Display for Count {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let values = |count: usize| if count == 1 { "value" } else { "values" };
+        match self.max {
+            Some(max) if max == self.min => {
+                write!(f, "{} {}", max, values(max))
+            }
+            Some(max) => write!(f, "{} to {} values", self.min, max),
+            None => write!(f, "at least {} {}", self.min, values(self.min)),
+        }
+    }
+}
+
+/// What is wrong with an entry
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum ViolationKind {
+    /// A required key that is not there
+    Missing,
+    /// A key that a closed section does not declare
+    Unknown,
+    /// A number of values outside the count of the entry
+    Count { count: Count, values: Vec<String> },
+    /// A value with entries under it where a plain value was expected
+    Nested { value: String },
+    /// A value not of the type of the entry, with what was expected
+    Invalid { value: String, expected: String },
+}
+
+/// A place where a document does not follow its schema, at the path of
+/// keys of the entry at fault
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Violation {
+    pub path: Vec<String>,
+    pub kind: ViolationKind,
+    /// Where the entry, or for a missing one the entry it is missing
+    /// from, is in the source; only known from `Schema::validate_str`
+    pub location: Option<Location>,
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (key, parents) = self
+            .path
+            .split_last()
+            .expect("Never: violation at the top level");
+        match &self.kind {
+            ViolationKind::Missing => {
+                write!(f, "Missing required key '{}'", key)?;
+            }
+            ViolationKind::Unknown => write!(f, "Unknown key '{}'", key)?,
+            ViolationKind::Count { count, values } => {
+                write!(
+                    f,
+                    "Expected {} of '{}', found {}",
+                    count,
+                    key,
+                    values.len()
+                )?;
+                if !values.is_empty() {
+                    write!(f, ": {}", values.join(", "))?;
+                }
+            }
+            ViolationKind::Nested { value } => write!(
+                f,
+                "Expected a plain value of '{}', found entries under '{}'",
+                key, value
+            )?,
+            ViolationKind::Invalid { value, expected } => write!(
+                f,
+                "Invalid value '{}' of '{}': expected {}",
+                value, key, expected
+            )?,
+        }
+        if !parents.is_empty() {
+            write!(f, " (in the value of {})", parents.join("="))?;
+        }
+        Ok(())
+    }
+}
+
+const TYPES: [&str; 6] = ["string", "int", "bool", "enum", "regex", "section"];
+const SECTION_KEYS: [&str; 3] = ["fields", "entries", "open"];
+
+impl Schema {
+    /// Read a schema written as described in [`crate::schema`]
+    pub fn from_ccl(ccl: &CCL) -> Result<Self, SchemaError> {
+        let mut path = Vec::new();
+        check_keys(ccl, &SECTION_KEYS, &path)?;
+        let root = parse_section(ccl, &mut path)?;
+        Ok(Self { root })
+    }
+
+    /// Every violation of the schema in `ccl`. Comments are not checked.
+    pub fn validate(&self, ccl: &CCL) -> Vec<Violation> {
+        let mut violations = Vec::new();
+        check_section(&self.root, ccl, &mut Vec::new(), &mut violations);
+        violations
+    }
+
+    /// Parse `data` and validate it, locating every violation in `data`.
+    /// The violations come in the order of the source, those with no
+    /// location last.
+    pub fn validate_str(
+        &self,
+        data: &str,
+    ) -> Result<Vec<Violation>, ParseError> {
+        let (ccl, source_map) = CCL::parse_spanned(data)?;
+        let mut violations = self.validate(&ccl);
+        for violation in &mut violations {
+            violation.location = locate(data, &source_map, violation);
+        }
+        violations.sort_by_key(|violation| {
+            violation
+                .location
+                .as_ref()
+                .map_or(usize::MAX, |location| location.span.start)
+        });
+        Ok(violations)
+    }
+}
+
+fn invalid(path: &[String], message: String) -> SchemaError {
+    SchemaError::Invalid {
+        path: path.to_vec(),
+        message,
+    }
+}
+
+/// The keys of `spec` other than comments
+fn spec_keys(spec: &CCL) -> impl Iterator<Item = &String> {
+    let CCL(map) = spec;
+    map.keys().filter(|key| *key != COMMENT_KEY)
+}
+
+fn check_keys(
+    spec: &CCL,
+    allowed: &[&str],
+    path: &[String],
+) -> Result<(), SchemaError> {
+    match spec_keys(spec).find(|key| !allowed.contains(&key.as_str())) {
+        Some(key) => Err(invalid(
+            path,
+            format!(
+                "unknown key `{}`, expected one of: {}",
+                key,
+                allowed.join(", ")
+            ),
+        )),
+        None => Ok(()),
+    }
+}
+
+/// The plain value of the setting `key` of `spec`, if it has one
+fn setting<'a>(
+    spec: &'a CCL,
+    key: &str,
+    path: &[String],
+) -> Result<Option<&'a str>, SchemaError> {
+    if !spec.contains(&[key]) {
+        return Ok(None);
+    }
+    spec.get_str(&[key]).map(Some).map_err(|_| {
+        invalid(path, format!("expected one plain value of `{}`", key))
+    })
+}
+
+fn flag(spec: &CCL, key: &str, path: &[String]) -> Result<bool, SchemaError> {
+    match setting(spec, key, path)? {
+        None => Ok(false),
+        Some("" | "true") => Ok(true),
+        Some("false") => Ok(false),
+        Some(value) => Err(invalid(
+            path,
+            format!("expected true or false for `{}`, found `{}`", key, value),
+        )),
+    }
+}
+
+fn parse_section(
+    spec: &CCL,
+    path: &mut Vec<String>,
+) -> Result<Section, SchemaError> {
+    let mut section = Section {
+        open: flag(spec, "open", path)?,
+        ..Section::default()
+    };
+    if let Ok(fields) = spec.get(&["fields"]) {
+        path.push("fields".to_string());
+        let CCL(map) = fields;
+        for (key, field) in map {
+            if key == COMMENT_KEY {
+                continue;
+            }
+            path.push(key.clone());
+            section
+                .fields
+                .insert(key.clone(), parse_field(field, path)?);
+            path.pop();
+        }
+        path.pop();
+    }
+    if let Ok(entries) = spec.get(&["entries"]) {
+        path.push("entries".to_string());
+        section.entries = Some(Box::new(parse_field(entries, path)?));
+        path.pop();
+    }
+    Ok(section)
+}
+
+fn parse_field(
+    spec: &CCL,
+    path: &mut Vec<String>,
+) -> Result<Field, SchemaError> {
+    let type_name = match setting(spec, "type", path)? {
+        Some(type_name) => type_name,
+        None if SECTION_KEYS.iter().any(|key| spec.contains(&[key])) => {
+            "section"
+        }
+        None => "string",
+    };
+    let own_keys: &[&str] = match type_name {
+        "enum" => &["values"],
+        "regex" => &["pattern"],
+        "section" => &SECTION_KEYS,
+        _ => &[],
+    };
+    let mut allowed = vec!["type", "required"];
+    if type_name != "section" {
+        allowed.push("count");
+    }
+    allowed.extend(own_keys);
+    check_keys(spec, &allowed, path)?;
+
+    let kind = match type_name {
+        "string" => Kind::String,
+        "int" => Kind::Int,
+        "bool" => Kind::Bool,
+        "enum" => {
+            let values = spec.get_list(&["values"]).unwrap_or_default();
+            if values.is_empty() {
+                return Err(invalid(path, "an enum needs `values`".into()));
+            }
+            Kind::Enum(values.into_iter().map(String::from).collect())
+        }
+        "regex" => {
+            let pattern = setting(spec, "pattern", path)?.ok_or_else(|| {
+                invalid(path, "a regex needs a `pattern`".into())
+            })?;
+            let regex_error = |err: regex::Error| SchemaError::InvalidRegex {
+                path: path.clone(),
+                pattern: pattern.to_string(),
+                message: err.to_string(),
+            };
+            // Compiled alone first, so that errors are about the pattern
+            Regex::new(pattern).map_err(regex_error)?;
+            let regex = Regex::new(&format!("^(?:{})$", pattern))
+                .map_err(regex_error)?;
+            Kind::Regex {
+                pattern: pattern.to_string(),
+                regex,
+            }
+        }
+        "section" => Kind::Section(parse_section(spec, path)?),
+        _ => {
+            return Err(invalid(
+                path,
+                format!(
+                    "unknown type `{}`, expected one of: {}",
+                    type_name,
+                    TYPES.join(", ")
+                ),
+            ));
+        }
+    };
+    let count = match setting(spec, "count", path)? {
+        Some(count) => count.parse().map_err(|err| invalid(path, err))?,
+        None if matches!(kind, Kind::Bool) => Count {
+            min: 0,
+            max: Some(1),
+        },
+        None => Count::exactly(1),
+    };
+    Ok(Field {
+        required: flag(spec, "required", path)?,
+        count,
+        kind,
+    })
+}
+
+fn check_section(
+    section: &Section,
+    ccl: &CCL,
+    path: &mut Vec<String>,
+    violations: &mut Vec<Violation>,
+) {
+    let CCL(map) = ccl;
+    for (key, field) in &section.fields {
+        path.push(key.clone());
+        match map.get(key) {
+            Some(value) => check_field(field, value, path, violations),
+            None if field.required => {
+                violations.push(violation(path, ViolationKind::Missing));
+            }
+            None => {}
+        }
+        path.pop();
+    }
+    for (key, value) in map {
+        if key == COMMENT_KEY || section.fields.contains_key(key) {
+            continue;
+        }
+        path.push(key.clone());
+        match &section.entries {
+            Some(field) => check_field(field, value, path, violations),
+            None if !section.open => {
+                violations.push(violation(path, ViolationKind::Unknown));
+            }
+            None => {}
+        }
+        path.pop();
+    }
+}
+
+fn check_field(
+    field: &Field,
+    ccl: &CCL,
+    path: &mut Vec<String>,
+    violations: &mut Vec<Violation>,
+) {
+    if let Kind::Section(section) = &field.kind {
+        check_section(section, ccl, path, violations);
+        return;
+    }
+    let mut violate = |kind| violations.push(violation(path, kind));
+    let values = plain_values(ccl);
+    if !field.count.contains(values.len()) {
+        violate(ViolationKind::Count {
+            count: field.count,
+            values: values.iter().map(|(value, _)| value.to_string()).collect(),
+        });
+    }
+    for (value, CCL(nested)) in values {
+        if !nested.is_empty() {
+            violate(ViolationKind::Nested {
+                value: value.clone(),
+            });
+        } else if let Some(expected) = mismatch(&field.kind, value) {
+            violate(ViolationKind::Invalid {
+                value: value.clone(),
+                expected,
+            });
+        }
+    }
+}
+
+fn violation(path: &[String], kind: ViolationKind) -> Violation {
+    Violation {
+        path: path.to_vec(),
+        kind,
+        location: None,
+    }
+}
+
+/// The values under an entry, or the items of a list written with the
+/// `= item` convention, as `CCL::get_list` reads them
+fn plain_values(ccl: &CCL) -> Vec<(&String, &CCL)> {
+    let CCL(map) = ccl;
+    let values = map
+        .iter()
+        .filter(|(key, _)| *key != COMMENT_KEY)
+        .collect::<Vec<_>>();
+    match values.as_slice() {
+        [(key, CCL(items))] if key.is_empty() => items.iter().collect(),
+        _ => values,
+    }
+}
+
+/// What a value of `kind` should have been, if `value` is not one
+fn mismatch(kind: &Kind, value: &str) -> Option<String> {
+    let matches = match kind {
+        Kind::String | Kind::Section(_) => true,
+        Kind::Int => value.parse::<i64>().is_ok(),
+        Kind::Bool => value == "true" || value == "false",
+        Kind::Enum(values) => values.contains(value),
+        Kind::Regex { regex, .. } => regex.is_match(value),
+    };
+    if matches {
+        return None;
+    }
+    Some(match kind {
+        Kind::Int => "an int".to_string(),
+        Kind::Bool => "true or false".to_string(),
+        Kind::Enum(values) => format!(
+            "one of: {}",
+            values.iter().cloned().collect::<Vec<_>>().join(", ")
+        ),
+        Kind::Regex { pattern, .. } => format!("a match for /{}/", pattern),
+        Kind::String | Kind::Section(_) => unreachable!("matches anything"),
+    })
+}
+
+/// Where the source defines the entry of `violation`: the value at fault
+/// for a value, the entry a missing key is missing from, or else the key
+fn locate(
+    data: &str,
+    source_map: &SourceMap,
+    violation: &Violation,
+) -> Option<Location> {
+    let path = violation.path.iter().map(String::as_str);
+    let mut path = path.collect::<Vec<_>>();
+    let value = match &violation.kind {
+        ViolationKind::Nested { value }
+        | ViolationKind::Invalid { value, .. } => Some(value.as_str()),
+        ViolationKind::Missing => {
+            path.pop();
+            None
+        }
+        ViolationKind::Unknown | ViolationKind::Count { .. } => None,
+    };
+    if let Some(value) = value {
+        // A value directly under the entry or an item of its list
+        for at in [vec![value], vec!["", value]] {
+            let at = path.iter().copied().chain(at).collect::<Vec<_>>();
+            if let Some(span) = source_map.get(&at).first() {
+                return Some(Location::new(data, span.value));
+            }
+        }
+    }
+    (1..=path.len())
+        .rev()
+        .find_map(|len| source_map.get(&path[..len]).first())
+        .map(|span| Location::new(data, span.key))
+}
@@ -0,0 +1,527 @@
+//! Serialize typed values to CCL text, the inverse of the mapping in
+//! [`crate::de`]:
+//! - structs and maps: one entry per field or key, nested values indented
+//!   under their key. Fields that are `None` are left out, and `Some` of
+//!   an empty value is an error, since it would read back as `None`.
+//! - scalars: `key = value`, with `Display`. Strings must read back the
+//!   same, so they cannot contain `=` or line breaks, or start or end with
+//!   whitespace.
+//! - sequences: one `= item` entry per item. Since a CCL is a map, only
+//!   sorted items without duplicates read back in the same order.
+//! - enums: the variant name for unit variants, or else an entry with the
+//!   variant's content under it
+//!
+//! ```
+//! use serde::Serialize;
+//!
+//! #[derive(Serialize)]
+//! struct Config {
+//!     name: String,
+//!     ports: Vec<u16>,
+//!     proxy: Option<String>,
+//! }
+//!
+//! let config = Config {
+//!     name: "api".into(),
+//!     ports: vec![8000, 8001],
+//!     proxy: None,
+//! };
+//! assert_eq!(
+//!     ccl_rs::ser::to_string(&config).unwrap(),
+//!     "name = api\nports =\n  = 8000\n  = 8001\n",
+//! );
+//! ```
+
+use crate::de::Error;
+use serde::ser::{
+    self, Serialize, SerializeMap, SerializeSeq, SerializeStruct,
+    SerializeStructVariant, SerializeTuple, SerializeTupleStruct,
+    SerializeTupleVariant,
+};
+use std::fmt::Display;
+
+const INDENT: &str = "  ";
+
+impl ser::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Error::Custom {
+            path: Vec::new(),
+            message: msg.to_string(),
+        }
+    }
+}
+
+/// Serialize `value` as CCL text. Only values that serialize to entries,
+/// such as structs, maps and sequences, can be written at the top level.
+pub fn to_string<T: Serialize + ?Sized>(value: &T) -> Result<String, Error> {
+    match value.serialize(Serializer::new(Vec::new()))? {
+        Value::Entries(entries) => {
+            let mut out = String::new();
+            write_entries(&mut out, &entries, 0);
+            Ok(out)
+        }
+        Value::None => Ok(String::new()),
+        Value::Scalar(scalar) => Err(Error::Custom {
+            path: Vec::new(),
+            message: format!(
+                "cannot write the single value `{}` as CCL, only entries",
+                scalar
+            ),
+        }),
+    }
+}
+
+/// A serialized value, before it is written out
+#[derive(Clone, Debug)]
+pub enum Value {
+    /// A value on the same line as its key
+    Scalar(String),
+    /// Entries indented under the key, in order
+    Entries(Vec<(String, Value)>),
+    /// An absent optional value
+    None,
+}
+
+fn write_entries(out: &mut String, entries: &[(String, Value)], depth: usize) {
+    for (key, value) in entries {
+        out.push_str(&INDENT.repeat(depth));
+        out.push_str(key);
+        if !key.is_empty() {
+            out.push(' ');
+        }
+        out.push('=');
+        match value {
+            Value::Scalar(scalar) if !scalar.is_empty() => {
+                out.push(' ');
+                out.push_str(scalar);
+                out.push('\n');
+            }
+            Value::Entries(entries) => {
+                out.push('\n');
+                write_entries(out, entries, depth + 1);
+            }
+            Value::Scalar(_) | Value::None => out.push('\n'),
+        }
+    }
+}
+
+/// Serializes to a `Value`
+pub struct Serializer {
+    /// Keys leading to the value
+    path: Vec<String>,
+}
+
+impl Serializer {
+    pub fn new(path: Vec<String>) -> Self {
+        Self { path }
+    }
+
+    fn error(&self, message: String) -> Error {
+        Error::Custom {
+            path: self.path.clone(),
+            message,
+        }
+    }
+
+    /// A scalar that reads back as `text`
+    fn scalar(&self, text: &str) -> Result<Value, Error> {
+        if text.contains(['=', '\n', '\r']) {
+            return Err(self.error(format!(
+                "cannot write `{}` as a CCL value: it contains `=` or a line \
+                 break",
+                text.escape_debug()
+            )));
+        }
+        if text.trim() != text {
+            return Err(self.error(format!(
+                "cannot write `{}` as a CCL value: it starts or ends with \
+                 whitespace",
+                text.escape_debug()
+            )));
+        }
+        Ok(Value::Scalar(text.to_string()))
+    }
+
+    fn child(&self, key: &str) -> Serializer {
+        let mut path = self.path.clone();
+        path.push(key.to_string());
+        Serializer::new(path)
+    }
+}
+
+macro_rules! serialize_display {
+    ($($method:ident: $ty:ty),* $(,)?) => {
+        $(
+            fn $method(self, v: $ty) -> Result<Value, Error> {
+                self.scalar(&v.to_string())
+            }
+        )*
+    };
+}
+
+impl ser::Serializer for Serializer {
+    type Ok = Value;
+    type Error = Error;
+    type SerializeSeq = Items;
+    type SerializeTuple = Items;
+    type SerializeTupleStruct = Items;
+    type SerializeTupleVariant = Variant<Items>;
+    type SerializeMap = Entries;
+    type SerializeStruct = Entries;
+    type SerializeStructVariant = Variant<Entries>;
+
+    serialize_display! {
+        serialize_bool: bool,
+        serialize_i8: i8,
+        serialize_i16: i16,
+        serialize_i32: i32,
+        serialize_i64: i64,
+        serialize_i128: i128,
+        serialize_u8: u8,
+        serialize_u16: u16,
+        serialize_u32: u32,
+        serialize_u64: u64,
+        serialize_u128: u128,
+        serialize_f32: f32,
+        serialize_f64: f64,
+        serialize_char: char,
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Value, Error> {
+        self.scalar(v)
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Value, Error> {
+        match std::str::from_utf8(v) {
+            Ok(text) => self.scalar(text),
+            Err(_) => Err(self.error("cannot write non-UTF-8 bytes".into())),
+        }
+    }
+
+    fn serialize_none(self) -> Result<Value, Error> {
+        Ok(Value::None)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(
+        self,
+        value: &T,
+    ) -> Result<Value, Error> {
+        let path = self.path.clone();
+        match value.serialize(self)? {
+            // Written as a bare `key =`, which reads back as `None`
+            Value::Scalar(scalar) if scalar.is_empty() => {
+                Err(Serializer::new(path)
+                    .error("cannot write `Some` of an empty value".into()))
+            }
+            Value::Entries(entries) if entries.is_empty() => {
+                Err(Serializer::new(path)
+                    .error("cannot write `Some` of an empty value".into()))
+            }
+            value => Ok(value),
+        }
+    }
+
+    fn serialize_unit(self) -> Result<Value, Error> {
+        Ok(Value::Entries(Vec::new()))
+    }
+
+    fn serialize_unit_struct(
+        self,
+        _name: &'static str,
+    ) -> Result<Value, Error> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Value, Error> {
+        self.scalar(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<Value, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Value, Error> {
+        let content = value.serialize(self.child(variant))?;
+        Ok(variant_entry(variant, content))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Items, Error> {
+        Ok(Items {
+            items: Vec::with_capacity(len.unwrap_or(0)),
+            path: self.path,
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Items, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Items, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Variant<Items>, Error> {
+        Ok(Variant {
+            variant,
+            content: self.child(variant).serialize_seq(Some(len))?,
+        })
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Entries, Error> {
+        Ok(Entries {
+            entries: Vec::with_capacity(len.unwrap_or(0)),
+            key: None,
+            path: self.path,
+        })
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<Entries, Error> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Variant<Entries>, Error> {
+        Ok(Variant {
+            variant,
+            content: self.child(variant).serialize_map(Some(len))?,
+        })
+    }
+}
+
+/// The `= item` entries of a sequence
+pub struct Items {
+    items: Vec<(String, Value)>,
+    path: Vec<String>,
+}
+
+impl SerializeSeq for Items {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(
+        &mut self,
+        value: &T,
+    ) -> Result<(), Error> {
+        // Items have no key of their own, so errors are reported at the
+        // sequence
+        let item = value
+            .serialize(Serializer::new(self.path.clone()))
+            .map_err(|err| err.at(&self.path))?;
+        // An item reads back as a single key, and whatever is under it
+        match item {
+            Value::Entries(entries) if entries.len() != 1 => {
+                Err(Serializer::new(self.path.clone()).error(format!(
+                    "cannot write a sequence item with {} entries, only \
+                     single values",
+                    entries.len()
+                )))
+            }
+            // Written as a bare `=`, which reads back as no item at all
+            Value::None => Err(Serializer::new(self.path.clone())
+                .error("cannot write an absent sequence item".into())),
+            Value::Scalar(scalar) if scalar.is_empty() => {
+                Err(Serializer::new(self.path.clone())
+                    .error("cannot write an empty sequence item".into()))
+            }
+            item => {
+                self.items.push((String::new(), item));
+                Ok(())
+            }
+        }
+    }
+
+    fn end(self) -> Result<Value, Error> {
+        Ok(Value::Entries(self.items))
+    }
+}
+
+impl SerializeTuple for Items {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(
+        &mut self,
+        value: &T,
+    ) -> Result<(), Error> {
+        SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Value, Error> {
+        SerializeSeq::end(self)
+    }
+}
+
+impl SerializeTupleStruct for Items {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        value: &T,
+    ) -> Result<(), Error> {
+        SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Value, Error> {
+        SerializeSeq::end(self)
+    }
+}
+
+/// The entries of a map or struct
+pub struct Entries {
+    entries: Vec<(String, Value)>,
+    /// Key of the entry whose value is serialized next
+    key: Option<String>,
+    path: Vec<String>,
+}
+
+impl Entries {
+    fn entry<T: Serialize + ?Sized>(
+        &mut self,
+        key: String,
+        value: &T,
+    ) -> Result<Value, Error> {
+        let mut path = self.path.clone();
+        path.push(key);
+        value
+            .serialize(Serializer::new(path.clone()))
+            .map_err(|err| err.at(&path))
+    }
+}
+
+impl SerializeMap for Entries {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(
+        &mut self,
+        key: &T,
+    ) -> Result<(), Error> {
+        match key.serialize(Serializer::new(self.path.clone()))? {
+            Value::Scalar(key) => {
+                self.key = Some(key);
+                Ok(())
+            }
+            _ => Err(Error::Custom {
+                path: self.path.clone(),
+                message: "cannot write a map key that is not a single value"
+                    .into(),
+            }),
+        }
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(
+        &mut self,
+        value: &T,
+    ) -> Result<(), Error> {
+        let key = self.key.take().expect("Never: value before key");
+        let value = self.entry(key.clone(), value)?;
+        self.entries.push((key, value));
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value, Error> {
+        Ok(Value::Entries(self.entries))
+    }
+}
+
+impl SerializeStruct for Entries {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        match self.entry(key.to_string(), value)? {
+            // A missing field reads back as `None`
+            Value::None => {}
+            value => self.entries.push((key.to_string(), value)),
+        }
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value, Error> {
+        SerializeMap::end(self)
+    }
+}
+
+/// The content of a tuple or struct variant, under the variant name
+pub struct Variant<T> {
+    variant: &'static str,
+    content: T,
+}
+
+fn variant_entry(variant: &str, content: Value) -> Value {
+    Value::Entries(vec![(variant.to_string(), content)])
+}
+
+impl SerializeTupleVariant for Variant<Items> {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        value: &T,
+    ) -> Result<(), Error> {
+        SerializeSeq::serialize_element(&mut self.content, value)
+    }
+
+    fn end(self) -> Result<Value, Error> {
+        let content = SerializeSeq::end(self.content)?;
+        Ok(variant_entry(self.variant, content))
+    }
+}
+
+impl SerializeStructVariant for Variant<Entries> {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        SerializeStruct::serialize_field(&mut self.content, key, value)
+    }
+
+    fn end(self) -> Result<Value, Error> {
+        let content = SerializeStruct::end(self.content)?;
+        Ok(variant_entry(self.variant, content))
+    }
+}
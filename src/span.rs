@@ -0,0 +1,140 @@
+/// Byte range `start..end` into the source text
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Self { start, end }
+    }
+
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+
+    /// Move the span forward by `offset` bytes
+    pub fn shifted(self, offset: usize) -> Self {
+        Self::new(self.start + offset, self.end + offset)
+    }
+}
+
+/// 1-based line and column (in chars) of a byte offset
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LineCol {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl LineCol {
+    /// Offsets past the end of `src` are clamped to the end.
+    pub fn of_offset(src: &str, offset: usize) -> Self {
+        let mut offset = offset.min(src.len());
+        while !src.is_char_boundary(offset) {
+            offset -= 1;
+        }
+        let before = &src[..offset];
+        let line = before.matches('\n').count() + 1;
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        let column = before[line_start..].chars().count() + 1;
+        Self { line, column }
+    }
+}
+
+/// 1-based, inclusive range of lines
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct LineRange {
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Start offsets of every line in a source text, for mapping byte offsets
+/// to lines without rescanning the text
+#[derive(Clone, Debug)]
+pub struct LineIndex {
+    line_starts: Vec<usize>,
+}
+
+impl LineIndex {
+    pub fn new(src: &str) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(src.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        Self { line_starts }
+    }
+
+    /// 1-based line containing `offset`
+    pub fn line(&self, offset: usize) -> usize {
+        self.line_starts.partition_point(|&start| start <= offset)
+    }
+
+    /// Lines covered by `span`; an empty span covers the line it is on
+    pub fn lines(&self, span: Span) -> LineRange {
+        let start = self.line(span.start);
+        let end = self.line(span.end.saturating_sub(1).max(span.start));
+        LineRange { start, end }
+    }
+}
+
+/// Iterate over lines like `str::lines`, pairing each line with the byte
+/// offset at which it starts
+pub fn lines_with_offsets(data: &str) -> impl Iterator<Item = (usize, &str)> {
+    data.split_inclusive('\n').scan(0, |offset, raw| {
+        let start = *offset;
+        *offset += raw.len();
+        let line = raw.strip_suffix('\n').unwrap_or(raw);
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        Some((start, line))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_line_col() {
+        let src = "a = b\nc = d\n  é = f";
+        assert_eq!(LineCol::of_offset(src, 0), LineCol { line: 1, column: 1 });
+        assert_eq!(LineCol::of_offset(src, 6), LineCol { line: 2, column: 1 });
+        assert_eq!(LineCol::of_offset(src, 16), LineCol { line: 3, column: 4 });
+        assert_eq!(
+            LineCol::of_offset(src, 100),
+            LineCol { line: 3, column: 8 }
+        );
+    }
+
+    #[test]
+    fn test_line_index() {
+        let src = "a = b\nc =\n  d = e\n";
+        let index = LineIndex::new(src);
+        assert_eq!(index.line(0), 1);
+        assert_eq!(index.line(5), 1);
+        assert_eq!(index.line(6), 2);
+        assert_eq!(index.line(src.len()), 4);
+        assert_eq!(
+            index.lines(Span::new(9, 17)),
+            LineRange { start: 2, end: 3 }
+        );
+        assert_eq!(
+            index.lines(Span::new(9, 9)),
+            LineRange { start: 2, end: 2 }
+        );
+    }
+
+    #[test]
+    fn test_lines_with_offsets() {
+        let src = "a = b\r\n\nc = d";
+        let lines = lines_with_offsets(src).collect::<Vec<_>>();
+        assert_eq!(lines, vec![(0, "a = b"), (7, ""), (8, "c = d")]);
+        assert_eq!(
+            lines.iter().map(|(_, l)| *l).collect::<Vec<_>>(),
+            src.lines().collect::<Vec<_>>()
+        );
+    }
+}
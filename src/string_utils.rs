@@ -1,3 +1,6 @@
+use crate::span::Span;
+use std::borrow::Cow;
+
 /// Indent a string by a given number of spaces for each line
 pub fn indent(s: &str, indent: usize) -> String {
     let indent_str = " ".repeat(indent);
@@ -24,6 +27,41 @@ pub fn dedent<'a>(lines: &[&'a str]) -> Vec<&'a str> {
     lines.iter().map(|line| &line[min_indent..]).collect()
 }
 
+/// Remove trailing whitespace from every line, normalising line endings to
+/// `\n`. Borrows `s` when there is nothing to remove.
+pub fn trim_lines(s: &str) -> Cow<'_, str> {
+    let is_trimmed = s.lines().all(|line| line.trim_end() == line)
+        && !s.contains('\r')
+        && !s.ends_with('\n');
+    if is_trimmed {
+        Cow::Borrowed(s)
+    } else {
+        Cow::Owned(
+            s.lines()
+                .map(|line| line.trim_end())
+                .collect::<Vec<&str>>()
+                .join("\n"),
+        )
+    }
+}
+
+/// Show the first source line touched by `span` with the spanned part
+/// underlined by carets
+pub fn underline(src: &str, span: Span) -> String {
+    let start = span.start.min(src.len());
+    let line_start = src[..start].rfind('\n').map_or(0, |i| i + 1);
+    let line_end = src[start..].find('\n').map_or(src.len(), |i| start + i);
+    let line = src[line_start..line_end].trim_end_matches('\r');
+    let end = span.end.clamp(start, line_start + line.len());
+
+    let pad = src[line_start..start]
+        .chars()
+        .map(|c| if c == '\t' { '\t' } else { ' ' })
+        .collect::<String>();
+    let carets = "^".repeat(src[start..end].chars().count().max(1));
+    format!("{}\n{}{}", line, pad, carets)
+}
+
 pub const BOX_DRAWING_CHARS: (&str, &str, &str, &str, &str, &str) =
     ("┌", "┐", "┘", "└", "─", "│");
 
@@ -43,12 +81,12 @@ pub fn add_box(s: &str) -> String {
     result.push_str(top_left);
     result.push_str(&"─".repeat(max_len));
     result.push_str(top_right);
-    result.push_str("\n");
+    result.push('\n');
     for line in lines {
         result.push_str(format!("{}{}", vertical, line).as_str());
         let pad = " ".repeat(max_len - line.len());
         result.push_str(format!("{}{}", pad, vertical).as_str());
-        result.push_str("\n");
+        result.push('\n');
     }
     result.push_str(bottom_left);
     result.push_str(&"─".repeat(max_len));
@@ -60,6 +98,15 @@ pub fn add_box(s: &str) -> String {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_underline() {
+        let src = "a = b\n  key\nc = d";
+        insta::assert_snapshot!(underline(src, Span::new(8, 11)), @r"
+          key
+          ^^^
+        ");
+    }
+
     #[test]
     fn test_add_box() {
         let s = "a\nbb\nc";
@@ -0,0 +1,468 @@
+use crate::comment::{COMMENT_KEY, CommentMode};
+use crate::diagnostic::Diagnostic;
+use crate::error::{Location, ParseError};
+use crate::key_val::{
+    KeyVal, KeyValNode, KeyValNodeSpan, KeyValSpan, KeyValSpanTree, KeyValTree,
+    insert_map,
+};
+use crate::options::{IndentChecker, ParseOptions};
+use crate::span::{LineIndex, Span, lines_with_offsets};
+use crate::string_utils::trim_lines;
+
+/// The key-value pairs read so far from one piece of text: the whole input,
+/// or the value of an entry of the enclosing frame
+#[derive(Default)]
+struct Frame<'a> {
+    /// Indentation of the first non-blank line, once seen
+    fst_indent: Option<usize>,
+    /// The largest `fst_indent` of this frame and those above it, which a
+    /// line must be indented past to continue the value of `open`
+    threshold: usize,
+    /// Checks of the indentation of this frame's text on its own, as
+    /// `KeyVal::parse_with` would check it if given just that text
+    checker: Option<IndentChecker<'a>>,
+    /// The first failed check, which makes this frame's text a leaf
+    bad_indentation: Option<(String, Span)>,
+    /// Source range of the non-blank text of the pending key
+    key_span: Option<Span>,
+    /// Number of entries, including stripped comments
+    entries: usize,
+    tree: KeyValTree,
+    /// Spans of the nodes of `tree`, if recorded
+    spans: KeyValSpanTree,
+    /// Key, key range and value range of the last entry, whose value is
+    /// the text of the next frame on the stack
+    open: Option<(String, Span, Span)>,
+    /// Number of warnings before this frame, those after it being about
+    /// its text
+    warnings: usize,
+}
+
+/// What becomes of a nested value that has an `=` but is not valid CCL,
+/// besides being kept as a leaf
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Broken {
+    /// Nothing more
+    Ignore,
+    /// The first one fails the build
+    Fail,
+    /// Each one is a warning, unless it is in the value of another
+    Warn,
+}
+
+/// Builds a `KeyValTree` in one pass over the lines of the input, keeping
+/// a stack of frames for the values being read at every level of nesting.
+/// The indentation of the input as a whole is checked beforehand.
+///
+/// Each line goes straight to the innermost frame, or to the one it
+/// dedents to, so reading it costs no more than the frames it closes.
+/// Only the indentation checks of `options`, if any, see a line once per
+/// level it is at, since each level's text is checked on its own.
+struct TreeBuilder<'a> {
+    data: &'a str,
+    options: &'a ParseOptions,
+    broken: Broken,
+    /// The nesting limit of `options`, if it applies
+    max_depth: Option<usize>,
+    /// The error for the first entry past `max_depth`, after which no more
+    /// lines are read
+    limit: Option<ParseError>,
+    /// The first broken nested value, with `Broken::Fail`
+    error: Option<ParseError>,
+    /// Every broken nested value, with `Broken::Warn`
+    warnings: Vec<Diagnostic>,
+    /// Lines of `data`, to record the spans of the nodes with
+    index: Option<LineIndex>,
+    stack: Vec<Frame<'a>>,
+    /// End of the last non-blank line read, which the values of the
+    /// entries open above it extend to
+    last_end: usize,
+}
+
+impl<'a> TreeBuilder<'a> {
+    fn new(data: &'a str, options: &'a ParseOptions) -> Self {
+        Self {
+            data,
+            options,
+            broken: Broken::Ignore,
+            max_depth: options.max_depth,
+            limit: None,
+            error: None,
+            warnings: Vec::new(),
+            index: None,
+            stack: vec![Frame::default()],
+            last_end: 0,
+        }
+    }
+
+    fn strict(data: &'a str, options: &'a ParseOptions) -> Self {
+        Self {
+            broken: Broken::Fail,
+            ..TreeBuilder::new(data, options)
+        }
+    }
+
+    fn recovering(data: &'a str, options: &'a ParseOptions) -> Self {
+        Self {
+            broken: Broken::Warn,
+            ..TreeBuilder::new(data, options)
+        }
+    }
+
+    /// Record the spans of the nodes too
+    fn spanned(self) -> Self {
+        Self {
+            index: Some(LineIndex::new(self.data)),
+            ..self
+        }
+    }
+
+    /// Ignore the nesting limit, for text that is itself nested
+    fn unlimited(self) -> Self {
+        Self {
+            max_depth: None,
+            ..self
+        }
+    }
+
+    /// The tree of `data`, or `None` if it has no entries. Unless strict,
+    /// only an unclosed key at the top level is an error: nested values
+    /// that are not key-value pairs are leaves.
+    fn build(mut self) -> Result<Option<KeyValTree>, ParseError> {
+        let root = self.read_all();
+        if let Some(err) = self.limit.take().or(self.error.take()) {
+            return Err(err);
+        }
+        if let Some(err) = self.unclosed(&root) {
+            return Err(err);
+        }
+        Ok((root.entries > 0).then_some(root.tree))
+    }
+
+    /// Read every line of `data`, or up to the entry past the nesting
+    /// limit, returning the top-level frame with the entries before a key
+    /// left unclosed, if any
+    fn read_all(&mut self) -> Frame<'a> {
+        for (offset, line) in lines_with_offsets(self.data) {
+            if self.limit.is_some() {
+                break;
+            }
+            if line.trim().is_empty() {
+                // Blank lines only matter once followed by more text
+                continue;
+            }
+            let indent = self.options.indent_width(line);
+            self.line(offset, line, indent);
+            self.last_end = offset + line.trim_end().len();
+        }
+
+        self.close(0);
+        self.stack.pop().expect("Never: empty stack")
+    }
+
+    /// The error for the key left unclosed at the end of `frame`
+    fn unclosed(&self, frame: &Frame) -> Option<ParseError> {
+        frame.key_span.map(|span| ParseError::UnclosedKey {
+            key: self.text(span),
+            location: Location::new(self.data, span),
+        })
+    }
+
+    fn text(&self, span: Span) -> String {
+        trim_lines(&self.data[span.start..span.end]).into_owned()
+    }
+
+    /// Read a non-blank `line` starting at `offset` into the innermost
+    /// frame whose open value it continues, closing those below it
+    fn line(&mut self, offset: usize, line: &'a str, indent: usize) {
+        // Thresholds grow down the stack, so the frames the line does not
+        // continue are all at the bottom
+        let mut depth = self.stack.len() - 1;
+        while depth > 0 && indent <= self.stack[depth - 1].threshold {
+            depth -= 1;
+        }
+        if self.options.checks_indentation() {
+            for frame in &mut self.stack[..depth] {
+                check_line(frame, offset, line);
+            }
+        }
+        self.read(depth, offset, line, indent);
+    }
+
+    /// Read `line` into the frame at `depth`, and the rest of it after an
+    /// `=` into a new frame for the value of the entry
+    fn read(
+        &mut self,
+        mut depth: usize,
+        mut offset: usize,
+        mut line: &'a str,
+        mut indent: usize,
+    ) {
+        loop {
+            self.close(depth);
+            let threshold = match depth {
+                0 => 0,
+                _ => self.stack[depth - 1].threshold,
+            };
+            let frame = &mut self.stack[depth];
+            check_line(frame, offset, line);
+            if frame.fst_indent.is_none() {
+                frame.fst_indent = Some(indent);
+                frame.threshold = threshold.max(indent);
+            }
+
+            let Some((curr_key, curr_value)) = line.split_once('=') else {
+                extend_key_span(&mut frame.key_span, offset, line);
+                return;
+            };
+
+            extend_key_span(&mut frame.key_span, offset, curr_key);
+            let eq = offset + curr_key.len();
+            let key_span = frame.key_span.take().unwrap_or(Span::new(eq, eq));
+            let value_start =
+                eq + 1 + curr_value.len() - curr_value.trim_start().len();
+            let value_end = value_start + curr_value.trim().len();
+            if let Some(max) = self.max_depth
+                && depth >= max
+            {
+                self.limit = Some(ParseError::LimitExceeded {
+                    limit: "nesting depth",
+                    max,
+                    location: Location::new(self.data, key_span),
+                });
+                return;
+            }
+            frame.entries += 1;
+            let key = self.text(key_span);
+            self.stack[depth].open =
+                Some((key, key_span, Span::new(value_start, value_end)));
+            let checker = self
+                .options
+                .checks_indentation()
+                .then(|| IndentChecker::new(self.options));
+            self.stack.push(Frame {
+                checker,
+                warnings: self.warnings.len(),
+                ..Frame::default()
+            });
+
+            // The rest of the line is the first line of the value, where it
+            // is not indented at all
+            if value_start == value_end {
+                return;
+            }
+            depth += 1;
+            offset = value_start;
+            line = &self.data[value_start..value_end];
+            indent = 0;
+        }
+    }
+
+    /// Finish the frames deeper than `depth`, adding each as a node to the
+    /// one above it
+    fn close(&mut self, depth: usize) {
+        while self.stack.len() > depth + 1 {
+            let child = self.stack.pop().expect("Never: empty stack");
+            let parent = self.stack.last_mut().expect("Never: empty stack");
+            let (key, key_span, mut value) =
+                parent.open.take().expect("Never: frame without an entry");
+            value.end = value.end.max(self.last_end);
+            if self.options.comments == CommentMode::Strip && key == COMMENT_KEY
+            {
+                continue;
+            }
+            let failed =
+                child.bad_indentation.is_some() || child.key_span.is_some();
+            let (node, children) = if !failed && child.entries > 0 {
+                (KeyValNode::Tree(child.tree), child.spans)
+            } else {
+                // A value with no entries is text, not a broken section
+                if self.broken != Broken::Ignore && child.entries > 0 {
+                    self.report(&key, child);
+                }
+                let text = trim_lines(&self.data[value.start..value.end]);
+                (KeyValNode::Leaf(text.into_owned()), KeyValSpanTree::new())
+            };
+            let parent = self.stack.last_mut().expect("Never: empty stack");
+            insert_map(&mut parent.tree, &key, node);
+            if let Some(index) = &self.index {
+                let span = KeyValNodeSpan {
+                    span: KeyValSpan::new(index, key_span, value),
+                    children,
+                };
+                insert_map(&mut parent.spans, &key, span);
+            }
+        }
+    }
+
+    /// Report `child`, the broken value of the entry `key` of the innermost
+    /// frame, as `broken` says
+    fn report(&mut self, key: &str, child: Frame) {
+        if self.error.is_some() {
+            return;
+        }
+        let failure = match (child.bad_indentation, child.key_span) {
+            (Some((message, span)), _) => ParseError::BadIndentation {
+                message,
+                location: Location::new(self.data, span),
+            },
+            (None, Some(span)) => ParseError::UnclosedKey {
+                key: self.text(span),
+                location: Location::new(self.data, span),
+            },
+            (None, None) => unreachable!("Never: not failed"),
+        };
+        let path = self
+            .stack
+            .iter()
+            .filter_map(|frame| frame.open.as_ref())
+            .map(|(key, _, _)| key.clone())
+            .chain([key.to_string()])
+            .collect::<Vec<_>>();
+        match self.broken {
+            Broken::Ignore => {}
+            Broken::Fail => {
+                self.error = Some(ParseError::InValue {
+                    path,
+                    error: Box::new(failure),
+                });
+            }
+            Broken::Warn => {
+                // The value is a leaf, so what is in it does not count
+                self.warnings.truncate(child.warnings);
+                self.warnings.push(Diagnostic::warning(path, failure));
+            }
+        }
+    }
+}
+
+/// Check the indentation of `line` as part of the text of `frame`
+fn check_line(frame: &mut Frame, offset: usize, line: &str) {
+    if let Some(checker) = &mut frame.checker
+        && let Some(failure) = checker.check(offset, line)
+    {
+        frame.bad_indentation = Some(failure);
+        frame.checker = None;
+    }
+}
+
+fn extend_key_span(key_span: &mut Option<Span>, offset: usize, text: &str) {
+    if text.trim().is_empty() {
+        return;
+    }
+    let start = offset + text.len() - text.trim_start().len();
+    let end = offset + text.trim_end().len();
+    let span = key_span.get_or_insert(Span::new(start, end));
+    span.end = end;
+}
+
+/// The node for `value`: the tree of the key-value pairs in it, or the
+/// value itself if it has none or is not valid CCL
+pub(crate) fn value_node(value: &str, options: &ParseOptions) -> KeyValNode {
+    let tree = options
+        .check_indentation(value)
+        .and_then(|()| TreeBuilder::new(value, options).unlimited().build());
+    match tree {
+        Ok(Some(tree)) => KeyValNode::Tree(tree),
+        Err(_) | Ok(None) => KeyValNode::Leaf(value.to_string()),
+    }
+}
+
+/// Like `value_node`, but a value with an `=` that is not valid CCL, at
+/// any depth, is an error. Error locations are in `value`.
+pub(crate) fn try_value_node(
+    value: &str,
+    options: &ParseOptions,
+) -> Result<KeyValNode, ParseError> {
+    let tree = options
+        .check_indentation(value)
+        .and_then(|()| TreeBuilder::strict(value, options).unlimited().build());
+    match tree {
+        Ok(Some(tree)) => Ok(KeyValNode::Tree(tree)),
+        Err(err) if value.contains('=') => Err(err),
+        Err(_) | Ok(None) => Ok(KeyValNode::Leaf(value.to_string())),
+    }
+}
+
+impl KeyVal {
+    /// Parse `data` straight into a tree, in a single pass:
+    /// parse_tree data ≡ parse_flat_to_tree (parse data)
+    pub fn parse_tree(data: &str) -> Result<KeyValTree, ParseError> {
+        KeyVal::parse_tree_with(data, &ParseOptions::default())
+    }
+
+    /// Like `parse_tree`, but reads indentation and comments according to
+    /// `options`:
+    /// parse_tree_with data options ≡
+    /// parse_flat_to_tree_with (parse_with data options) options
+    pub fn parse_tree_with(
+        data: &str,
+        options: &ParseOptions,
+    ) -> Result<KeyValTree, ParseError> {
+        options.check_indentation(data)?;
+        Ok(TreeBuilder::new(data, options).build()?.unwrap_or_default())
+    }
+
+    /// Like `parse_tree`, but also builds a tree of spans with the same
+    /// shape, pointing into `data`
+    pub fn parse_tree_spanned(
+        data: &str,
+    ) -> Result<(KeyValTree, KeyValSpanTree), ParseError> {
+        KeyVal::parse_tree_spanned_with(data, &ParseOptions::default())
+    }
+
+    /// Like `parse_tree_spanned`, but reads indentation and comments
+    /// according to `options`
+    pub fn parse_tree_spanned_with(
+        data: &str,
+        options: &ParseOptions,
+    ) -> Result<(KeyValTree, KeyValSpanTree), ParseError> {
+        options.check_indentation(data)?;
+        let mut builder = TreeBuilder::new(data, options).spanned();
+        let root = builder.read_all();
+        match builder.limit.take().or(builder.unclosed(&root)) {
+            Some(err) => Err(err),
+            None => Ok((root.tree, root.spans)),
+        }
+    }
+
+    /// Like `parse_tree`, but a nested value with an `=` that is not
+    /// valid CCL is an error, `ParseError::InValue` with the keys leading
+    /// to it, rather than a leaf
+    pub fn try_parse_tree(data: &str) -> Result<KeyValTree, ParseError> {
+        KeyVal::try_parse_tree_with(data, &ParseOptions::default())
+    }
+
+    /// Like `try_parse_tree`, but reads indentation and comments according
+    /// to `options`
+    pub fn try_parse_tree_with(
+        data: &str,
+        options: &ParseOptions,
+    ) -> Result<KeyValTree, ParseError> {
+        options.check_indentation(data)?;
+        Ok(TreeBuilder::strict(data, options)
+            .build()?
+            .unwrap_or_default())
+    }
+
+    /// Like `parse_flat_to_tree_with` on the result of `parse_recovering`,
+    /// but instead of silently keeping a nested value that looks like
+    /// entries (it has an `=`) yet fails to parse as a leaf, also reports
+    /// it as a warning
+    pub fn parse_tree_recovering(
+        data: &str,
+        options: &ParseOptions,
+    ) -> (KeyValTree, Vec<Diagnostic>) {
+        let mut diagnostics = options
+            .indentation_errors(data)
+            .into_iter()
+            .map(Diagnostic::error)
+            .collect::<Vec<Diagnostic>>();
+        let mut builder = TreeBuilder::recovering(data, options);
+        let root = builder.read_all();
+        diagnostics.extend(builder.limit.take().map(Diagnostic::error));
+        diagnostics.extend(builder.unclosed(&root).map(Diagnostic::error));
+        diagnostics.append(&mut builder.warnings);
+        (root.tree, diagnostics)
+    }
+}
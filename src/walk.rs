@@ -0,0 +1,110 @@
+use crate::parser::CCL;
+use std::collections::btree_map;
+
+/// Hooks called by `CCL::walk` for every entry, with the path of keys
+/// leading to it and the map under it
+pub trait Visitor {
+    /// Called before the entries under this one
+    fn enter(&mut self, _path: &[&str], _ccl: &CCL) {}
+
+    /// Called after the entries under this one
+    fn leave(&mut self, _path: &[&str], _ccl: &CCL) {}
+}
+
+/// Every entry of a `CCL`, depth first and in key order, with the path of
+/// keys leading to it. Keeps its own stack, so the depth of the `CCL` is
+/// not limited by the call stack.
+pub struct Paths<'a> {
+    path: Vec<&'a str>,
+    stack: Vec<btree_map::Iter<'a, String, CCL>>,
+}
+
+impl<'a> Iterator for Paths<'a> {
+    type Item = (Vec<&'a str>, &'a CCL);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let entries = self.stack.last_mut()?;
+            let Some((key, ccl)) = entries.next() else {
+                self.stack.pop();
+                self.path.pop();
+                continue;
+            };
+            let mut path = self.path.clone();
+            path.push(key);
+            let CCL(map) = ccl;
+            self.path.push(key);
+            self.stack.push(map.iter());
+            return Some((path, ccl));
+        }
+    }
+}
+
+/// Depth-first traversal. None of these recurse, so they work on a `CCL`
+/// of any depth.
+impl CCL {
+    /// Every entry, outermost first, with the path of keys leading to it
+    pub fn iter_paths(&self) -> Paths<'_> {
+        let CCL(map) = self;
+        Paths {
+            path: Vec::new(),
+            stack: vec![map.iter()],
+        }
+    }
+
+    /// The paths of the entries with nothing under them
+    pub fn leaves(&self) -> impl Iterator<Item = Vec<&str>> {
+        self.iter_paths()
+            .filter_map(|(path, CCL(map))| map.is_empty().then_some(path))
+    }
+
+    /// Call `visitor` on every entry, depth first
+    pub fn walk<V: Visitor + ?Sized>(&self, visitor: &mut V) {
+        let CCL(map) = self;
+        let mut path = Vec::new();
+        let mut stack = vec![(None, map.iter())];
+        while let Some((entry, entries)) = stack.last_mut() {
+            match entries.next() {
+                Some((key, ccl)) => {
+                    path.push(key.as_str());
+                    visitor.enter(&path, ccl);
+                    let CCL(map) = ccl;
+                    stack.push((Some(ccl), map.iter()));
+                }
+                None => {
+                    if let Some(ccl) = entry {
+                        visitor.leave(&path, ccl);
+                        path.pop();
+                    }
+                    stack.pop();
+                }
+            }
+        }
+    }
+
+    /// Call `f` on every entry, outermost first, with the path leading to
+    /// it and the map under it to change. The entries under one are those
+    /// it has once `f` returns.
+    pub fn walk_mut<F>(&mut self, mut f: F)
+    where
+        F: FnMut(&[String], &mut CCL),
+    {
+        let CCL(map) = self;
+        let mut path = Vec::new();
+        // Entries still to visit, with the depth they are at
+        let mut stack = map
+            .iter_mut()
+            .rev()
+            .map(|(key, ccl)| (0, key, ccl))
+            .collect::<Vec<_>>();
+        while let Some((depth, key, ccl)) = stack.pop() {
+            path.truncate(depth);
+            path.push(key.clone());
+            f(&path, ccl);
+            let CCL(map) = ccl;
+            stack.extend(
+                map.iter_mut().rev().map(|(key, ccl)| (depth + 1, key, ccl)),
+            );
+        }
+    }
+}
@@ -0,0 +1,112 @@
+use ccl_rs::error::AccessError;
+use ccl_rs::parser::CCL;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+name = api
+database =
+    port = 5432
+    hosts =
+        = db1
+        = db2
+    replicas = a
+    replicas = b
+    pool =
+        size = 8
+debug =
+verbose = false
+timeout = soon
+"#;
+
+    fn config() -> CCL {
+        CCL::parse_str(CONFIG).unwrap()
+    }
+
+    #[test]
+    fn test_get() {
+        let ccl = config();
+        assert_eq!(ccl.get(&[]).unwrap(), &ccl);
+        assert_eq!(
+            ccl.get(&["database", "pool"]).unwrap(),
+            &CCL::key_val("size", "8")
+        );
+        assert!(ccl.contains(&["database", "port", "5432"]));
+        assert!(!ccl.contains(&["database", "user"]));
+    }
+
+    #[test]
+    fn test_get_values() {
+        let ccl = config();
+        assert_eq!(ccl.get_str(&["name"]).unwrap(), "api");
+        assert_eq!(ccl.get_str(&["debug"]).unwrap(), "");
+        assert_eq!(ccl.get_one(&["database", "pool"]).unwrap(), "size");
+        assert_eq!(ccl.get_all(&["database", "replicas"]).unwrap(), ["a", "b"]);
+        assert_eq!(
+            ccl.get_list(&["database", "hosts"]).unwrap(),
+            ["db1", "db2"]
+        );
+        assert_eq!(
+            ccl.get_list(&["database", "replicas"]).unwrap(),
+            ["a", "b"]
+        );
+        assert_eq!(ccl.get_parsed::<u16>(&["database", "port"]), Ok(5432));
+        assert_eq!(ccl.get_bool(&["debug"]), Ok(true));
+        assert_eq!(ccl.get_bool(&["verbose"]), Ok(false));
+    }
+
+    #[test]
+    fn test_missing() {
+        let err = config().get_str(&["database", "user"]).unwrap_err();
+        assert_eq!(
+            err,
+            AccessError::Missing {
+                path: vec!["database".into(), "user".into()]
+            }
+        );
+        insta::assert_snapshot!(
+            err,
+            @"Key 'user' not found (in the value of database)"
+        );
+
+        // The path stops at the first key that is missing
+        let err = config().get(&["cache", "size"]).unwrap_err();
+        insta::assert_snapshot!(err, @"Key 'cache' not found");
+    }
+
+    #[test]
+    fn test_multiple() {
+        let err = config().get_one(&["database", "replicas"]).unwrap_err();
+        assert!(matches!(err, AccessError::Multiple { .. }));
+        insta::assert_snapshot!(
+            err,
+            @"Expected one value, found 2: a, b (in the value of database=replicas)"
+        );
+    }
+
+    #[test]
+    fn test_nested() {
+        let err = config().get_str(&["database", "pool"]).unwrap_err();
+        insta::assert_snapshot!(
+            err,
+            @"Expected a plain value, found entries under 'size' (in the value of database=pool)"
+        );
+    }
+
+    #[test]
+    fn test_invalid() {
+        let err = config().get_parsed::<u32>(&["timeout"]).unwrap_err();
+        assert_eq!(err.path(), ["timeout"]);
+        insta::assert_snapshot!(
+            err,
+            @"Invalid value 'soon': invalid digit found in string (in the value of timeout)"
+        );
+        let err = config().get_bool(&["name"]).unwrap_err();
+        insta::assert_snapshot!(
+            err,
+            @"Invalid value 'api': provided string was not `true` or `false` (in the value of name)"
+        );
+    }
+}
@@ -0,0 +1,60 @@
+use ccl_rs::key_val::{KeyVal, KeyValRef};
+use proptest::prelude::*;
+use std::borrow::Cow;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_borrowed() {
+        let config = "a = b\nc =\n  d = e\n  f = g\n";
+        let key_vals = KeyVal::parse_borrowed(config).unwrap();
+        assert!(matches!(key_vals[0].key, Cow::Borrowed("a")));
+        assert!(matches!(key_vals[0].value, Cow::Borrowed("b")));
+        assert!(matches!(
+            key_vals[1].value,
+            Cow::Borrowed("\n  d = e\n  f = g")
+        ));
+        let owned = key_vals
+            .into_iter()
+            .map(KeyValRef::into_owned)
+            .collect::<Vec<KeyVal>>();
+        assert_eq!(owned, KeyVal::parse(config).unwrap());
+    }
+
+    #[test]
+    fn test_parse_borrowed_copies_trimmed_lines() {
+        let config = "key =\n  d = e  \n  f = g\r\n  h = i";
+        let key_vals = KeyVal::parse_borrowed(config).unwrap();
+        assert!(matches!(key_vals[0].key, Cow::Borrowed("key")));
+        assert!(matches!(key_vals[0].value, Cow::Owned(_)));
+        assert_eq!(key_vals[0].value, "\n  d = e\n  f = g\n  h = i");
+    }
+
+    #[test]
+    fn test_parse_borrowed_error() {
+        let err = KeyVal::parse_borrowed("a = b\nkey").unwrap_err();
+        insta::assert_snapshot!(err, @"No value found for key: key");
+    }
+}
+
+proptest! {
+    #[test]
+    fn test_borrowed_matches_owned(
+        text in prop::collection::vec((0..4usize, "[a-c= ]{0,6}"), 0..20)
+            .prop_map(|lines| {
+                lines
+                    .into_iter()
+                    .map(|(indent, line)| {
+                        format!("{}{}", " ".repeat(indent), line)
+                    })
+                    .collect::<Vec<String>>()
+                    .join("\n")
+            })
+    ) {
+        let borrowed = KeyVal::parse_borrowed(&text)
+            .map(|kvs| kvs.into_iter().map(KeyVal::from).collect::<Vec<_>>());
+        prop_assert_eq!(borrowed, KeyVal::parse(&text));
+    }
+}
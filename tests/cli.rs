@@ -1,10 +1,28 @@
 use assert_cmd::Command;
 use predicates::prelude::*;
+use std::path::PathBuf;
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    /// A path in the temporary directory, unique to the test and the test
+    /// process, removed on drop even when the test fails
+    struct TempFile(PathBuf);
+
+    impl TempFile {
+        fn new(test: &str) -> Self {
+            let name = format!("cclq-{}-{}.ccl", std::process::id(), test);
+            Self(std::env::temp_dir().join(name))
+        }
+    }
+
+    impl Drop for TempFile {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.0);
+        }
+    }
+
     #[test]
     fn test_help() {
         let mut cmd = Command::cargo_bin("ccl-rs").unwrap();
@@ -70,6 +88,41 @@ mod tests {
         ");
     }
 
+    #[test]
+    fn test_merge_strategy() {
+        let mut cmd = Command::cargo_bin("ccl-rs").unwrap();
+
+        let output = cmd
+            .arg("--file")
+            .arg("tests/fixtures/sample1.ccl")
+            .arg("tests/fixtures/sample2.ccl")
+            .arg("--merge")
+            .arg("last-wins")
+            .arg("--merge-at")
+            .arg("numbers=baz=union")
+            .arg("--query")
+            .arg("numbers")
+            .output()
+            .unwrap();
+
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        insta::assert_snapshot!(stdout, @r"
+        bar =
+          19023135 =
+        baz =
+          123 =
+          12905843 =
+        foo =
+          1 =
+        ");
+
+        let mut cmd = Command::cargo_bin("ccl-rs").unwrap();
+        let output = cmd.arg("--merge").arg("newest").output().unwrap();
+        assert!(!output.status.success());
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(stderr.contains("unknown merge strategy `newest`"));
+    }
+
     #[test]
     fn test_query_single() {
         let mut cmd = Command::cargo_bin("ccl-rs").unwrap();
@@ -144,4 +197,423 @@ mod tests {
         baz =
         ");
     }
+
+    #[test]
+    fn test_query_wildcards() {
+        let mut cmd = Command::cargo_bin("ccl-rs").unwrap();
+
+        let output = cmd
+            .arg("--file")
+            .arg("tests/fixtures/sample1.ccl")
+            .arg("tests/fixtures/sample2.ccl")
+            .arg("--query")
+            .arg("**=ba?")
+            .output()
+            .unwrap();
+
+        let stdout = String::from_utf8(output.stdout).unwrap();
+
+        // Every match, under its path
+        insta::assert_snapshot!(stdout, @r"
+        numbers =
+          bar =
+            19023135 =
+          baz =
+            123 =
+            12905843 =
+        this =
+          bar =
+            baz =
+        ");
+    }
+
+    #[test]
+    fn test_query_not_found() {
+        let mut cmd = Command::cargo_bin("ccl-rs").unwrap();
+
+        let output = cmd
+            .arg("--file")
+            .arg("tests/fixtures/sample1.ccl")
+            .arg("--query")
+            .arg("numbers=qux")
+            .output()
+            .unwrap();
+
+        assert!(!output.status.success());
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        insta::assert_snapshot!(
+            stderr,
+            @"Query failed: Key 'qux' not found (in the value of numbers)"
+        );
+
+        let mut cmd = Command::cargo_bin("ccl-rs").unwrap();
+        let output = cmd
+            .arg("--file")
+            .arg("tests/fixtures/sample1.ccl")
+            .arg("--query")
+            .arg("*=qux")
+            .output()
+            .unwrap();
+
+        assert!(!output.status.success());
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        insta::assert_snapshot!(
+            stderr,
+            @"Query failed: No match for '*=qux'"
+        );
+    }
+
+    #[test]
+    fn test_eval() {
+        let mut cmd = Command::cargo_bin("ccl-rs").unwrap();
+
+        let output = cmd
+            .arg("eval")
+            .arg(".numbers | map(select(length == 2)) | keys")
+            .arg("--file")
+            .arg("tests/fixtures/sample1.ccl")
+            .arg("tests/fixtures/sample2.ccl")
+            .output()
+            .unwrap();
+
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        insta::assert_snapshot!(stdout, @r"
+        baz
+        foo
+        ");
+    }
+
+    #[test]
+    fn test_eval_errors() {
+        let mut cmd = Command::cargo_bin("ccl-rs").unwrap();
+        let output = cmd.arg("eval").arg(".a | sort").output().unwrap();
+        assert!(!output.status.success());
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        insta::assert_snapshot!(
+            stderr,
+            @"Invalid filter at byte 5: unknown filter `sort`"
+        );
+
+        let mut cmd = Command::cargo_bin("ccl-rs").unwrap();
+        let output = cmd
+            .arg("eval")
+            .arg(".somekey | raw | length | keys")
+            .write_stdin("somekey = someval\n")
+            .output()
+            .unwrap();
+        assert!(!output.status.success());
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        insta::assert_snapshot!(
+            stderr,
+            @"Filter failed: keys: cannot apply to a number (7)"
+        );
+    }
+
+    #[test]
+    fn test_diff() {
+        let mut cmd = Command::cargo_bin("ccl-rs").unwrap();
+        let output = cmd
+            .arg("diff")
+            .arg("tests/fixtures/sample1.ccl")
+            .arg("tests/fixtures/sample2.ccl")
+            .output()
+            .unwrap();
+        assert_eq!(output.status.code(), Some(1));
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        insta::assert_snapshot!(stdout, @r"
+        - numbers=bar=19023135
+        ~ numbers=baz: 12905843 -> 123
+        ~ numbers=foo: 12341234 -> 1
+        + somekey=someval
+        + this=bar=baz
+        + this=foo
+        + this=that
+        ");
+
+        let mut cmd = Command::cargo_bin("ccl-rs").unwrap();
+        cmd.arg("diff")
+            .arg("--porcelain")
+            .arg("tests/fixtures/sample1.ccl")
+            .arg("tests/fixtures/sample1.ccl")
+            .assert()
+            .success()
+            .stdout("");
+    }
+
+    #[test]
+    fn test_merge3() {
+        let mut cmd = Command::cargo_bin("ccl-rs").unwrap();
+        let output = cmd
+            .arg("merge3")
+            .arg("tests/fixtures/merge3/base.ccl")
+            .arg("tests/fixtures/merge3/ours.ccl")
+            .arg("tests/fixtures/merge3/theirs.ccl")
+            .output()
+            .unwrap();
+        assert_eq!(output.status.code(), Some(1));
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        insta::assert_snapshot!(stderr, @r"
+        Conflict: ours removed 'database', theirs changed it
+        Conflict: both sides changed the values of 'port': 443 and 9090
+        ");
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        insta::assert_snapshot!(stdout, @r"
+        /= Conflict: ours removed 'database', theirs changed it
+        /= Conflict: both sides changed the values of 'port': 443 and 9090
+        port = 443
+        port = 9090
+        hosts =
+          = db1
+          = db2
+          = db3
+        database =
+          pool =
+            size = 20
+        ");
+
+        // As a git merge driver, writing over our version
+        let merged = TempFile::new("test_merge3");
+        let mut cmd = Command::cargo_bin("ccl-rs").unwrap();
+        cmd.arg("merge3")
+            .arg("tests/fixtures/merge3/base.ccl")
+            .arg("tests/fixtures/merge3/clean.ccl")
+            .arg("tests/fixtures/merge3/theirs.ccl")
+            .arg("--output")
+            .arg(&merged.0)
+            .assert()
+            .success()
+            .stdout("");
+        let text = std::fs::read_to_string(&merged.0).unwrap();
+        insta::assert_snapshot!(text, @r"
+        port = 9090
+        /= One per line
+        hosts =
+          = db1
+          = db2
+          = db3
+        database =
+          pool =
+            size = 20
+        ");
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut cmd = Command::cargo_bin("ccl-rs").unwrap();
+        cmd.arg("validate")
+            .arg("--schema")
+            .arg("tests/fixtures/schema/schema.ccl")
+            .arg("tests/fixtures/schema/valid.ccl")
+            .assert()
+            .success()
+            .stdout("");
+
+        let mut cmd = Command::cargo_bin("ccl-rs").unwrap();
+        let output = cmd
+            .arg("validate")
+            .arg("--schema")
+            .arg("tests/fixtures/schema/schema.ccl")
+            .arg("tests/fixtures/schema/valid.ccl")
+            .arg("tests/fixtures/schema/invalid.ccl")
+            .output()
+            .unwrap();
+        assert_eq!(output.status.code(), Some(1));
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        insta::assert_snapshot!(stdout, @r"
+        tests/fixtures/schema/invalid.ccl:1:8: Invalid value 'Billing' of 'name': expected a match for /[a-z][a-z0-9-]*/
+        tests/fixtures/schema/invalid.ccl:2:8: Invalid value 'http' of 'port': expected an int
+        tests/fixtures/schema/invalid.ccl:3:8: Invalid value 'append' of 'mode': expected one of: ro, rw
+        tests/fixtures/schema/invalid.ccl:4:1: Expected at least 1 value of 'hosts', found 0
+        tests/fixtures/schema/invalid.ccl:5:1: Missing required key 'user' (in the value of database)
+        tests/fixtures/schema/invalid.ccl:6:3: Expected 1 value of 'pool', found 2: 10, 20 (in the value of database)
+        tests/fixtures/schema/invalid.ccl:8:3: Unknown key 'usr' (in the value of database)
+        tests/fixtures/schema/invalid.ccl:9:1: Unknown key 'debgu'
+        ");
+
+        let mut cmd = Command::cargo_bin("ccl-rs").unwrap();
+        let output = cmd
+            .arg("validate")
+            .arg("--schema")
+            .arg("/dev/stdin")
+            .arg("tests/fixtures/schema/valid.ccl")
+            .write_stdin("fields =\n  port =\n    type = float\n")
+            .output()
+            .unwrap();
+        assert_eq!(output.status.code(), Some(1));
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        insta::assert_snapshot!(stderr, @"/dev/stdin: Invalid schema: unknown type `float`, expected one of: string, int, bool, enum, regex, section (in the value of fields=port)");
+    }
+
+    #[test]
+    fn test_validate_includes() {
+        let mut cmd = Command::cargo_bin("ccl-rs").unwrap();
+        let output = cmd
+            .arg("--includes")
+            .arg("validate")
+            .arg("--schema")
+            .arg("tests/fixtures/schema/schema.ccl")
+            .arg("tests/fixtures/schema/includes.ccl")
+            .output()
+            .unwrap();
+        assert_eq!(output.status.code(), Some(1));
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        insta::assert_snapshot!(stdout, @"tests/fixtures/schema/includes.ccl: Unknown key 'debgu'");
+    }
+
+    #[test]
+    fn test_includes() {
+        let mut cmd = Command::cargo_bin("ccl-rs").unwrap();
+        let output = cmd
+            .arg("--includes")
+            .arg("--file")
+            .arg("tests/fixtures/include/deep/one.ccl")
+            .output()
+            .unwrap();
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        insta::assert_snapshot!(stdout, @r"
+        one =
+        three =
+        two =
+        ");
+
+        let mut cmd = Command::cargo_bin("ccl-rs").unwrap();
+        let output = cmd
+            .arg("--includes")
+            .arg("--file")
+            .arg("tests/fixtures/include/cycle/a.ccl")
+            .output()
+            .unwrap();
+        assert!(!output.status.success());
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        insta::assert_snapshot!(
+            stderr,
+            @"Include cycle: tests/fixtures/include/cycle/a.ccl -> tests/fixtures/include/cycle/b.ccl -> tests/fixtures/include/cycle/a.ccl"
+        );
+    }
+
+    #[test]
+    fn test_parse_error_location() {
+        let mut cmd = Command::cargo_bin("ccl-rs").unwrap();
+
+        let output = cmd
+            .write_stdin("a = b\n  c = d\nbroken\n")
+            .output()
+            .unwrap();
+
+        assert!(!output.status.success());
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        insta::assert_snapshot!(stderr, @r"
+        /dev/stdin:3:1: No value found for key: broken
+        broken
+        ^^^^^^
+        ");
+    }
+
+    #[test]
+    fn test_comments() {
+        let mut cmd = Command::cargo_bin("ccl-rs").unwrap();
+
+        let output = cmd
+            .arg("--file")
+            .arg("tests/fixtures/comments.ccl")
+            .output()
+            .unwrap();
+
+        let stdout = String::from_utf8(output.stdout).unwrap();
+
+        insta::assert_snapshot!(stdout, @r"
+        database =
+          port =
+            5432 =
+        name =
+          api =
+        ");
+    }
+
+    #[test]
+    fn test_keep_comments() {
+        let mut cmd = Command::cargo_bin("ccl-rs").unwrap();
+
+        let output = cmd
+            .arg("--file")
+            .arg("tests/fixtures/comments.ccl")
+            .arg("--keep-comments")
+            .output()
+            .unwrap();
+
+        let stdout = String::from_utf8(output.stdout).unwrap();
+
+        insta::assert_snapshot!(stdout, @r"
+        / =
+          End of file =
+          Service configuration =
+        database =
+          / =
+            Only read on startup =
+          port =
+            5432 =
+        name =
+          api =
+        ");
+    }
+
+    #[test]
+    fn test_crlf_with_bom() {
+        let mut cmd = Command::cargo_bin("ccl-rs").unwrap();
+
+        let output = cmd
+            .write_stdin("\u{feff}a =\r\n  b = c\r\n")
+            .output()
+            .unwrap();
+
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        assert_eq!(stdout, "a =\r\n  b =\r\n    c =\r\n");
+    }
+
+    #[test]
+    fn test_invalid_utf8() {
+        let mut cmd = Command::cargo_bin("ccl-rs").unwrap();
+
+        let output =
+            cmd.write_stdin(&b"a = b\nc = \xff\n"[..]).output().unwrap();
+
+        assert!(!output.status.success());
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        insta::assert_snapshot!(
+            stderr,
+            @"/dev/stdin:2:5: Invalid UTF-8 at byte 10"
+        );
+    }
+
+    #[test]
+    fn test_reports_all_problems() {
+        let mut cmd = Command::cargo_bin("ccl-rs").unwrap();
+
+        let output = cmd
+            .write_stdin("a =\n  b = c\n  d\nbroken\n")
+            .output()
+            .unwrap();
+
+        assert!(!output.status.success());
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        insta::assert_snapshot!(stderr, @r"
+        /dev/stdin:4:1: No value found for key: broken
+        broken
+        ^^^^^^
+        /dev/stdin:3:3: warning: No value found for key: d (in the value of a)
+          d
+          ^
+        ");
+    }
+
+    #[test]
+    fn test_warnings_are_not_fatal() {
+        let mut cmd = Command::cargo_bin("ccl-rs").unwrap();
+
+        let output = cmd.write_stdin("a =\n  b = c\n  d\n").output().unwrap();
+
+        assert!(output.status.success());
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        assert!(stdout.starts_with("a =\n"));
+    }
 }
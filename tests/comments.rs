@@ -0,0 +1,96 @@
+use ccl_rs::comment::{Comment, CommentMode};
+use ccl_rs::key_val::KeyVal;
+use ccl_rs::options::ParseOptions;
+use ccl_rs::parser::CCL;
+
+fn strip() -> ParseOptions {
+    ParseOptions {
+        comments: CommentMode::Strip,
+        ..ParseOptions::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+/= This is a CCL document
+/= with two comment lines
+title = CCL Example
+database =
+    /= Nested comment
+    enabled = true
+/= Trailing comment
+"#;
+
+    #[test]
+    fn test_split_comments() {
+        let key_vals = KeyVal::parse(CONFIG).unwrap();
+        let (entries, comments) = KeyVal::split_comments(key_vals);
+        assert_eq!(
+            entries.iter().map(|kv| kv.key.as_str()).collect::<Vec<_>>(),
+            vec!["title", "database"]
+        );
+        assert_eq!(
+            comments,
+            vec![
+                Comment {
+                    text: "This is a CCL document".to_string(),
+                    attached_to: Some(0),
+                },
+                Comment {
+                    text: "with two comment lines".to_string(),
+                    attached_to: Some(0),
+                },
+                Comment {
+                    text: "Trailing comment".to_string(),
+                    attached_to: None,
+                },
+            ]
+        );
+
+        // Nested comments are left in the value they are in
+        let database = &entries[1].value;
+        assert!(database.contains("/= Nested comment"));
+        let nested = KeyVal::parse(database).unwrap();
+        let (entries, comments) = KeyVal::split_comments(nested);
+        assert_eq!(entries, vec![KeyVal::new("enabled".into(), "true".into())]);
+        assert_eq!(
+            comments,
+            vec![Comment {
+                text: "Nested comment".to_string(),
+                attached_to: Some(0),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_strip_comments() {
+        let key_vals = KeyVal::parse(CONFIG).unwrap();
+        let ccl = CCL::parse_with(key_vals, &strip());
+        insta::assert_snapshot!(ccl.pretty(), @r"
+        database =
+          enabled =
+            true =
+        title =
+          CCL Example =
+        ");
+    }
+
+    #[test]
+    fn test_keep_comments() {
+        let key_vals = KeyVal::parse(CONFIG).unwrap();
+        assert_eq!(
+            CCL::parse_with(key_vals.clone(), &ParseOptions::default()),
+            CCL::parse(key_vals)
+        );
+    }
+
+    #[test]
+    fn test_strip_comment_only_section() {
+        let key_vals = KeyVal::parse("a =\n  /= nothing here\n").unwrap();
+        let ccl = CCL::parse_with(key_vals, &strip());
+        assert_eq!(ccl, CCL::key("a"));
+    }
+}
@@ -0,0 +1,87 @@
+#![allow(dead_code)]
+
+pub mod reparsing;
+
+use ccl_rs::key_val::{KeyVal, KeyVals};
+use ccl_rs::parser::CCL;
+use proptest::prelude::*;
+
+/// Small documents over few keys, so that two of them often share entries
+pub fn ccl_strategy() -> impl Strategy<Value = CCL> {
+    let value = "[a-c]{0,2}".prop_recursive(4, 32, 4, |inner| {
+        ("[a-c]{0,2}", inner)
+            .prop_map(|(key, value)| format!("{} = {}", key, value))
+    });
+    prop::collection::vec(("[a-c]{0,2}", value), 0..20).prop_map(|pairs| {
+        CCL::parse(
+            pairs
+                .into_iter()
+                .map(|(key, value)| KeyVal::new(key, value))
+                .collect(),
+        )
+    })
+}
+
+fn short_str() -> impl Strategy<Value = String> {
+    "[a-c]{0,3}"
+}
+
+pub fn value_strat() -> impl Strategy<Value = String> {
+    short_str().prop_recursive(8, 256, 10, |inner| {
+        (short_str(), inner)
+            .prop_map(|(key, value)| format!("{} = {}", key, value))
+    })
+}
+
+pub fn key_vals_strat() -> impl Strategy<Value = KeyVals> {
+    prop::collection::vec(
+        (short_str(), value_strat())
+            .prop_map(|(key, value)| KeyVal::new(key, value)),
+        0..50,
+    )
+}
+
+/// Arbitrary lines of keys, values, comments and `=` at varying
+/// indentation, with trailing whitespace and every kind of line ending,
+/// including none, which runs two lines together
+pub fn raw_text_strat() -> impl Strategy<Value = String> {
+    prop::collection::vec(
+        (
+            prop_oneof!["", " ", "  ", "\t", "    ", " \t", "\u{a0}"],
+            prop_oneof!["[a-c= \t]{0,6}", Just("/= c".to_string())],
+            prop_oneof!["", "\n", "\r\n", "\n\n"],
+        ),
+        0..40,
+    )
+    .prop_map(|lines| {
+        lines
+            .into_iter()
+            .map(|(indent, line, newline)| {
+                format!("{}{}{}", indent, line, newline)
+            })
+            .collect::<String>()
+    })
+}
+
+/// Documents as `CCL::pretty` writes them, or with a line per entry
+pub fn regular_text_strat() -> impl Strategy<Value = String> {
+    prop_oneof![
+        key_vals_strat().prop_map(|kvs| CCL::parse(kvs).pretty()),
+        key_vals_strat().prop_map(|kvs| {
+            kvs.iter()
+                .map(|kv| match kv.key.as_str() {
+                    "" => format!("= {}\n", kv.value),
+                    key => format!("{} = {}\n", key, kv.value),
+                })
+                .collect::<String>()
+        }),
+    ]
+}
+
+/// Documents as `CCL::pretty` writes them, or arbitrary text
+pub fn text_strat() -> impl Strategy<Value = String> {
+    prop_oneof![
+        key_vals_strat().prop_map(|kvs| CCL::parse(kvs).pretty()),
+        raw_text_strat(),
+    ]
+}
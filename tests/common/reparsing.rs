@@ -0,0 +1,29 @@
+use ccl_rs::comment::CommentMode;
+use ccl_rs::key_val::{KeyVal, KeyValNode, KeyValTree, KeyVals};
+use ccl_rs::options::ParseOptions;
+
+/// `KeyVal::parse_flat_to_tree_with` as first defined: by parsing every
+/// value again with `parse_with`, recursively. Kept as the reference the
+/// single-pass tree builder is tested and benchmarked against.
+pub fn parse_flat_to_tree_reparsing(
+    key_vals: &KeyVals,
+    options: &ParseOptions,
+) -> KeyValTree {
+    let mut tree = KeyValTree::new();
+
+    for key_val in key_vals {
+        if options.comments == CommentMode::Strip && key_val.is_comment() {
+            continue;
+        }
+        let KeyVal { key, value } = key_val;
+        let node = match KeyVal::parse_with(value, options) {
+            Ok(new_key_vals) if !new_key_vals.is_empty() => KeyValNode::Tree(
+                parse_flat_to_tree_reparsing(&new_key_vals, options),
+            ),
+            Err(_) | Ok(_) => KeyValNode::Leaf(value.to_string()),
+        };
+        tree.entry(key.clone()).or_default().push(node);
+    }
+
+    tree
+}
@@ -0,0 +1,318 @@
+mod common;
+
+use ccl_rs::comment::CommentMode;
+use ccl_rs::cst::{Cst, EditError, SyntaxKind};
+use ccl_rs::key_val::KeyVal;
+use ccl_rs::options::ParseOptions;
+use ccl_rs::parser::CCL;
+use common::{ccl_strategy, raw_text_strat, regular_text_strat};
+use proptest::prelude::*;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = "/= Service configuration
+name   =  api
+
+database =
+    host = localhost
+    port = 5432
+
+    limits =
+        cpu = 1500mi
+user = chshersh
+";
+
+    #[test]
+    fn test_roundtrip() {
+        let cst = Cst::parse(CONFIG).unwrap();
+        assert_eq!(cst.to_string(), CONFIG);
+        assert_eq!(cst.root().span().len(), CONFIG.len());
+    }
+
+    #[test]
+    fn test_find() {
+        let cst = Cst::parse(CONFIG).unwrap();
+        let cpu = cst.find(&["database", "limits", "cpu"]).unwrap();
+        assert_eq!(cpu.kind(), SyntaxKind::Entry);
+        assert_eq!(cpu.text(), "cpu = 1500mi");
+        assert_eq!(&CONFIG[cpu.span().start..cpu.span().end], "cpu = 1500mi");
+        assert_eq!(cpu.value().unwrap().text(), "1500mi");
+        let limits = cpu.parent().unwrap().parent().unwrap();
+        assert_eq!(limits.key().as_deref(), Some("limits"));
+        assert!(cst.find(&["database", "missing"]).is_none());
+        assert!(cst.find(&["name", "api"]).is_none());
+    }
+
+    #[test]
+    fn test_set_value() {
+        let mut cst = Cst::parse(CONFIG).unwrap();
+        cst.set_value(&["database", "port"], "6543").unwrap();
+        cst.set_value(&["name"], "web").unwrap();
+        insta::assert_snapshot!(cst, @r"
+        /= Service configuration
+        name   =  web
+
+        database =
+            host = localhost
+            port = 6543
+
+            limits =
+                cpu = 1500mi
+        user = chshersh
+        ");
+    }
+
+    #[test]
+    fn test_set_value_replaces_subtree() {
+        let mut cst = Cst::parse(CONFIG).unwrap();
+        cst.set_value(&["database"], "none").unwrap();
+        insta::assert_snapshot!(cst, @r"
+        /= Service configuration
+        name   =  api
+
+        database = none
+        user = chshersh
+        ");
+    }
+
+    #[test]
+    fn test_insert() {
+        let mut cst = Cst::parse(CONFIG).unwrap();
+        cst.insert(&["database", "limits"], "memory", "10Gb")
+            .unwrap();
+        cst.insert(&["database"], "user", "admin").unwrap();
+        cst.insert(&[], "debug", "").unwrap();
+        insta::assert_snapshot!(cst, @r"
+        /= Service configuration
+        name   =  api
+
+        database =
+            host = localhost
+            port = 5432
+
+            limits =
+                cpu = 1500mi
+                memory = 10Gb
+            user = admin
+        user = chshersh
+        debug =
+        ");
+    }
+
+    #[test]
+    fn test_insert_into_empty_value() {
+        let mut cst = Cst::parse("a = \nb = c").unwrap();
+        cst.insert(&["a"], "x", "y").unwrap();
+        assert_eq!(cst.to_string(), "a =\n  x = y\nb = c");
+        assert_eq!(
+            cst.insert(&["b"], "x", "y"),
+            Err(EditError::NotASection {
+                path: vec!["b".to_string()]
+            })
+        );
+    }
+
+    #[test]
+    fn test_insert_into_empty_document() {
+        let mut cst = Cst::parse("").unwrap();
+        cst.insert(&[], "a", "b").unwrap();
+        cst.insert(&[], "c", "d").unwrap();
+        assert_eq!(cst.to_string(), "a = b\nc = d");
+    }
+
+    #[test]
+    fn test_insert_keeps_crlf() {
+        let mut cst = Cst::parse("a =\r\n  b = c\r\n").unwrap();
+        cst.insert(&["a"], "d", "e").unwrap();
+        assert_eq!(cst.to_string(), "a =\r\n  b = c\r\n  d = e\r\n");
+    }
+
+    #[test]
+    fn test_remove() {
+        let mut cst = Cst::parse(CONFIG).unwrap();
+        cst.remove(&["database", "port"]).unwrap();
+        cst.remove(&["database", "limits", "cpu"]).unwrap();
+        cst.remove(&["/"]).unwrap();
+        insta::assert_snapshot!(cst, @r"
+        name   =  api
+
+        database =
+            host = localhost
+
+            limits =
+        user = chshersh
+        ");
+        assert_eq!(
+            cst.remove(&["database", "port"]),
+            Err(EditError::NotFound {
+                path: vec!["database".to_string(), "port".to_string()]
+            })
+        );
+
+        // The indentation of the first line goes with it
+        let mut cst = Cst::parse("  a = 1\n  b = 2\n").unwrap();
+        cst.remove(&["a"]).unwrap();
+        assert_eq!(cst.to_string(), "  b = 2\n");
+    }
+
+    #[test]
+    fn test_remove_subtree() {
+        let mut cst = Cst::parse(CONFIG).unwrap();
+        cst.remove(&["database"]).unwrap();
+        insta::assert_snapshot!(cst, @r"
+        /= Service configuration
+        name   =  api
+
+        user = chshersh
+        ");
+    }
+
+    #[test]
+    fn test_update() {
+        let mut cst = Cst::parse(CONFIG).unwrap();
+        let target = CCL::parse_str(
+            "
+name = api
+database =
+  host = localhost
+  port = 6543
+  limits =
+    cpu = 1500mi
+    memory = 2Gi
+hosts =
+  = db1
+  = db2
+",
+        )
+        .unwrap();
+        // Comments are not in the target, so they stay
+        cst.update(&target, &options(true));
+        insta::assert_snapshot!(cst, @r"
+        /= Service configuration
+        name   =  api
+
+        database =
+            host = localhost
+            port = 6543
+
+            limits =
+                cpu = 1500mi
+                memory = 2Gi
+        hosts =
+          = db1
+          = db2
+        ");
+    }
+
+    #[test]
+    fn test_update_values() {
+        let mut cst =
+            Cst::parse("hosts =\n  = db1\n  = db2\nport = 80\n").unwrap();
+        let target =
+            CCL::parse_str("hosts =\n  = db1\n  = db3\n  = db4\nport =")
+                .unwrap();
+        cst.update(&target, &ParseOptions::default());
+        insta::assert_snapshot!(cst, @r"
+        hosts =
+          = db1
+          = db3
+          = db4
+        port =
+        ");
+    }
+
+    #[test]
+    fn test_edits_share_untouched_subtrees() {
+        let cst = Cst::parse(CONFIG).unwrap();
+        let mut edited = cst.clone();
+        edited.set_value(&["user"], "someone").unwrap();
+        let database =
+            |cst: &Cst| cst.find(&["database"]).unwrap().green().clone();
+        assert!(std::sync::Arc::ptr_eq(&database(&cst), &database(&edited)));
+    }
+}
+
+proptest! {
+    #[test]
+    fn test_cst_roundtrip(text in raw_text_strat()) {
+        let Ok(cst) = Cst::parse(&text) else {
+            return Ok(());
+        };
+        prop_assert_eq!(cst.to_string(), text);
+    }
+
+    #[test]
+    fn test_cst_edits_keep_other_entries(text in raw_text_strat()) {
+        let Ok(mut cst) = Cst::parse(&text) else {
+            return Ok(());
+        };
+        let entries = cst.root().entries();
+        let Some(first) = entries.first() else {
+            return Ok(());
+        };
+        let key = first.key().unwrap();
+        cst.set_value(&[&key], "x").unwrap();
+
+        let before = KeyVal::parse(&text).unwrap();
+        let after = KeyVal::parse(&cst.to_string()).unwrap();
+        prop_assert_eq!(before.len(), after.len());
+        prop_assert_eq!(&after[0].value, "x");
+        prop_assert_eq!(&before[1..], &after[1..]);
+
+        // Removing leaves the lines of the other entries as they were,
+        // though they may nest differently without the first line
+        let mut cst = Cst::parse(&text).unwrap();
+        cst.remove(&[&key]).unwrap();
+        let removed = cst.to_string();
+        let mut lines = text.lines();
+        for line in removed.lines() {
+            prop_assert!(
+                lines.any(|original| original == line),
+                "line {:?} of {:?} is not in {:?}",
+                line,
+                removed,
+                text
+            );
+        }
+    }
+
+    #[test]
+    fn test_cst_update_to_same(
+        text in raw_text_strat(),
+        strip in any::<bool>(),
+    ) {
+        let Ok(mut cst) = Cst::parse(&text) else {
+            return Ok(());
+        };
+        let options = options(strip);
+        let ccl = CCL::parse_with(KeyVal::parse(&text).unwrap(), &options);
+        cst.update(&ccl, &options);
+        prop_assert_eq!(cst.to_string(), text);
+    }
+
+    #[test]
+    fn test_cst_update_reads_as_target(
+        text in regular_text_strat(),
+        target in ccl_strategy(),
+        strip in any::<bool>(),
+    ) {
+        let mut cst = Cst::parse(&text).unwrap();
+        let options = options(strip);
+        cst.update(&target, &options);
+        let updated = KeyVal::parse(&cst.to_string()).unwrap();
+        prop_assert_eq!(CCL::parse_with(updated, &options), target);
+    }
+}
+
+fn options(strip_comments: bool) -> ParseOptions {
+    ParseOptions {
+        comments: if strip_comments {
+            CommentMode::Strip
+        } else {
+            CommentMode::Keep
+        },
+        ..ParseOptions::default()
+    }
+}
@@ -0,0 +1,137 @@
+use ccl_rs::diff::Change;
+use ccl_rs::monoid::Monoid;
+use ccl_rs::parser::CCL;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        let old = r#"
+port = 8080
+debug =
+database =
+    host = localhost
+    hosts =
+        = db1
+        = db2
+    pool =
+        size = 10
+"#;
+        let new = r#"
+port = 443
+database =
+    host = localhost
+    hosts =
+        = db1
+        = db3
+        = db4
+cache =
+    size = 100
+"#;
+        let diff = CCL::parse_str(old)
+            .unwrap()
+            .diff(&CCL::parse_str(new).unwrap());
+        insta::assert_snapshot!(diff, @r"
+        + cache=size=100
+        ~ database=hosts=: db2 -> db3, db4
+        - database=pool=size=10
+        - debug
+        ~ port: 8080 -> 443
+        ");
+    }
+
+    #[test]
+    fn test_porcelain() {
+        let old = r#"
+port = 8080
+hosts =
+    = db1
+    = db2
+pool =
+    size = 10
+"#;
+        let new = r#"
+port = 443
+hosts =
+    = db1
+    = db3
+cache =
+    size = 100
+"#;
+        let diff = CCL::parse_str(old)
+            .unwrap()
+            .diff(&CCL::parse_str(new).unwrap());
+        insta::assert_snapshot!(diff.porcelain(), @r"
+        +	cache	size	100
+        -	hosts		db2
+        +	hosts		db3
+        -	pool
+        -	port	8080
+        +	port	443
+        ");
+    }
+
+    #[test]
+    fn test_changes() {
+        let old = r#"
+debug =
+database =
+    hosts =
+        = db1
+        = db2
+"#;
+        let new = r#"
+database =
+    hosts =
+        = db1
+        = db3
+        = db4
+cache =
+    size = 100
+"#;
+        let diff = CCL::parse_str(old)
+            .unwrap()
+            .diff(&CCL::parse_str(new).unwrap());
+        let paths = diff
+            .changes
+            .iter()
+            .map(|change| change.path().join("="))
+            .collect::<Vec<_>>();
+        assert_eq!(paths, ["cache", "database=hosts=", "debug"]);
+        assert_eq!(
+            diff.changes[1],
+            Change::Changed {
+                path: vec!["database".into(), "hosts".into(), "".into()],
+                old: vec!["db2".into()],
+                new: vec!["db3".into(), "db4".into()],
+            }
+        );
+    }
+
+    #[test]
+    fn test_no_changes() {
+        let ccl = CCL::parse_str("port = 8080\ndatabase =\n  host = localhost")
+            .unwrap();
+        assert!(ccl.diff(&ccl.clone()).is_empty());
+        assert_eq!(ccl.diff(&ccl).to_string(), "");
+    }
+
+    #[test]
+    fn test_values_added_or_removed() {
+        // Without values the other side lacks, nothing has changed
+        let old = CCL::parse_str("port = 8080").unwrap();
+        let new = CCL::parse_str("port = 8080\nport = 443").unwrap();
+        insta::assert_snapshot!(old.diff(&new), @"+ port=443");
+        insta::assert_snapshot!(new.diff(&old), @"- port=443");
+    }
+
+    #[test]
+    fn test_porcelain_escapes() {
+        let old = CCL::empty();
+        let new = CCL::key_val("a\tb", "c\\d");
+        assert_eq!(new.diff(&old).porcelain(), "-\ta\\tb\n");
+        assert_eq!(old.diff(&new).porcelain(), "+\ta\\tb\tc\\\\d\n");
+    }
+}
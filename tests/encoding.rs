@@ -0,0 +1,105 @@
+use ccl_rs::encoding::{BOM, LineEnding, decode};
+use ccl_rs::error::ParseError;
+use ccl_rs::key_val::KeyVal;
+use ccl_rs::span::Span;
+use proptest::prelude::*;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bom_does_not_leak_into_key() {
+        let config = "\u{feff}key = value\n";
+        let result = KeyVal::parse_bytes(config.as_bytes()).unwrap();
+        assert_eq!(result, vec![KeyVal::new("key".into(), "value".into())]);
+
+        // `parse` takes text as it is
+        assert_eq!(KeyVal::parse(config).unwrap()[0].key, "\u{feff}key");
+    }
+
+    #[test]
+    fn test_crlf() {
+        let config = "a = b \r\nc =\r\n  d = e\r\n\r\n  f = g\r\n";
+        let result = KeyVal::parse_bytes(config.as_bytes()).unwrap();
+        insta::assert_debug_snapshot!(result, @r#"
+        [
+            KeyVal {
+                key: "a",
+                value: "b",
+            },
+            KeyVal {
+                key: "c",
+                value: "\n  d = e\n\n  f = g",
+            },
+        ]
+        "#);
+        assert_eq!(KeyVal::parse(config).unwrap(), result);
+    }
+
+    #[test]
+    fn test_decode_records_style() {
+        let decoded = decode(b"\xef\xbb\xbfa = b\r\nc = d").unwrap();
+        assert_eq!(decoded.text, "a = b\nc = d");
+        assert_eq!(decoded.line_ending, LineEnding::CrLf);
+        assert!(decoded.bom);
+        assert_eq!(LineEnding::detect("a = b"), LineEnding::Lf);
+    }
+
+    #[test]
+    fn test_invalid_utf8() {
+        let err = KeyVal::parse_bytes(b"a = b\nc = \xe2\x82\n").unwrap_err();
+        insta::assert_snapshot!(err, @"Invalid UTF-8 at byte 10");
+        insta::assert_snapshot!(err.location(), @"<input>:2:5");
+        assert_eq!(err.span(), Span::new(10, 12));
+    }
+
+    #[test]
+    fn test_truncated_utf8() {
+        let err = KeyVal::parse_bytes(b"a = \xe2\x82").unwrap_err();
+        assert!(matches!(err, ParseError::InvalidUtf8 { .. }));
+        assert_eq!(err.span(), Span::new(4, 6));
+    }
+
+    #[test]
+    fn test_invalid_utf8_offset_counts_bom() {
+        let err = KeyVal::parse_bytes(b"\xef\xbb\xbfa = \xff").unwrap_err();
+        assert_eq!(err.span(), Span::new(7, 8));
+    }
+}
+
+/// Arbitrary lines of keys, values and `=` at varying indentation
+fn raw_lines_strat() -> impl Strategy<Value = Vec<String>> {
+    prop::collection::vec(
+        (0..4usize, "[a-c= ]{0,6}").prop_map(|(indent, line)| {
+            format!("{}{}", " ".repeat(indent), line)
+        }),
+        0..30,
+    )
+}
+
+proptest! {
+    #[test]
+    fn test_parse_bytes_ignores_line_endings_and_bom(
+        lines in raw_lines_strat(),
+        bom in any::<bool>(),
+    ) {
+        let lf = lines.join("\n");
+        let crlf = format!(
+            "{}{}",
+            if bom { BOM } else { "" },
+            lines.join("\r\n")
+        );
+        prop_assert_eq!(KeyVal::parse_bytes(crlf.as_bytes()), KeyVal::parse(&lf));
+    }
+
+    #[test]
+    fn test_line_ending_apply_roundtrip(lines in raw_lines_strat()) {
+        let crlf = lines.join("\r\n");
+        let decoded = decode(crlf.as_bytes()).unwrap();
+        if lines.len() > 1 {
+            prop_assert_eq!(decoded.line_ending, LineEnding::CrLf);
+            prop_assert_eq!(decoded.line_ending.apply(&decoded.text), crlf.as_str());
+        }
+    }
+}
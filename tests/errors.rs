@@ -1,4 +1,8 @@
+use ccl_rs::error::ParseError;
 use ccl_rs::key_val::KeyVal;
+use ccl_rs::options::ParseOptions;
+use ccl_rs::parser::CCL;
+use ccl_rs::span::Span;
 
 #[cfg(test)]
 mod tests {
@@ -27,4 +31,153 @@ mod tests {
           we
         ");
     }
+
+    #[test]
+    fn test_error_location() {
+        let config = r#"
+  bf = wd
+    sd = de
+ sdfg
+    sge
+  we
+"#;
+        let err = KeyVal::parse(config).unwrap_err().with_file("a.ccl");
+        let ParseError::UnclosedKey { location, .. } = &err else {
+            panic!("expected an unclosed key, got {:?}", err);
+        };
+        assert_eq!(location.file.as_deref(), Some("a.ccl"));
+        assert_eq!((location.line, location.column), (4, 2));
+        assert_eq!(location.span, Span::new(24, 41));
+        assert_eq!(
+            &config[location.span.start..location.span.end],
+            "sdfg\n    sge\n  we"
+        );
+        insta::assert_snapshot!(err.location(), @"a.ccl:4:2");
+    }
+
+    #[test]
+    fn test_error_location_no_file() {
+        let err = KeyVal::parse("a = b\nkey").unwrap_err();
+        insta::assert_snapshot!(err.location(), @"<input>:2:1");
+        assert_eq!(err.span(), Span::new(6, 9));
+    }
+
+    const MISSING_EQ: &str = r#"
+name = api
+server =
+  limits =
+    cpu = 1500mi
+    memory 10Gb
+  port = 8080
+description =
+  Free text
+  over two lines
+"#;
+
+    #[test]
+    fn test_try_parse_nested_error() {
+        let key_vals = KeyVal::parse(MISSING_EQ).unwrap();
+        let err = CCL::try_parse(key_vals.clone()).unwrap_err();
+        insta::assert_snapshot!(
+            err,
+            @"No value found for key: memory 10Gb (in the value of server=limits)"
+        );
+        let ParseError::InValue { path, error } = &err else {
+            panic!("expected an error in a value, got {:?}", err);
+        };
+        assert_eq!(path, &["server", "limits"]);
+        assert!(matches!(**error, ParseError::UnclosedKey { .. }));
+        // Located in the value of `server`
+        insta::assert_snapshot!(err.location(), @"<input>:4:5");
+
+        // The lenient parser keeps the section as text
+        let tree = KeyVal::parse_flat_to_tree(&key_vals);
+        insta::assert_debug_snapshot!(tree["server"], @r#"
+        [
+            Tree(
+                {
+                    "limits": [
+                        Leaf(
+                            "\n    cpu = 1500mi\n    memory 10Gb",
+                        ),
+                    ],
+                    "port": [
+                        Leaf(
+                            "8080",
+                        ),
+                    ],
+                },
+            ),
+        ]
+        "#);
+    }
+
+    #[test]
+    fn test_try_parse_str_locates_error_in_source() {
+        let err = CCL::try_parse_str(MISSING_EQ).unwrap_err();
+        insta::assert_snapshot!(err.location(), @"<input>:6:5");
+        assert_eq!(
+            &MISSING_EQ[err.span().start..err.span().end],
+            "memory 10Gb"
+        );
+        assert_eq!(
+            err,
+            KeyVal::try_parse_tree(MISSING_EQ).map(|_| ()).unwrap_err()
+        );
+    }
+
+    #[test]
+    fn test_try_parse_accepts_text_values() {
+        let config = "a = b\nc =\n  Free text\n  over two lines\nd = e = f\n";
+        let key_vals = KeyVal::parse(config).unwrap();
+        assert_eq!(
+            KeyVal::try_parse_flat_to_tree(&key_vals).unwrap(),
+            KeyVal::parse_flat_to_tree(&key_vals)
+        );
+        assert_eq!(CCL::try_parse_str(config).unwrap(), CCL::parse(key_vals));
+    }
+
+    #[test]
+    fn test_try_parse_first_error() {
+        let config = "a =\n  b = c\n  d\ne =\n  f = g\n  h\n";
+        let err = CCL::try_parse_str(config).unwrap_err();
+        insta::assert_snapshot!(
+            err,
+            @"No value found for key: d (in the value of a)"
+        );
+    }
+
+    #[test]
+    fn test_try_parse_nested_indentation() {
+        let options = ParseOptions::strict();
+        let key_vals = vec![KeyVal::new(
+            "a".into(),
+            "b =\n\tc = 1\nd =\n    e = 2".into(),
+        )];
+        let err = KeyVal::try_parse_flat_to_tree_with(&key_vals, &options)
+            .unwrap_err();
+        insta::assert_snapshot!(
+            err,
+            @"Bad indentation: indented with spaces, but earlier lines use tabs (in the value of a)"
+        );
+    }
+
+    #[test]
+    fn test_max_depth() {
+        let options = ParseOptions {
+            max_depth: Some(2),
+            ..ParseOptions::default()
+        };
+        let config = "a =\n  b =\n    c = 1\nd = 2";
+        let err = CCL::parse_str_with(config, &options).unwrap_err();
+        insta::assert_snapshot!(err, @"Limit exceeded: nesting depth (max 2)");
+        insta::assert_snapshot!(err.location(), @"<input>:3:5");
+
+        let err =
+            KeyVal::parse_tree_with("a = b = c = 1", &options).unwrap_err();
+        insta::assert_snapshot!(err.location(), @"<input>:1:9");
+
+        let tree = KeyVal::parse_tree_with("a =\n  b = c\nd = 2", &options);
+        assert!(tree.is_ok());
+    }
 }
@@ -0,0 +1,169 @@
+use ccl_rs::error::FilterError;
+use ccl_rs::filter::{Filter, Value};
+use ccl_rs::parser::CCL;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+database =
+    main =
+        port = 5432
+        enabled = true
+    cache =
+        port = 6379
+        enabled = false
+    backup =
+        port = 5433
+hosts =
+    = db1
+    = db2
+"#;
+
+    /// The outputs of `filter`, one per line
+    fn eval(filter: &str) -> String {
+        let ccl = CCL::parse_str(CONFIG).unwrap();
+        Filter::parse(filter)
+            .unwrap()
+            .eval(&ccl)
+            .unwrap()
+            .iter()
+            .map(|value| match value {
+                Value::Ccl(ccl) => ccl.pretty(),
+                value => format!("{}\n", value),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_paths() {
+        insta::assert_snapshot!(eval(".database.main"), @r"
+        enabled =
+          true =
+        port =
+          5432 =
+        ");
+        assert_eq!(eval(r#".database | ."main" | .port | raw"#), "5432\n");
+        assert_eq!(eval(".database.missing"), "");
+        assert_eq!(eval(".hosts | .\"\" | keys"), "db1\ndb2\n");
+    }
+
+    #[test]
+    fn test_builtins() {
+        assert_eq!(eval(".database | keys"), "backup\ncache\nmain\n");
+        assert_eq!(eval(".database | length"), "3\n");
+        assert_eq!(
+            eval(".database | values | .port | raw"),
+            "5433\n6379\n5432\n"
+        );
+        assert_eq!(eval(".database | first | keys"), "backup\n");
+        assert_eq!(eval("first(.database | keys)"), "backup\n");
+        assert_eq!(eval(".database | keys | first(length)"), "6\n5\n4\n");
+    }
+
+    #[test]
+    fn test_select() {
+        assert_eq!(
+            eval(
+                ".database | values | select(.enabled == \"true\") | .port | raw"
+            ),
+            "5432\n"
+        );
+        assert_eq!(
+            eval(".database | values | select(.enabled) | .port | raw"),
+            "6379\n5432\n"
+        );
+        assert_eq!(
+            eval(".database | values | select(.port != 5432) | .port | raw"),
+            "5433\n6379\n"
+        );
+        assert_eq!(
+            eval(".database | keys | select(length == 4 | not)"),
+            "backup\ncache\n"
+        );
+    }
+
+    #[test]
+    fn test_map() {
+        // `backup` has no `enabled`, so nothing to compare or select
+        insta::assert_snapshot!(
+            eval(".database | map(select(.enabled == \"false\" | not))"),
+            @r"
+        main =
+          enabled =
+            true =
+          port =
+            5432 =
+        "
+        );
+        insta::assert_snapshot!(eval(".database | map(.port)"), @r"
+        backup =
+          5433 =
+        cache =
+          6379 =
+        main =
+          5432 =
+        ");
+        insta::assert_snapshot!(eval(".database | map(length)"), @r"
+        backup =
+          1 =
+        cache =
+          2 =
+        main =
+          2 =
+        ");
+    }
+
+    #[test]
+    fn test_eval_errors() {
+        let ccl = CCL::parse_str(CONFIG).unwrap();
+        let err = Filter::parse(".database | keys | keys")
+            .unwrap()
+            .eval(&ccl)
+            .unwrap_err();
+        insta::assert_snapshot!(
+            err,
+            @"keys: cannot apply to text (backup)"
+        );
+        let err = Filter::parse(".database | raw")
+            .unwrap()
+            .eval(&ccl)
+            .unwrap_err();
+        insta::assert_snapshot!(
+            err,
+            @"raw: Expected one value, found 3: backup, cache, main"
+        );
+    }
+
+    #[test]
+    fn test_syntax_errors() {
+        assert_eq!(
+            Filter::parse("keys | sort").unwrap_err(),
+            FilterError::Syntax {
+                position: 7,
+                message: "unknown filter `sort`".into()
+            }
+        );
+        insta::assert_snapshot!(
+            Filter::parse("select(.a").unwrap_err(),
+            @"Invalid filter at byte 9: expected `)`, found the end of the filter"
+        );
+        insta::assert_snapshot!(
+            Filter::parse(".a |").unwrap_err(),
+            @"Invalid filter at byte 4: expected a filter"
+        );
+        insta::assert_snapshot!(
+            Filter::parse(".a .b)").unwrap_err(),
+            @"Invalid filter at byte 5: unexpected `)`"
+        );
+        insta::assert_snapshot!(
+            Filter::parse("\"abc").unwrap_err(),
+            @"Invalid filter at byte 0: unclosed string"
+        );
+        insta::assert_snapshot!(
+            Filter::parse(".a = 1").unwrap_err(),
+            @"Invalid filter at byte 3: unexpected character `=`"
+        );
+    }
+}
@@ -0,0 +1,129 @@
+use ccl_rs::error::IncludeError;
+use ccl_rs::include::Loader;
+use ccl_rs::parser::CCL;
+use std::path::PathBuf;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIR: &str = "tests/fixtures/include";
+
+    fn load(file: &str) -> Result<CCL, IncludeError> {
+        Loader::default().load(format!("{}/{}", DIR, file))
+    }
+
+    #[test]
+    fn test_load() {
+        // Hidden files and directories are not matched by the glob
+        let ccl = load("main.ccl").unwrap();
+        insta::assert_snapshot!(ccl.pretty(), @r"
+        / =
+          Everything the service needs =
+        hosts =
+           =
+            db1 =
+            db2 =
+        name =
+          main =
+        port =
+          8080 =
+        services =
+          billing =
+            replicas =
+              2 =
+          search =
+            replicas =
+              3 =
+        ");
+    }
+
+    #[test]
+    fn test_resolve() {
+        let ccl = CCL::parse_str("@include = deep/three.ccl\nhere =").unwrap();
+        let resolved = Loader::default()
+            .resolve(ccl, format!("{}/<input>", DIR))
+            .unwrap();
+        assert_eq!(resolved, CCL::parse_str("here =\nthree =").unwrap());
+
+        let ccl = CCL::parse_str("@include = none/*.ccl\nhere =").unwrap();
+        let resolved = Loader::default()
+            .resolve(ccl, format!("{}/<input>", DIR))
+            .unwrap();
+        assert_eq!(resolved, CCL::parse_str("here =").unwrap());
+    }
+
+    #[test]
+    fn test_not_opted_in() {
+        let text = std::fs::read_to_string(format!("{}/main.ccl", DIR));
+        let ccl = CCL::parse_str(&text.unwrap()).unwrap();
+        assert!(ccl.contains(&["@include", "common.ccl"]));
+    }
+
+    #[test]
+    fn test_cycle() {
+        let err = load("cycle/a.ccl").unwrap_err();
+        assert!(matches!(err, IncludeError::Cycle { .. }));
+        insta::assert_snapshot!(
+            err,
+            @"Include cycle: tests/fixtures/include/cycle/a.ccl -> tests/fixtures/include/cycle/b.ccl -> tests/fixtures/include/cycle/a.ccl"
+        );
+    }
+
+    #[test]
+    fn test_symlink() {
+        // `work/conf` links to `real/app/conf`, so `..` from it is
+        // `real/app`, where the OS resolves it, and not `work`
+        let ccl = load("symlink/work/conf/main.ccl").unwrap();
+        assert_eq!(ccl, CCL::parse_str("main = 1\nshared = real").unwrap());
+    }
+
+    #[test]
+    fn test_max_depth() {
+        let loader = Loader::default().with_max_depth(1);
+        let err = loader.load(format!("{}/deep/one.ccl", DIR)).unwrap_err();
+        assert_eq!(
+            err,
+            IncludeError::TooDeep {
+                chain: ["one.ccl", "two.ccl", "three.ccl"]
+                    .map(|file| PathBuf::from(DIR).join("deep").join(file))
+                    .to_vec(),
+                max: 1,
+            }
+        );
+        insta::assert_snapshot!(
+            err,
+            @"Includes nested deeper than 1 (include chain: tests/fixtures/include/deep/one.ccl -> tests/fixtures/include/deep/two.ccl -> tests/fixtures/include/deep/three.ccl)"
+        );
+
+        let loader = Loader::default().with_max_depth(2);
+        let ccl = loader.load(format!("{}/deep/one.ccl", DIR)).unwrap();
+        assert_eq!(ccl, CCL::parse_str("one =\ntwo =\nthree =").unwrap());
+    }
+
+    #[test]
+    fn test_errors() {
+        let err = load("errors/missing.ccl").unwrap_err();
+        assert!(matches!(err, IncludeError::Io { .. }));
+        insta::assert_snapshot!(
+            err,
+            @"Failed to read file 'tests/fixtures/include/errors/nothere.ccl': No such file or directory (os error 2) (include chain: tests/fixtures/include/errors/missing.ccl -> tests/fixtures/include/errors/nothere.ccl)"
+        );
+
+        let err = load("errors/includes_bad.ccl").unwrap_err();
+        let IncludeError::Parse { error, .. } = &err else {
+            panic!("expected a parse error, found {:?}", err);
+        };
+        assert_eq!(error.location().line, 2);
+        insta::assert_snapshot!(
+            err,
+            @"tests/fixtures/include/errors/bad.ccl:2:1: No value found for key: broken (include chain: tests/fixtures/include/errors/includes_bad.ccl -> tests/fixtures/include/errors/bad.ccl)"
+        );
+
+        let err = load("errors/nested.ccl").unwrap_err();
+        insta::assert_snapshot!(
+            err,
+            @"Invalid include in 'tests/fixtures/include/errors/nested.ccl': expected a path, found entries under 'path'"
+        );
+    }
+}
@@ -1,4 +1,6 @@
 use ccl_rs::key_val::KeyVal;
+use ccl_rs::options::ParseOptions;
+use ccl_rs::span::Span;
 
 #[cfg(test)]
 mod tests {
@@ -168,4 +170,166 @@ wd
           we
         ");
     }
+
+    #[test]
+    fn test_tab_counts_as_one_column_by_default() {
+        // The tab is as wide as the single space of the first line, so
+        // `b` is a new entry rather than part of `a`'s value
+        let config = " a = 1\n\tb = 2\n";
+        let result = KeyVal::parse(config).unwrap();
+        assert_eq!(result.len(), 2);
+        assert_eq!(
+            KeyVal::parse_with(config, &ParseOptions::default()).unwrap(),
+            result
+        );
+    }
+
+    #[test]
+    fn test_tab_width() {
+        let config = " a = 1\n\tb = 2\n";
+        let options = ParseOptions {
+            tab_width: 4,
+            ..ParseOptions::default()
+        };
+        let result = KeyVal::parse_with(config, &options).unwrap();
+        insta::assert_debug_snapshot!(result, @r#"
+        [
+            KeyVal {
+                key: "a",
+                value: "1\n\tb = 2",
+            },
+        ]
+        "#);
+    }
+
+    #[test]
+    fn test_tab_stops() {
+        let options = ParseOptions {
+            tab_width: 4,
+            ..ParseOptions::default()
+        };
+        assert_eq!(options.indent_width("\tx"), 4);
+        assert_eq!(options.indent_width("  \tx"), 4);
+        assert_eq!(options.indent_width("\t  x"), 6);
+        assert_eq!(options.indent_width("    \tx"), 8);
+    }
+
+    #[test]
+    fn test_reject_tabs() {
+        let config = "a =\n  b = 1\n\tc = 2\n";
+        let options = ParseOptions {
+            reject_tabs: true,
+            ..ParseOptions::default()
+        };
+        let err = KeyVal::parse_with(config, &options).unwrap_err();
+        insta::assert_snapshot!(err, @"Bad indentation: tab in indentation");
+        insta::assert_snapshot!(err.location(), @"<input>:3:1");
+        assert_eq!(err.span(), Span::new(12, 13));
+    }
+
+    #[test]
+    fn test_strict_mixed_tabs_and_spaces_in_line() {
+        let config = "a =\n \tb = 1\n";
+        let err =
+            KeyVal::parse_with(config, &ParseOptions::strict()).unwrap_err();
+        insta::assert_snapshot!(err, @"Bad indentation: mixed tabs and spaces");
+        assert_eq!(err.span(), Span::new(4, 6));
+    }
+
+    #[test]
+    fn test_strict_mixed_tabs_and_spaces_across_lines() {
+        let config = "a =\n\tb = 1\nc =\n    d = 2\n";
+        let err =
+            KeyVal::parse_with(config, &ParseOptions::strict()).unwrap_err();
+        insta::assert_snapshot!(
+            err,
+            @"Bad indentation: indented with spaces, but earlier lines use tabs"
+        );
+        insta::assert_snapshot!(err.location(), @"<input>:4:1");
+    }
+
+    #[test]
+    fn test_strict_inconsistent_dedent() {
+        let config = r#"
+a =
+    b = 1
+  c = 2
+"#;
+        // Leniently, `c` is a sibling of `b`
+        assert_eq!(KeyVal::parse(config).unwrap().len(), 1);
+
+        let err =
+            KeyVal::parse_with(config, &ParseOptions::strict()).unwrap_err();
+        insta::assert_snapshot!(
+            err,
+            @"Bad indentation: dedent does not match any outer indentation level"
+        );
+        insta::assert_snapshot!(err.location(), @"<input>:4:1");
+    }
+
+    #[test]
+    fn test_strict_dedent_below_first_key() {
+        let config = r#"
+  key
+val = 1
+"#;
+        let err =
+            KeyVal::parse_with(config, &ParseOptions::strict()).unwrap_err();
+        insta::assert_snapshot!(err.location(), @"<input>:3:1");
+    }
+
+    #[test]
+    fn test_strict_accepts_consistent_indentation() {
+        let config = "a =\n\tb =\n\t\tc = 1\n\td = 2\ne = 3\n";
+        let result = KeyVal::parse_with(config, &ParseOptions::strict());
+        assert_eq!(result, KeyVal::parse(config));
+    }
+
+    #[test]
+    fn test_unicode_whitespace_is_indentation() {
+        // U+3000 IDEOGRAPHIC SPACE is three bytes, so three columns: more
+        // than the two spaces before `b`, which therefore starts a new
+        // entry
+        let config = "\u{3000}a = 1\n  b = 2\n";
+        let result = KeyVal::parse(config).unwrap();
+        insta::assert_debug_snapshot!(result, @r#"
+        [
+            KeyVal {
+                key: "a",
+                value: "1",
+            },
+            KeyVal {
+                key: "b",
+                value: "2",
+            },
+        ]
+        "#);
+    }
+
+    #[test]
+    fn test_unicode_whitespace_nests_values() {
+        // U+00A0 NO-BREAK SPACE counts as two columns
+        let config = "a =\n\u{a0}b = 1\n";
+        let result = KeyVal::parse(config).unwrap();
+        insta::assert_debug_snapshot!(result, @r#"
+        [
+            KeyVal {
+                key: "a",
+                value: "\n\u{a0}b = 1",
+            },
+        ]
+        "#);
+    }
+
+    #[test]
+    fn test_strict_rejects_unicode_whitespace() {
+        let config = "a =\n\u{2003}b = 1\n";
+        let err =
+            KeyVal::parse_with(config, &ParseOptions::strict()).unwrap_err();
+        insta::assert_snapshot!(
+            err,
+            @"Bad indentation: non-ASCII whitespace U+2003 in indentation"
+        );
+        assert_eq!(err.span(), Span::new(4, 7));
+    }
 }
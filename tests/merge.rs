@@ -0,0 +1,373 @@
+mod common;
+
+use ccl_rs::merge::{Conflict, MergeOptions, MergeStrategy};
+use ccl_rs::merge3;
+use ccl_rs::options::ParseOptions;
+use ccl_rs::parser::CCL;
+use common::ccl_strategy;
+use proptest::prelude::*;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_last_wins() {
+        let defaults = r#"
+port = 8080
+database =
+    host = localhost
+    pool =
+        size = 10
+    verbose =
+"#;
+        let prod = r#"
+port = 443
+database =
+    host = db.example.com
+"#;
+        let options = MergeOptions::new(MergeStrategy::LastWins);
+        let merged = CCL::parse_str(defaults)
+            .unwrap()
+            .merge_with(CCL::parse_str(prod).unwrap(), &options);
+        // `verbose` is a value of `database`, so it goes as well, while
+        // `pool` has entries under it and stays
+        insta::assert_snapshot!(merged.pretty(), @r"
+        database =
+          host =
+            db.example.com =
+          pool =
+            size =
+              10 =
+        port =
+          443 =
+        ");
+    }
+
+    #[test]
+    fn test_first_wins() {
+        let defaults = r#"
+port = 8080
+database =
+    host = localhost
+"#;
+        let prod = r#"
+port = 443
+debug =
+database =
+    host = db.example.com
+    pool =
+        size = 10
+"#;
+        let options = MergeOptions::new(MergeStrategy::FirstWins);
+        let merged = CCL::parse_str(defaults)
+            .unwrap()
+            .merge_with(CCL::parse_str(prod).unwrap(), &options);
+        insta::assert_snapshot!(merged.pretty(), @r"
+        database =
+          host =
+            localhost =
+          pool =
+            size =
+              10 =
+        debug =
+        port =
+          8080 =
+        ");
+    }
+
+    #[test]
+    fn test_replace() {
+        let defaults = r#"
+debug =
+database =
+    host = localhost
+    pool =
+        size = 10
+"#;
+        let prod = r#"
+database =
+    host = db.example.com
+"#;
+        let options = MergeOptions::new(MergeStrategy::Replace);
+        let merged = CCL::parse_str(defaults)
+            .unwrap()
+            .merge_with(CCL::parse_str(prod).unwrap(), &options);
+        insta::assert_snapshot!(merged.pretty(), @r"
+        database =
+          host =
+            db.example.com =
+        debug =
+        ");
+        // Nothing under the entry on the right replaces nothing
+        let merged = CCL::parse_str("a = b")
+            .unwrap()
+            .merge_with(CCL::parse_str("a =").unwrap(), &options);
+        assert_eq!(merged.get_str(&["a"]), Ok("b"));
+    }
+
+    #[test]
+    fn test_overrides() {
+        let defaults = r#"
+port = 8080
+database =
+    host = localhost
+    hosts =
+        = db1
+        = db2
+"#;
+        let prod = r#"
+port = 443
+database =
+    host = db.example.com
+    hosts =
+        = db3
+"#;
+        let options = MergeOptions::new(MergeStrategy::LastWins)
+            .with_override(&["database"], MergeStrategy::Union)
+            .with_override(&["database", "hosts"], MergeStrategy::LastWins);
+        assert_eq!(
+            options.strategy_at(&["database".into(), "host".into()]),
+            MergeStrategy::Union
+        );
+        let merged = CCL::parse_str(defaults)
+            .unwrap()
+            .merge_with(CCL::parse_str(prod).unwrap(), &options);
+        insta::assert_snapshot!(merged.pretty(), @r"
+        database =
+          host =
+            db.example.com =
+            localhost =
+          hosts =
+             =
+              db3 =
+        port =
+          443 =
+        ");
+    }
+
+    #[test]
+    fn test_union() {
+        let defaults = CCL::parse_str("port = 8080\nhosts =\n  = db1").unwrap();
+        let prod = CCL::parse_str("port = 443\nhosts =\n  = db2").unwrap();
+        assert_eq!(
+            defaults
+                .clone()
+                .merge_with(prod.clone(), &MergeOptions::default()),
+            CCL::aggregate_with(vec![defaults, prod], &MergeOptions::default())
+        );
+    }
+
+    #[test]
+    fn test_parse_strategy() {
+        for strategy in MergeStrategy::ALL {
+            assert_eq!(strategy.to_string().parse(), Ok(strategy));
+        }
+        insta::assert_snapshot!(
+            "newest".parse::<MergeStrategy>().unwrap_err(),
+            @"unknown merge strategy `newest`, expected one of: union, last-wins, first-wins, replace"
+        );
+    }
+
+    #[test]
+    fn test_merge3_clean() {
+        let base = r#"
+port = 8080
+hosts =
+    = db1
+debug =
+pool =
+    size = 10
+    timeout = 5
+"#;
+        let ours = r#"
+port = 443
+hosts =
+    = db1
+    = db2
+pool =
+    size = 10
+    timeout = 5
+"#;
+        let theirs = r#"
+port = 8080
+hosts =
+    = db1
+    = db3
+debug =
+pool =
+    size = 20
+    timeout = 5
+"#;
+        let result = merge3(
+            &CCL::parse_str(base).unwrap(),
+            &CCL::parse_str(ours).unwrap(),
+            &CCL::parse_str(theirs).unwrap(),
+        );
+        assert!(result.is_clean());
+        // Both additions to the list, each side's change to a value, and
+        // the removal of `debug`
+        insta::assert_snapshot!(result.merged.pretty(), @r"
+        hosts =
+           =
+            db1 =
+            db2 =
+            db3 =
+        pool =
+          size =
+            20 =
+          timeout =
+            5 =
+        port =
+          443 =
+        ");
+    }
+
+    #[test]
+    fn test_merge3_conflicts() {
+        let base = r#"
+port = 8080
+database =
+    pool =
+        size = 10
+"#;
+        let ours = r#"
+port = 443
+"#;
+        let theirs = r#"
+port = 9090
+database =
+    pool =
+        size = 20
+"#;
+        let result = merge3(
+            &CCL::parse_str(base).unwrap(),
+            &CCL::parse_str(ours).unwrap(),
+            &CCL::parse_str(theirs).unwrap(),
+        );
+        assert_eq!(
+            result.conflicts[0],
+            Conflict {
+                path: vec!["database".into()],
+                ours: None,
+                theirs: Some(CCL::parse_str("pool =\n  size = 20").unwrap()),
+            }
+        );
+        let conflicts = result
+            .conflicts
+            .iter()
+            .map(|conflict| conflict.to_string())
+            .collect::<Vec<_>>();
+        insta::assert_debug_snapshot!(conflicts, @r#"
+        [
+            "ours removed 'database', theirs changed it",
+            "both sides changed the values of 'port': 443 and 9090",
+        ]
+        "#);
+        // Whatever either side has where they conflict is kept
+        insta::assert_snapshot!(result.merged.pretty(), @r"
+        database =
+          pool =
+            size =
+              20 =
+        port =
+          443 =
+          9090 =
+        ");
+        // Written out, the conflicts are named in comments at the top
+        let text = result.pretty();
+        let comments = text.strip_suffix(&result.merged.pretty());
+        insta::assert_snapshot!(comments.unwrap(), @r"
+        /= Conflict: ours removed 'database', theirs changed it
+        /= Conflict: both sides changed the values of 'port': 443 and 9090
+        ");
+    }
+
+    #[test]
+    fn test_merge3_apply() {
+        let base = r#"
+/= Listens on
+port = 8080
+hosts =
+    = db1
+"#;
+        let ours = r#"
+/= Listens on
+port   =   8080
+hosts =
+    = db1
+    = db2
+"#;
+        let theirs = r#"
+/= Listens on
+port = 9090
+hosts =
+    = db1
+"#;
+        let options = ParseOptions::default();
+        let result = merge3(
+            &CCL::parse_str(base).unwrap(),
+            &CCL::parse_str(ours).unwrap(),
+            &CCL::parse_str(theirs).unwrap(),
+        );
+        assert!(result.is_clean());
+        // Only the value of `port` changes in our text
+        insta::assert_snapshot!(result.apply(ours, &options).unwrap(), @r"
+        /= Listens on
+        port   =   9090
+        hosts =
+            = db1
+            = db2
+        ");
+    }
+
+    #[test]
+    fn test_merge3_added_on_both_sides() {
+        let base = CCL::parse_str("port = 8080").unwrap();
+        let result = merge3(
+            &base,
+            &CCL::parse_str("port = 8080\ncache = size = 1").unwrap(),
+            &CCL::parse_str("port = 8080\ncache = size = 2").unwrap(),
+        );
+        insta::assert_snapshot!(
+            result.conflicts[0],
+            @"both sides changed the values of 'cache=size': 1 and 2"
+        );
+        let result = merge3(
+            &base,
+            &CCL::parse_str("port = 8080\ncache = size = 1").unwrap(),
+            &CCL::parse_str("port = 8080\ncache = ttl = 2").unwrap(),
+        );
+        assert!(result.is_clean());
+    }
+}
+
+proptest! {
+    #[test]
+    fn test_merge3_one_side_changed(
+        base in ccl_strategy(),
+        changed in ccl_strategy()
+    ) {
+        let ours = merge3(&base, &changed, &base);
+        prop_assert!(ours.is_clean());
+        prop_assert_eq!(&ours.merged, &changed);
+        let theirs = merge3(&base, &base, &changed);
+        prop_assert!(theirs.is_clean());
+        prop_assert_eq!(&theirs.merged, &changed);
+        let both = merge3(&base, &changed, &changed);
+        prop_assert!(both.is_clean());
+        prop_assert_eq!(&both.merged, &changed);
+    }
+
+    #[test]
+    fn test_merge3_symmetric(
+        base in ccl_strategy(),
+        ours in ccl_strategy(),
+        theirs in ccl_strategy()
+    ) {
+        let forward = merge3(&base, &ours, &theirs);
+        let backward = merge3(&base, &theirs, &ours);
+        prop_assert_eq!(forward.merged, backward.merged);
+        prop_assert_eq!(forward.conflicts.len(), backward.conflicts.len());
+    }
+}
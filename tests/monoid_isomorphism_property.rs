@@ -1,5 +1,8 @@
+mod common;
+
 use ccl_rs::key_val::{KeyVal, KeyVals};
 use ccl_rs::parser::CCL;
+use common::raw_text_strat;
 use proptest::prelude::*;
 
 prop_compose! {
@@ -51,7 +54,41 @@ prop_compose! {
     }
 }
 
+prop_compose! {
+    /// Key-value pairs as `KeyVal::parse` returns them
+    fn parsed_key_vals_strat()(
+        text in raw_text_strat()
+            .prop_filter("must parse", |text| KeyVal::parse(text).is_ok())
+    ) -> KeyVals {
+        KeyVal::parse(&text).unwrap()
+    }
+}
+
 proptest! {
+    #[test]
+    fn test_key_vals_roundtrip(key_vals in key_vals_strat()) {
+        let pretty = KeyVal::pretty(&key_vals);
+        prop_assert_eq!(KeyVal::parse(&pretty).unwrap(), key_vals);
+    }
+
+    #[test]
+    fn test_parsed_key_vals_roundtrip(key_vals in parsed_key_vals_strat()) {
+        let pretty = KeyVal::pretty(&key_vals);
+        prop_assert_eq!(KeyVal::parse(&pretty).unwrap(), key_vals);
+    }
+
+    #[test]
+    fn test_pretty_homomorphism(
+        x in parsed_key_vals_strat(),
+        y in parsed_key_vals_strat()
+    ) {
+        let both = x.iter().chain(&y).cloned().collect::<KeyVals>();
+        prop_assert_eq!(
+            KeyVal::pretty(&both),
+            KeyVal::pretty(&x) + &KeyVal::pretty(&y)
+        );
+    }
+
     #[test]
     fn test_roundtrip(ccl in ccl_strat()) {
         let ccl2 = CCL::parse(KeyVal::parse(&ccl.pretty()).unwrap());
@@ -1,5 +1,7 @@
 use ccl_rs::key_val::{KeyVal, KeyVals};
+use ccl_rs::merge::{MergeOptions, MergeStrategy};
 use ccl_rs::monoid::Monoid;
+use ccl_rs::parser::CCL;
 use proptest::prelude::*;
 
 prop_compose! {
@@ -54,6 +56,30 @@ prop_compose! {
     }
 }
 
+prop_compose! {
+    fn ccl_strategy()(key_vals in key_vals_strategy()) -> CCL {
+        CCL::parse(key_vals)
+    }
+}
+
+fn merge_strategy() -> impl Strategy<Value = MergeStrategy> {
+    prop::sample::select(MergeStrategy::ALL.to_vec())
+}
+
+prop_compose! {
+    /// A default strategy, with others under a few short paths
+    fn merge_options_strategy()(
+        default in merge_strategy(),
+        overrides in prop::collection::btree_map(
+            prop::collection::vec(key_strategy(), 1..3),
+            merge_strategy(),
+            0..4,
+        )
+    ) -> MergeOptions {
+        MergeOptions { default, overrides }
+    }
+}
+
 proptest! {
     #[test]
     fn test_associativity(
@@ -80,4 +106,34 @@ proptest! {
         prop_assert_eq!(x, result);
     }
 
+    #[test]
+    fn test_merge_with_associativity(
+        x in ccl_strategy(),
+        y in ccl_strategy(),
+        z in ccl_strategy(),
+        options in merge_options_strategy()
+    ) {
+        let left = x
+            .clone()
+            .merge_with(y.clone(), &options)
+            .merge_with(z.clone(), &options);
+        let right = x.merge_with(y.merge_with(z, &options), &options);
+        prop_assert_eq!(left, right);
+    }
+
+    #[test]
+    fn test_merge_with_empty(
+        x in ccl_strategy(),
+        options in merge_options_strategy()
+    ) {
+        prop_assert_eq!(&CCL::empty().merge_with(x.clone(), &options), &x);
+        prop_assert_eq!(&x.clone().merge_with(CCL::empty(), &options), &x);
+    }
+
+    #[test]
+    fn test_union_is_merge(x in ccl_strategy(), y in ccl_strategy()) {
+        let options = MergeOptions::default();
+        let union = x.clone().merge_with(y.clone(), &options);
+        prop_assert_eq!(union, x.merge(y));
+    }
 }
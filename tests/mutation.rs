@@ -0,0 +1,130 @@
+use ccl_rs::monoid::Monoid;
+use ccl_rs::parser::CCL;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+database =
+    port = 5432
+    hosts =
+        = db1
+        = db2
+debug =
+"#;
+
+    fn config() -> CCL {
+        CCL::parse_str(CONFIG).unwrap()
+    }
+
+    #[test]
+    fn test_insert_path() {
+        let mut ccl = config();
+        ccl.insert_path(&["database", "hosts", "", "db3"]);
+        ccl.insert_path(&["cache", "size", "10"]);
+        // Inserting what is there already changes nothing
+        ccl.insert_path(&["database", "port", "5432"]);
+        assert_eq!(
+            ccl.get_list(&["database", "hosts"]).unwrap(),
+            ["db1", "db2", "db3"]
+        );
+        // The same as parsing the text with the new entries
+        assert_eq!(
+            ccl,
+            config().merge(
+                CCL::parse_str(
+                    "cache = size = 10\ndatabase =\n  hosts =\n    = db3"
+                )
+                .unwrap()
+            )
+        );
+    }
+
+    #[test]
+    fn test_set_path() {
+        let mut ccl = config();
+        ccl.set_path(&["database", "port"], "6543");
+        ccl.set_path(&["debug"], "false");
+        ccl.set_path(&["cache", "size"], "10");
+        insta::assert_snapshot!(ccl.pretty(), @r"
+        cache =
+          size =
+            10 =
+        database =
+          hosts =
+             =
+              db1 =
+              db2 =
+          port =
+            6543 =
+        debug =
+          false =
+        ");
+
+        ccl.set_path(&["database", "hosts"], "");
+        assert_eq!(ccl.get(&["database", "hosts"]), Ok(&CCL::empty()));
+    }
+
+    #[test]
+    fn test_remove_path() {
+        let mut ccl = config();
+        assert_eq!(
+            ccl.remove_path(&["database", "port"]),
+            Some(CCL::key("5432"))
+        );
+        assert_eq!(ccl.remove_path(&["database", "user"]), None);
+        assert_eq!(ccl.remove_path(&["cache", "size"]), None);
+        assert_eq!(ccl.remove_path(&[]), None);
+        // Removing the only value leaves the entry with none
+        ccl.remove_path(&["database", "hosts", ""]);
+        insta::assert_snapshot!(ccl.pretty(), @r"
+        database =
+          hosts =
+        debug =
+        ");
+    }
+
+    #[test]
+    fn test_entry() {
+        let mut ccl = config();
+        ccl.entry(&["cache", "size"]).or_insert(CCL::key("10"));
+        ccl.entry(&["database", "port"]).or_insert(CCL::key("1"));
+        ccl.entry(&["database", "port"])
+            .and_modify(|port| *port = CCL::key("6543"));
+        ccl.entry(&["missing"])
+            .and_modify(|_| panic!("never called"));
+        assert!(!ccl.entry(&["missing"]).exists());
+        ccl.entry(&["flags"]).or_default();
+
+        assert_eq!(ccl.get_str(&["cache", "size"]), Ok("10"));
+        assert_eq!(ccl.get_str(&["database", "port"]), Ok("6543"));
+        assert_eq!(ccl.get(&["flags"]), Ok(&CCL::empty()));
+    }
+
+    #[test]
+    fn test_retain() {
+        let mut ccl = config();
+        let mut visited = Vec::new();
+        ccl.retain(|path, _| {
+            visited.push(path.join("="));
+            path.last().is_none_or(|key| key != "hosts")
+        });
+        insta::assert_snapshot!(ccl.pretty(), @r"
+        database =
+          port =
+            5432 =
+        debug =
+        ");
+        // Nothing under `hosts` is visited once it is removed
+        insta::assert_debug_snapshot!(visited, @r#"
+        [
+            "database",
+            "database=hosts",
+            "database=port",
+            "database=port=5432",
+            "debug",
+        ]
+        "#);
+    }
+}
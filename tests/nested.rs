@@ -1,6 +1,5 @@
 /// Test cases for nested value parsing
 /// Extracted from vendor/ccl/test/test_parser/test_nested.ml
-
 use ccl_rs::key_val::KeyVal;
 use ccl_rs::parser::CCL;
 
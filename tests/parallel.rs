@@ -0,0 +1,78 @@
+mod common;
+
+use ccl_rs::key_val::{KeyVal, KeyVals};
+use ccl_rs::monoid::Monoid;
+use ccl_rs::parser::CCL;
+use common::{key_vals_strat, text_strat};
+use proptest::prelude::*;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_entries() {
+        let config = "a = b\nc =\n  d = e\nkey\n= f\ng = h\n";
+        let chunks = KeyVal::split_entries(config, 100);
+        insta::assert_debug_snapshot!(chunks, @r#"
+        [
+            "a = b\n",
+            "c =\n  d = e\nkey\n= f\n",
+            "g = h\n",
+        ]
+        "#);
+        assert_eq!(chunks.concat(), config);
+    }
+
+    #[test]
+    fn test_split_entries_keeps_first_indent() {
+        let config = "  a = b\n c = d\n  e = f\n";
+        let chunks = KeyVal::split_entries(config, 100);
+        assert_eq!(chunks, vec!["  a = b\n c = d\n", "  e = f\n"]);
+    }
+
+    #[test]
+    fn test_parse_parallel_error_location() {
+        let config = "a = b\nc = d\ne = f\nbroken\n";
+        let err = KeyVal::parse_parallel(config, 4).unwrap_err();
+        assert_eq!(err, KeyVal::parse(config).unwrap_err());
+        insta::assert_snapshot!(err.location(), @"<input>:4:1");
+    }
+}
+
+proptest! {
+    #[test]
+    fn test_parse_parallel(text in text_strat(), threads in 1..8usize) {
+        for chunk in KeyVal::split_entries(&text, threads) {
+            prop_assert!(!chunk.is_empty() || text.is_empty());
+        }
+        prop_assert_eq!(
+            KeyVal::parse_parallel(&text, threads),
+            KeyVal::parse(&text)
+        );
+    }
+
+    #[test]
+    fn test_parse_homomorphism(text in text_strat(), threads in 1..8usize) {
+        let chunks = KeyVal::split_entries(&text, threads);
+        let Ok(parsed) = KeyVal::parse(&text) else {
+            return Ok(());
+        };
+        let per_chunk = chunks
+            .iter()
+            .map(|chunk| KeyVal::parse(chunk).unwrap())
+            .collect::<Vec<KeyVals>>();
+        prop_assert_eq!(KeyVals::aggregate(per_chunk), parsed);
+    }
+
+    #[test]
+    fn test_ccl_parse_parallel(
+        key_vals in key_vals_strat(),
+        threads in 1..8usize
+    ) {
+        prop_assert_eq!(
+            CCL::parse_parallel(key_vals.clone(), threads),
+            CCL::parse(key_vals)
+        );
+    }
+}
@@ -0,0 +1,232 @@
+mod common;
+
+use ccl_rs::error::PatchError;
+use ccl_rs::monoid::Monoid;
+use ccl_rs::parser::CCL;
+use ccl_rs::patch::{Edit, Patch};
+use common::ccl_strategy;
+use proptest::prelude::*;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_and_invert() {
+        let old = r#"
+port = 8080
+debug =
+database =
+    host = localhost
+    pool =
+        size = 10
+"#;
+        let new = r#"
+port = 443
+database =
+    host = localhost
+cache =
+    size = 100
+"#;
+        let old = CCL::parse_str(old).unwrap();
+        let new = CCL::parse_str(new).unwrap();
+        let patch = Patch::from_diff(&old.diff(&new));
+        let mut ccl = old.clone();
+        ccl.apply_patch(&patch).unwrap();
+        assert_eq!(ccl, new);
+        ccl.apply_patch(&patch.invert()).unwrap();
+        assert_eq!(ccl, old);
+    }
+
+    #[test]
+    fn test_to_ccl() {
+        let old = r#"
+port = 8080
+pool =
+    size = 10
+"#;
+        let new = r#"
+port = 443
+cache =
+    size = 100
+"#;
+        let old = CCL::parse_str(old).unwrap();
+        let patch = Patch::from_diff(&old.diff(&CCL::parse_str(new).unwrap()));
+        let text = patch.to_ccl().pretty();
+        insta::assert_snapshot!(text, @r"
+        add =
+          1 =
+            path =
+              cache =
+            value =
+              size =
+                100 =
+          2 =
+            path =
+              port =
+                443 =
+        remove =
+          1 =
+            path =
+              pool =
+            value =
+              size =
+                10 =
+          2 =
+            path =
+              port =
+                8080 =
+        ");
+        let parsed = CCL::parse_str(&text).unwrap();
+        assert_eq!(Patch::from_ccl(&parsed), Ok(patch));
+    }
+
+    #[test]
+    fn test_from_ccl() {
+        let text = r#"
+remove =
+  1 = path = port = 8080
+add =
+  1 = path = port = 443
+  2 =
+    path = cache
+    value =
+      size = 100
+"#;
+        let patch = Patch::from_ccl(&CCL::parse_str(text).unwrap()).unwrap();
+        assert_eq!(patch.remove, [Edit::new(&["port", "8080"], CCL::empty())]);
+        assert_eq!(
+            patch.add,
+            [
+                Edit::new(&["port", "443"], CCL::empty()),
+                Edit::new(&["cache"], CCL::key_val("size", "100")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_invalid_patch() {
+        let invalid = |text: &str| {
+            Patch::from_ccl(&CCL::parse_str(text).unwrap())
+                .unwrap_err()
+                .to_string()
+        };
+        insta::assert_snapshot!(
+            invalid("replace = 1 = path = a"),
+            @"Invalid patch: unknown section `replace`"
+        );
+        insta::assert_snapshot!(
+            invalid("add = 1 = value = a"),
+            @"Invalid patch: no path in add 1"
+        );
+        insta::assert_snapshot!(
+            invalid("add =\n  1 =\n    path = a = b\n    patch = c"),
+            @"Invalid patch: unknown key `patch` in add 1"
+        );
+        insta::assert_snapshot!(
+            invalid("add =\n  1 =\n    path =\n      a = b\n      c = d"),
+            @"Invalid patch: the path of add 1 branches"
+        );
+        insta::assert_snapshot!(
+            invalid("add = 1 = path ="),
+            @"Invalid patch: empty path in add 1"
+        );
+    }
+
+    #[test]
+    fn test_checked() {
+        let old = r#"
+port = 8080
+database =
+    host = localhost
+    pool =
+        size = 10
+"#;
+        let old = CCL::parse_str(old).unwrap();
+        let apply = |patch: &Patch| {
+            let mut ccl = old.clone();
+            let err = ccl.apply_patch(patch).unwrap_err();
+            // Nothing is applied unless everything is
+            assert_eq!(ccl, old);
+            err
+        };
+
+        let patch = Patch {
+            remove: vec![
+                Edit::new(&["port", "8080"], CCL::empty()),
+                Edit::new(&["database", "user"], CCL::empty()),
+            ],
+            add: vec![],
+        };
+        assert_eq!(
+            apply(&patch),
+            PatchError::Missing {
+                path: vec!["database".into(), "user".into()]
+            }
+        );
+        insta::assert_snapshot!(
+            apply(&patch),
+            @"Cannot remove 'user', it is not there (in the value of database)"
+        );
+
+        let patch = Patch {
+            remove: vec![Edit::new(&["database", "pool"], CCL::empty())],
+            add: vec![],
+        };
+        insta::assert_snapshot!(
+            apply(&patch),
+            @"Cannot remove 'pool', it has other values than in the patch (in the value of database)"
+        );
+
+        let patch = Patch {
+            remove: vec![],
+            add: vec![Edit::new(&["database", "host"], CCL::empty())],
+        };
+        insta::assert_snapshot!(
+            apply(&patch),
+            @"Cannot add 'host', it is there already (in the value of database)"
+        );
+
+        let patch = Patch {
+            remove: vec![],
+            add: vec![Edit::new(&["cache", "size"], CCL::key("10"))],
+        };
+        insta::assert_snapshot!(
+            apply(&patch),
+            @"Cannot add 'size', there is no entry to add it to (in the value of cache)"
+        );
+    }
+
+    #[test]
+    fn test_apply_twice() {
+        let old = r#"
+port = 8080
+pool =
+    size = 10
+"#;
+        let mut ccl = CCL::parse_str(old).unwrap();
+        let patch =
+            Patch::from_diff(&ccl.diff(&CCL::parse_str("port = 443").unwrap()));
+        ccl.apply_patch(&patch).unwrap();
+        // The entries to remove are no longer there
+        insta::assert_snapshot!(
+            ccl.apply_patch(&patch).unwrap_err(),
+            @"Cannot remove 'pool', it is not there"
+        );
+    }
+}
+
+proptest! {
+    #[test]
+    fn test_patch_round_trip(old in ccl_strategy(), new in ccl_strategy()) {
+        let patch = Patch::from_diff(&old.diff(&new));
+        let mut ccl = old.clone();
+        ccl.apply_patch(&patch).unwrap();
+        prop_assert_eq!(&ccl, &new);
+        ccl.apply_patch(&patch.invert()).unwrap();
+        prop_assert_eq!(&ccl, &old);
+
+        let written = CCL::parse_str(&patch.to_ccl().pretty()).unwrap();
+        prop_assert_eq!(Patch::from_ccl(&written).unwrap(), patch);
+    }
+}
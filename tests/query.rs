@@ -0,0 +1,139 @@
+use ccl_rs::error::QueryError;
+use ccl_rs::parser::CCL;
+use ccl_rs::query::Query;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+database =
+    main =
+        port = 5432
+        enabled = true
+    cache =
+        port = 6379
+        enabled = false
+    backup_1 =
+        port = 5433
+server =
+    enabled = true
+"#;
+
+    /// The matched paths, joined with `=`
+    fn paths(query: &str) -> Vec<String> {
+        let ccl = CCL::parse_str(CONFIG).unwrap();
+        ccl.query(query)
+            .unwrap()
+            .iter()
+            .map(|m| m.path.join("="))
+            .collect()
+    }
+
+    #[test]
+    fn test_literal() {
+        assert_eq!(paths("database=main=port"), ["database=main=port"]);
+        assert_eq!(paths(" database = main "), ["database=main"]);
+        assert!(paths("database=other").is_empty());
+        assert_eq!(
+            Query::parse("a=b").unwrap().literal_path(),
+            Some(vec!["a", "b"])
+        );
+        assert_eq!(Query::parse("a=*").unwrap().literal_path(), None);
+    }
+
+    #[test]
+    fn test_wildcard() {
+        assert_eq!(
+            paths("database=*=port"),
+            [
+                "database=backup_1=port",
+                "database=cache=port",
+                "database=main=port"
+            ]
+        );
+        assert_eq!(paths("*=*=enabled=true"), ["database=main=enabled=true"]);
+    }
+
+    #[test]
+    fn test_recursive_descent() {
+        assert_eq!(
+            paths("**=enabled"),
+            [
+                "database=cache=enabled",
+                "database=main=enabled",
+                "server=enabled"
+            ]
+        );
+        // `**` also matches no levels at all, and each entry is matched
+        // once however many ways it is reached
+        assert_eq!(paths("**=server"), ["server"]);
+        assert_eq!(paths("**=**=server"), ["server"]);
+    }
+
+    #[test]
+    fn test_patterns() {
+        assert_eq!(
+            paths("database=ma?n"),
+            ["database=main"],
+            "single-character glob"
+        );
+        assert_eq!(paths("database=backup_*"), ["database=backup_1"], "glob");
+        assert_eq!(
+            paths("database=/ma.*|cache/"),
+            ["database=cache", "database=main"],
+            "regex"
+        );
+        // Regexes match the whole key
+        assert!(paths("database=/ai/").is_empty());
+    }
+
+    #[test]
+    fn test_escaping() {
+        let ccl = CCL::parse_str("* =\n  x =\n\"q\" = y\ns p = z").unwrap();
+        let values = |query: &str| {
+            ccl.query(query)
+                .unwrap()
+                .into_iter()
+                .map(|m| m.to_ccl().pretty())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(values(r"\*"), ["* =\n  x =\n"]);
+        assert_eq!(values(r#""\"q\"""#), ["\"q\" =\n  y =\n"]);
+        assert_eq!(values("s p"), ["s p =\n  z =\n"]);
+
+        // Parsed keys never have an `=`, but built ones can
+        let ccl = CCL::key_val("a=b", "c");
+        assert_eq!(ccl.query(r"a\=b=c").unwrap()[0].path, ["a=b", "c"]);
+        assert_eq!(ccl.query(r#""a=b"=c"#).unwrap()[0].path, ["a=b", "c"]);
+    }
+
+    #[test]
+    fn test_errors() {
+        let err = Query::parse(r#"a="b"#).unwrap_err();
+        assert_eq!(
+            err,
+            QueryError::Syntax {
+                position: 2,
+                message: "unclosed quote".into()
+            }
+        );
+        insta::assert_snapshot!(
+            Query::parse(r"a\").unwrap_err(),
+            @"Invalid query at byte 1: trailing backslash"
+        );
+        insta::assert_snapshot!(
+            Query::parse(r#""a"b=c"#).unwrap_err(),
+            @"Invalid query at byte 3: text after closing quote"
+        );
+        insta::assert_snapshot!(
+            Query::parse("a=/(/").unwrap_err(),
+            @r"
+        Invalid regex /(/: regex parse error:
+            (
+            ^
+        error: unclosed group
+        "
+        );
+    }
+}
@@ -0,0 +1,145 @@
+mod common;
+
+use ccl_rs::diagnostic::{Diagnostic, Severity};
+use ccl_rs::key_val::KeyVal;
+use ccl_rs::options::ParseOptions;
+use ccl_rs::parser::CCL;
+use ccl_rs::span::Span;
+use common::raw_text_strat;
+use proptest::prelude::*;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn messages(diagnostics: &[Diagnostic]) -> Vec<String> {
+        diagnostics
+            .iter()
+            .map(|diagnostic| {
+                format!("{}: {}", diagnostic.location(), diagnostic)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_partial_key_vals() {
+        let config = "a = b\nc = d\nbroken\n";
+        let (key_vals, diagnostics) =
+            KeyVal::parse_recovering(config, &ParseOptions::default());
+        assert_eq!(key_vals, KeyVal::parse("a = b\nc = d").unwrap());
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+        assert_eq!(diagnostics[0].error, KeyVal::parse(config).unwrap_err());
+    }
+
+    #[test]
+    fn test_nested_errors_are_reported() {
+        let config = r#"
+database =
+  host = localhost
+  port
+server =
+  limits =
+    cpu = 1
+    memory
+  name = api
+description =
+  A plain multi-line
+  text value
+"#;
+        let (ccl, diagnostics) =
+            CCL::parse_recovering(config, &ParseOptions::default());
+        insta::assert_debug_snapshot!(messages(&diagnostics), @r#"
+        [
+            "<input>:4:3: warning: No value found for key: port (in the value of database)",
+            "<input>:8:5: warning: No value found for key: memory (in the value of server=limits)",
+        ]
+        "#);
+        assert_eq!(diagnostics[0].span(), Span::new(33, 37));
+        assert!(diagnostics.iter().all(|d| !d.is_error()));
+
+        // Same result as the lenient parser
+        assert_eq!(ccl, CCL::parse(KeyVal::parse(config).unwrap()));
+    }
+
+    #[test]
+    fn test_errors_inside_broken_values() {
+        // The value of `a` is broken, so it is a leaf and the broken value
+        // of `b` in it is not reported on its own
+        let config = "a =\n  b =\n    c = 1\n    d\n  e\n";
+        let (ccl, diagnostics) =
+            CCL::parse_recovering(config, &ParseOptions::default());
+        insta::assert_debug_snapshot!(messages(&diagnostics), @r#"
+        [
+            "<input>:5:3: warning: No value found for key: e (in the value of a)",
+        ]
+        "#);
+        assert_eq!(ccl, CCL::parse(KeyVal::parse(config).unwrap()));
+    }
+
+    #[test]
+    fn test_all_indentation_errors() {
+        let config = "a =\n\tb = 1\n  c = 2\nd =\n \te = 3\n";
+        let (key_vals, diagnostics) =
+            KeyVal::parse_recovering(config, &ParseOptions::strict());
+        assert_eq!(key_vals.len(), 2);
+        insta::assert_debug_snapshot!(messages(&diagnostics), @r#"
+        [
+            "<input>:3:1: Bad indentation: indented with spaces, but earlier lines use tabs",
+            "<input>:5:1: Bad indentation: mixed tabs and spaces",
+        ]
+        "#);
+    }
+
+    #[test]
+    fn test_dedent_errors_resynchronise() {
+        let config = "a =\n    b = 1\n  c = 2\n  d = 3\n e = 4\n";
+        let (_, diagnostics) =
+            KeyVal::parse_recovering(config, &ParseOptions::strict());
+        insta::assert_debug_snapshot!(messages(&diagnostics), @r#"
+        [
+            "<input>:3:1: Bad indentation: dedent does not match any outer indentation level",
+            "<input>:5:1: Bad indentation: dedent does not match any outer indentation level",
+        ]
+        "#);
+    }
+
+    #[test]
+    fn test_errors_and_warnings_together() {
+        let config = "a =\n  b = c\n  d\ne\n";
+        let (ccl, diagnostics) =
+            CCL::parse_recovering(config, &ParseOptions::default());
+        insta::assert_debug_snapshot!(messages(&diagnostics), @r#"
+        [
+            "<input>:4:1: No value found for key: e",
+            "<input>:3:3: warning: No value found for key: d (in the value of a)",
+        ]
+        "#);
+        assert_eq!(
+            ccl,
+            CCL::parse(KeyVal::parse("a =\n  b = c\n  d").unwrap())
+        );
+    }
+}
+
+proptest! {
+    #[test]
+    fn test_recovering_agrees_with_lenient(text in raw_text_strat()) {
+        let options = ParseOptions::default();
+        let (key_vals, diagnostics) =
+            KeyVal::parse_recovering(&text, &options);
+        let (ccl, tree_diagnostics) = CCL::parse_recovering(&text, &options);
+        match KeyVal::parse(&text) {
+            Ok(parsed) => {
+                prop_assert_eq!(&key_vals, &parsed);
+                prop_assert!(diagnostics.is_empty());
+                prop_assert!(tree_diagnostics.iter().all(|d| !d.is_error()));
+                prop_assert_eq!(ccl, CCL::parse(parsed));
+            }
+            Err(err) => {
+                prop_assert_eq!(&diagnostics, &vec![Diagnostic::error(err)]);
+                prop_assert_eq!(ccl, CCL::parse(key_vals));
+            }
+        }
+    }
+}
@@ -0,0 +1,285 @@
+use ccl_rs::error::SchemaError;
+use ccl_rs::parser::CCL;
+use ccl_rs::schema::{Count, Schema, ViolationKind};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schema_error(schema: &str) -> SchemaError {
+        Schema::from_ccl(&CCL::parse_str(schema).unwrap()).unwrap_err()
+    }
+
+    #[test]
+    fn test_valid() {
+        let schema = r#"
+fields =
+  name =
+    type = regex
+    pattern = [a-z][a-z0-9-]*
+    required = true
+  port =
+    type = int
+    required = true
+  debug =
+    type = bool
+  mode =
+    type = enum
+    values =
+      = ro
+      = rw
+  hosts =
+    count = 1..3
+  database =
+    required = true
+    fields =
+      user =
+        required = true
+      pool =
+        type = int
+  labels =
+    open = true
+  replicas =
+    entries =
+      fields =
+        weight =
+          type = int
+"#;
+        let config = r#"
+name = billing
+port = 8080
+debug =
+mode = rw
+hosts =
+  = db1
+  = db2
+database =
+  user = admin
+labels =
+  team = payments
+  tier =
+replicas =
+  eu =
+    weight = 2
+  us =
+/= comments are not checked
+"#;
+        let schema =
+            Schema::from_ccl(&CCL::parse_str(schema).unwrap()).unwrap();
+        let config = CCL::parse_str(config).unwrap();
+        assert!(schema.validate(&config).is_empty());
+    }
+
+    #[test]
+    fn test_violations() {
+        let schema = r#"
+fields =
+  name =
+    type = regex
+    pattern = [a-z][a-z0-9-]*
+    required = true
+  port =
+    type = int
+    required = true
+  debug =
+    type = bool
+  mode =
+    type = enum
+    values =
+      = ro
+      = rw
+  hosts =
+    count = 1..3
+  database =
+    required = true
+    fields =
+      user =
+        required = true
+      pool =
+        type = int
+  labels =
+    open = true
+  replicas =
+    entries =
+      fields =
+        weight =
+          type = int
+"#;
+        let config = r#"
+name = Billing
+port = http
+debug = maybe
+mode = append
+hosts = a
+hosts = b
+hosts = c
+hosts = d
+database =
+  usr = admin
+  pool =
+    size = 10
+replicas =
+  eu =
+    weight = heavy
+    zone = west
+debgu =
+"#;
+        let schema =
+            Schema::from_ccl(&CCL::parse_str(schema).unwrap()).unwrap();
+        let violations = |config: &str| {
+            schema
+                .validate(&CCL::parse_str(config).unwrap())
+                .iter()
+                .map(|violation| violation.to_string())
+                .collect::<Vec<_>>()
+        };
+        insta::assert_debug_snapshot!(violations(config), @r#"
+        [
+            "Expected a plain value of 'pool', found entries under 'size' (in the value of database)",
+            "Missing required key 'user' (in the value of database)",
+            "Unknown key 'usr' (in the value of database)",
+            "Invalid value 'maybe' of 'debug': expected true or false",
+            "Expected 1 to 3 values of 'hosts', found 4: a, b, c, d",
+            "Invalid value 'append' of 'mode': expected one of: ro, rw",
+            "Invalid value 'Billing' of 'name': expected a match for /[a-z][a-z0-9-]*/",
+            "Invalid value 'http' of 'port': expected an int",
+            "Invalid value 'heavy' of 'weight': expected an int (in the value of replicas=eu)",
+            "Unknown key 'zone' (in the value of replicas=eu)",
+            "Unknown key 'debgu'",
+        ]
+        "#);
+        insta::assert_debug_snapshot!(violations(""), @r#"
+        [
+            "Missing required key 'database'",
+            "Missing required key 'name'",
+            "Missing required key 'port'",
+        ]
+        "#);
+    }
+
+    #[test]
+    fn test_violation_paths() {
+        let schema = r#"
+fields =
+  database =
+    fields =
+      user =
+"#;
+        let schema =
+            Schema::from_ccl(&CCL::parse_str(schema).unwrap()).unwrap();
+        let config = CCL::parse_str("database =\n  user =\n    a = b").unwrap();
+        let violations = schema.validate(&config);
+        let found = violations
+            .iter()
+            .map(|violation| (violation.path.join("="), &violation.kind))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            found,
+            [(
+                "database=user".to_string(),
+                &ViolationKind::Nested {
+                    value: "a".to_string()
+                }
+            )]
+        );
+    }
+
+    #[test]
+    fn test_counts() {
+        assert_eq!("2".parse(), Ok(Count::exactly(2)));
+        assert_eq!(
+            "0..2".parse(),
+            Ok(Count {
+                min: 0,
+                max: Some(2)
+            })
+        );
+        assert_eq!("1..".parse(), Ok(Count { min: 1, max: None }));
+        assert!("2..1".parse::<Count>().is_err());
+        assert!("many".parse::<Count>().is_err());
+
+        let schema = "fields =\n  a =\n    count = 0..\n  b =\n    count = 2";
+        let schema = Schema::from_ccl(&CCL::parse_str(schema).unwrap());
+        let config = CCL::parse_str("a =\nb = x").unwrap();
+        let violations = schema.unwrap().validate(&config);
+        insta::assert_snapshot!(violations[0], @"Expected 2 values of 'b', found 1: x");
+        assert_eq!(violations.len(), 1);
+    }
+
+    #[test]
+    fn test_open_root() {
+        let schema = "open = true\nfields =\n  port =\n    type = int";
+        let schema =
+            Schema::from_ccl(&CCL::parse_str(schema).unwrap()).unwrap();
+        let config = CCL::parse_str("port = 1\nanything = else").unwrap();
+        assert!(schema.validate(&config).is_empty());
+    }
+
+    #[test]
+    fn test_invalid_schema() {
+        insta::assert_snapshot!(
+            schema_error("fields =\n  a =\n    type = float"),
+            @"Invalid schema: unknown type `float`, expected one of: string, int, bool, enum, regex, section (in the value of fields=a)"
+        );
+        insta::assert_snapshot!(
+            schema_error("fields =\n  a =\n    type = int\n    values = x"),
+            @"Invalid schema: unknown key `values`, expected one of: type, required, count (in the value of fields=a)"
+        );
+        insta::assert_snapshot!(
+            schema_error("feilds ="),
+            @"Invalid schema: unknown key `feilds`, expected one of: fields, entries, open"
+        );
+        insta::assert_snapshot!(
+            schema_error("fields =\n  a =\n    type = enum"),
+            @"Invalid schema: an enum needs `values` (in the value of fields=a)"
+        );
+        insta::assert_snapshot!(
+            schema_error("fields =\n  a =\n    required = yes"),
+            @"Invalid schema: expected true or false for `required`, found `yes` (in the value of fields=a)"
+        );
+        insta::assert_snapshot!(
+            schema_error("fields =\n  a =\n    count = 1..x"),
+            @"Invalid schema: invalid count `1..x`, expected N, N..M or N.. (in the value of fields=a)"
+        );
+        assert!(matches!(
+            schema_error("fields =\n  a =\n    type = regex\n    pattern = ("),
+            SchemaError::InvalidRegex { .. }
+        ));
+    }
+
+    #[test]
+    fn test_locations() {
+        let schema = r#"
+fields =
+  name =
+  port =
+    type = int
+  database =
+    fields =
+      user =
+        required = true
+      pool =
+        type = int
+"#;
+        let schema =
+            Schema::from_ccl(&CCL::parse_str(schema).unwrap()).unwrap();
+        let config =
+            "name = ok\nport =\n  = 80\n  = x\ndatabase =\n  pool = big\n";
+        let violations = schema.validate_str(config).unwrap();
+        let located = violations
+            .iter()
+            .map(|violation| {
+                let location = violation.location.as_ref().unwrap();
+                format!("{}:{} {}", location.line, location.column, violation)
+            })
+            .collect::<Vec<_>>();
+        insta::assert_debug_snapshot!(located, @r#"
+        [
+            "2:1 Expected 1 value of 'port', found 2: 80, x",
+            "4:5 Invalid value 'x' of 'port': expected an int",
+            "5:1 Missing required key 'user' (in the value of database)",
+            "6:10 Invalid value 'big' of 'pool': expected an int (in the value of database)",
+        ]
+        "#);
+    }
+}
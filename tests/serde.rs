@@ -0,0 +1,343 @@
+#![cfg(feature = "serde")]
+
+use ccl_rs::de::{Error, from_ccl, from_str};
+use ccl_rs::key_val::KeyVal;
+use ccl_rs::parser::CCL;
+use ccl_rs::to_string;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    struct Config {
+        title: String,
+        database: Database,
+        user: BTreeMap<String, String>,
+        debug: bool,
+        verbose: bool,
+        timeout: Option<u32>,
+        retries: Option<u32>,
+        proxy: Option<String>,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    struct Database {
+        enabled: bool,
+        ports: Vec<u16>,
+        limits: Limits,
+        mode: Mode,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    struct Limits {
+        cpu: String,
+        memory: String,
+        ratio: f64,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    #[serde(rename_all = "lowercase")]
+    enum Mode {
+        Primary,
+        Replica,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    enum Backend {
+        Memory,
+        File { path: String },
+        Remote(String),
+        Pair(u8, String),
+    }
+
+    const CONFIG: &str = r#"
+title = CCL Example
+
+database =
+    enabled = true
+    ports =
+        = 8000
+        = 8001
+        = 8002
+    limits =
+        cpu = 1500mi
+        memory = 10Gb
+        ratio = 0.5
+    mode = replica
+
+user =
+    guestId = 42
+
+user =
+    login = chshersh
+    createdAt = 2024-12-31
+
+debug =
+verbose = false
+timeout = 30
+proxy =
+"#;
+
+    #[test]
+    fn test_from_str() {
+        let config: Config = from_str(CONFIG).unwrap();
+        insta::assert_debug_snapshot!(config, @r#"
+        Config {
+            title: "CCL Example",
+            database: Database {
+                enabled: true,
+                ports: [
+                    8000,
+                    8001,
+                    8002,
+                ],
+                limits: Limits {
+                    cpu: "1500mi",
+                    memory: "10Gb",
+                    ratio: 0.5,
+                },
+                mode: Replica,
+            },
+            user: {
+                "createdAt": "2024-12-31",
+                "guestId": "42",
+                "login": "chshersh",
+            },
+            debug: true,
+            verbose: false,
+            timeout: Some(
+                30,
+            ),
+            retries: None,
+            proxy: None,
+        }
+        "#);
+    }
+
+    #[test]
+    fn test_from_ccl() {
+        let ccl = CCL::parse(KeyVal::parse(CONFIG).unwrap());
+        let config: Config = from_ccl(&ccl).unwrap();
+        assert_eq!(config, from_str(CONFIG).unwrap());
+    }
+
+    #[test]
+    fn test_borrowed_str() {
+        #[derive(Deserialize)]
+        struct Borrowed<'a> {
+            name: &'a str,
+        }
+        let ccl = CCL::parse_str("name = api").unwrap();
+        let borrowed: Borrowed = from_ccl(&ccl).unwrap();
+        assert_eq!(borrowed.name, "api");
+    }
+
+    #[test]
+    fn test_sequences() {
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Lists {
+            items: Vec<String>,
+            repeated: Vec<u8>,
+            single: Vec<u8>,
+            empty: Vec<u8>,
+            pair: (u8, String),
+        }
+        let lists: Lists = from_str(
+            "items =\n  = b\n  = a\nrepeated = 2\nrepeated = 1\n\
+             single = 7\nempty =\npair =\n  = 1\n  = x\n",
+        )
+        .unwrap();
+        // Items come out sorted
+        assert_eq!(
+            lists,
+            Lists {
+                items: vec!["a".into(), "b".into()],
+                repeated: vec![1, 2],
+                single: vec![7],
+                empty: vec![],
+                pair: (1, "x".into()),
+            }
+        );
+    }
+
+    #[test]
+    fn test_enum_variants() {
+        let backends: BTreeMap<String, Backend> = from_str(
+            "a = Memory\nb =\n  File =\n    path = /tmp/x\nc = Remote = url\n",
+        )
+        .unwrap();
+        assert_eq!(
+            backends,
+            BTreeMap::from([
+                ("a".into(), Backend::Memory),
+                (
+                    "b".into(),
+                    Backend::File {
+                        path: "/tmp/x".into()
+                    }
+                ),
+                ("c".into(), Backend::Remote("url".into())),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_error_path() {
+        let err =
+            from_str::<Config>(&CONFIG.replace("ratio = 0.5", "ratio = x"))
+                .unwrap_err();
+        insta::assert_snapshot!(
+            err,
+            @"invalid value `x`: invalid float literal (in the value of database=limits=ratio)"
+        );
+        let Error::Custom { path, .. } = err else {
+            panic!("expected a custom error");
+        };
+        assert_eq!(path, ["database", "limits", "ratio"]);
+    }
+
+    #[test]
+    fn test_missing_field_path() {
+        let err = from_str::<Config>(&CONFIG.replace("memory = 10Gb", ""))
+            .unwrap_err();
+        insta::assert_snapshot!(
+            err,
+            @"missing field `memory` (in the value of database=limits)"
+        );
+    }
+
+    #[test]
+    fn test_not_a_scalar() {
+        let err = from_str::<BTreeMap<String, String>>("a =\n  b = c\n  d = e")
+            .unwrap_err();
+        insta::assert_snapshot!(
+            err,
+            @"expected a single value, found 2 keys (in the value of a)"
+        );
+    }
+
+    #[test]
+    fn test_parse_error() {
+        let err = from_str::<Config>("a = b\nbroken").unwrap_err();
+        assert!(matches!(err, Error::Parse(_)));
+        insta::assert_snapshot!(err, @"No value found for key: broken");
+    }
+
+    #[test]
+    fn test_to_string() {
+        let config: Config = from_str(CONFIG).unwrap();
+        insta::assert_snapshot!(to_string(&config).unwrap(), @"
+        title = CCL Example
+        database =
+          enabled = true
+          ports =
+            = 8000
+            = 8001
+            = 8002
+          limits =
+            cpu = 1500mi
+            memory = 10Gb
+            ratio = 0.5
+          mode = replica
+        user =
+          createdAt = 2024-12-31
+          guestId = 42
+          login = chshersh
+        debug = true
+        verbose = false
+        timeout = 30
+        ");
+    }
+
+    #[test]
+    fn test_to_string_roundtrip() {
+        let config: Config = from_str(CONFIG).unwrap();
+        let text = to_string(&config).unwrap();
+        assert_eq!(from_str::<Config>(&text).unwrap(), config);
+
+        let backends = BTreeMap::from([
+            ("a".to_string(), Backend::Memory),
+            (
+                "b".to_string(),
+                Backend::File {
+                    path: "/tmp/x".into(),
+                },
+            ),
+            ("c".to_string(), Backend::Remote("url".into())),
+            ("d".to_string(), Backend::Pair(1, "x".into())),
+        ]);
+        let text = to_string(&backends).unwrap();
+        insta::assert_snapshot!(text, @"
+        a = Memory
+        b =
+          File =
+            path = /tmp/x
+        c =
+          Remote = url
+        d =
+          Pair =
+            = 1
+            = x
+        ");
+        assert_eq!(
+            from_str::<BTreeMap<String, Backend>>(&text).unwrap(),
+            backends
+        );
+    }
+
+    #[test]
+    fn test_to_string_sequences() {
+        let items = vec![Backend::Memory, Backend::Remote("url".into())];
+        let text = to_string(&items).unwrap();
+        insta::assert_snapshot!(text, @"
+        = Memory
+        =
+          Remote = url
+        ");
+        assert_eq!(from_str::<Vec<Backend>>(&text).unwrap(), items);
+    }
+
+    #[test]
+    fn test_to_string_errors() {
+        let err = to_string(&BTreeMap::from([("a", "b = c")])).unwrap_err();
+        insta::assert_snapshot!(err, @"cannot write `b = c` as a CCL value: it contains `=` or a line break (in the value of a)");
+
+        let err =
+            to_string(&BTreeMap::from([("a", vec![(1, 2)])])).unwrap_err();
+        insta::assert_snapshot!(err, @"cannot write a sequence item with 2 entries, only single values (in the value of a)");
+
+        // A bare `=` item would read back as no item at all
+        let err = to_string(&BTreeMap::from([("a", vec![Some(1), None])]))
+            .unwrap_err();
+        insta::assert_snapshot!(
+            err,
+            @"cannot write an absent sequence item (in the value of a)"
+        );
+
+        let err =
+            to_string(&BTreeMap::from([("a", vec!["", "b"])])).unwrap_err();
+        insta::assert_snapshot!(
+            err,
+            @"cannot write an empty sequence item (in the value of a)"
+        );
+
+        // A bare `key =` would read back as `None`
+        let err = to_string(&BTreeMap::from([("a", Some(""))])).unwrap_err();
+        insta::assert_snapshot!(
+            err,
+            @"cannot write `Some` of an empty value (in the value of a)"
+        );
+        let err = to_string(&BTreeMap::from([("a", Some(Vec::<u8>::new()))]))
+            .unwrap_err();
+        insta::assert_snapshot!(
+            err,
+            @"cannot write `Some` of an empty value (in the value of a)"
+        );
+
+        let err = to_string("value").unwrap_err();
+        insta::assert_snapshot!(err, @"cannot write the single value `value` as CCL, only entries");
+    }
+}
@@ -0,0 +1,197 @@
+mod common;
+
+use ccl_rs::comment::CommentMode;
+use ccl_rs::key_val::{KeyVal, KeyValNode, KeyValSpanTree, KeyValTree};
+use ccl_rs::options::ParseOptions;
+use ccl_rs::parser::CCL;
+use ccl_rs::span::{LineRange, Span};
+use common::text_strat;
+use proptest::prelude::*;
+
+/// What a key or value looks like once parsed from its source text
+fn normalize(text: &str) -> String {
+    text.lines()
+        .map(|line| line.trim_end())
+        .collect::<Vec<&str>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+title = CCL Example
+
+database =
+    enabled = true
+    ports =
+        = 8000
+        = 8001
+    limits =
+        cpu = 1500mi
+
+user =
+  login = chshersh
+"#;
+
+    #[test]
+    fn test_key_val_spans() {
+        let key_vals = KeyVal::parse_spanned(CONFIG).unwrap();
+        let spans = key_vals
+            .iter()
+            .map(|(_, span)| {
+                (
+                    &CONFIG[span.key.start..span.key.end],
+                    span.key_lines.start,
+                    span.value_lines.end,
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            spans,
+            vec![("title", 2, 2), ("database", 4, 10), ("user", 12, 13)]
+        );
+    }
+
+    #[test]
+    fn test_multiline_key_span() {
+        let config = "a = b\nkey1\n  key2  \n= val";
+        let key_vals = KeyVal::parse_spanned(config).unwrap();
+        let (key_val, span) = &key_vals[1];
+        assert_eq!(key_val.key, "key1\n  key2");
+        assert_eq!(span.key, Span::new(6, 17));
+        assert_eq!(span.key_lines, LineRange { start: 2, end: 3 });
+        assert_eq!(span.value, Span::new(22, 25));
+        assert_eq!(span.value_lines, LineRange { start: 4, end: 4 });
+    }
+
+    #[test]
+    fn test_empty_value_span() {
+        let key_vals = KeyVal::parse_spanned("a =  \nb = c").unwrap();
+        let (_, span) = &key_vals[0];
+        assert_eq!(span.value, Span::new(5, 5));
+        assert_eq!(span.value_lines, LineRange { start: 1, end: 1 });
+    }
+
+    #[test]
+    fn test_nested_spans_are_rebased() {
+        let (ccl, source_map) = CCL::parse_spanned(CONFIG).unwrap();
+        assert_eq!(ccl, CCL::parse(KeyVal::parse(CONFIG).unwrap()));
+
+        let cpu = source_map.get(&["database", "limits", "cpu"]);
+        assert_eq!(cpu.len(), 1);
+        assert_eq!(&CONFIG[cpu[0].key.start..cpu[0].key.end], "cpu");
+        assert_eq!(cpu[0].key_lines, LineRange { start: 10, end: 10 });
+
+        let value = source_map.get(&["database", "limits", "cpu", "1500mi"]);
+        assert_eq!(&CONFIG[value[0].value.start..value[0].value.end], "1500mi");
+
+        let ports = source_map.get(&["database", "ports", ""]);
+        let lines = ports
+            .iter()
+            .map(|span| span.lines().start)
+            .collect::<Vec<_>>();
+        assert_eq!(lines, vec![7, 8]);
+
+        assert!(source_map.get(&["database", "missing"]).is_empty());
+    }
+
+    #[test]
+    fn test_spans_follow_options() {
+        let config = "/= comment\na =\n  /= nested comment\n  b = c\n";
+        let options = ParseOptions {
+            comments: CommentMode::Strip,
+            ..ParseOptions::default()
+        };
+        let (ccl, source_map) =
+            CCL::parse_spanned_with(config, &options).unwrap();
+        assert_eq!(ccl, CCL::parse_str("a =\n  b = c").unwrap());
+        assert!(source_map.get(&["/"]).is_empty());
+        assert!(source_map.get(&["a", "/"]).is_empty());
+        let b = source_map.get(&["a", "b"]);
+        assert_eq!(&config[b[0].key.start..b[0].key.end], "b");
+        assert_eq!(b[0].key_lines, LineRange { start: 4, end: 4 });
+
+        let err = CCL::parse_spanned_with(
+            "a =\n\tb = c",
+            &ParseOptions {
+                reject_tabs: true,
+                ..ParseOptions::default()
+            },
+        );
+        assert!(err.is_err());
+    }
+}
+
+/// Check that `spans` has the shape of `tree`, each span covering the key
+/// and the value of its node
+fn check_spans(
+    text: &str,
+    tree: &KeyValTree,
+    spans: &KeyValSpanTree,
+) -> Result<(), TestCaseError> {
+    prop_assert_eq!(
+        tree.keys().collect::<Vec<_>>(),
+        spans.keys().collect::<Vec<_>>()
+    );
+    for (key, nodes) in tree {
+        prop_assert_eq!(nodes.len(), spans[key].len());
+        for (node, span) in nodes.iter().zip(&spans[key]) {
+            let entry = span.span;
+            prop_assert_eq!(
+                &normalize(&text[entry.key.start..entry.key.end]),
+                key
+            );
+            match node {
+                KeyValNode::Leaf(value) => {
+                    prop_assert!(span.children.is_empty());
+                    prop_assert_eq!(
+                        &normalize(&text[entry.value.start..entry.value.end]),
+                        value
+                    );
+                }
+                KeyValNode::Tree(tree) => {
+                    check_spans(text, tree, &span.children)?
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+proptest! {
+    #[test]
+    fn test_tree_spans_match_tree(text in text_strat()) {
+        let Ok((tree, spans)) = KeyVal::parse_tree_spanned(&text) else {
+            return Ok(());
+        };
+        prop_assert_eq!(Ok(tree.clone()), KeyVal::parse_tree(&text));
+        check_spans(&text, &tree, &spans)?;
+    }
+
+    #[test]
+    fn test_spans_cover_parsed_text(text in text_strat()) {
+        let Ok(spanned) = KeyVal::parse_spanned(&text) else {
+            return Ok(());
+        };
+        for (key_val, span) in &spanned {
+            prop_assert_eq!(
+                &normalize(&text[span.key.start..span.key.end]),
+                &key_val.key
+            );
+            prop_assert_eq!(
+                &normalize(&text[span.value.start..span.value.end]),
+                &key_val.value
+            );
+        }
+
+        let key_vals = KeyVal::parse(&text).unwrap();
+        prop_assert_eq!(
+            spanned.iter().map(|(kv, _)| kv.clone()).collect::<Vec<_>>(),
+            key_vals.clone()
+        );
+        let (ccl, _) = CCL::parse_spanned(&text).unwrap();
+        prop_assert_eq!(ccl, CCL::parse(key_vals));
+    }
+}
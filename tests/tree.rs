@@ -0,0 +1,140 @@
+mod common;
+
+use ccl_rs::comment::CommentMode;
+use ccl_rs::key_val::KeyVal;
+use ccl_rs::options::ParseOptions;
+use ccl_rs::parser::CCL;
+use common::reparsing::parse_flat_to_tree_reparsing;
+use common::{key_vals_strat, raw_text_strat};
+use proptest::prelude::*;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_tree() {
+        let config = r#"
+/= This is a CCL document
+title = CCL Example
+
+database =
+    enabled = true
+    ports =
+        = 8000
+        = 8001
+    limits =
+        cpu = 1500mi
+        memory = 10Gb
+    description =
+        Free text
+        over two lines
+
+user = login = chshersh
+"#;
+        let key_vals = KeyVal::parse(config).unwrap();
+        assert_eq!(
+            KeyVal::parse_tree(config).unwrap(),
+            KeyVal::parse_flat_to_tree(&key_vals)
+        );
+        assert_eq!(CCL::parse_str(config).unwrap(), CCL::parse(key_vals));
+    }
+
+    #[test]
+    fn test_parse_tree_error() {
+        let config = "a =\n  b\nc = d\nbroken\n";
+        assert_eq!(
+            KeyVal::parse_tree(config).unwrap_err(),
+            KeyVal::parse(config).unwrap_err()
+        );
+    }
+
+    #[test]
+    fn test_parse_tree_deep_nesting() {
+        let depth = 200;
+        let config = (0..depth)
+            .map(|i| format!("{}k{} =\n", " ".repeat(i), i))
+            .collect::<String>();
+        let ccl = CCL::parse_str(&config).unwrap();
+        let mut ccl = &ccl;
+        for i in 0..depth {
+            let CCL(map) = ccl;
+            ccl = &map[&format!("k{}", i)];
+        }
+        assert_eq!(ccl, &CCL(Default::default()));
+    }
+}
+
+fn options_strat() -> impl Strategy<Value = ParseOptions> {
+    (1..5usize, any::<bool>(), any::<bool>(), any::<bool>()).prop_map(
+        |(tab_width, reject_tabs, strict_indentation, strip)| ParseOptions {
+            tab_width,
+            reject_tabs,
+            strict_indentation,
+            comments: if strip {
+                CommentMode::Strip
+            } else {
+                CommentMode::Keep
+            },
+            max_depth: None,
+        },
+    )
+}
+
+proptest! {
+    #[test]
+    fn test_parse_tree_equivalence(text in raw_text_strat()) {
+        let pipeline = KeyVal::parse(&text)
+            .map(|key_vals| KeyVal::parse_flat_to_tree(&key_vals));
+        prop_assert_eq!(KeyVal::parse_tree(&text), pipeline);
+    }
+
+    #[test]
+    fn test_parse_tree_with_equivalence(
+        text in raw_text_strat(),
+        options in options_strat()
+    ) {
+        let pipeline = KeyVal::parse_with(&text, &options).map(|key_vals| {
+            parse_flat_to_tree_reparsing(&key_vals, &options)
+        });
+        prop_assert_eq!(KeyVal::parse_tree_with(&text, &options), pipeline);
+    }
+
+    #[test]
+    fn test_flat_to_tree_equivalence(
+        text in raw_text_strat(),
+        options in options_strat()
+    ) {
+        let Ok(key_vals) = KeyVal::parse(&text) else {
+            return Ok(());
+        };
+        prop_assert_eq!(
+            KeyVal::parse_flat_to_tree_with(&key_vals, &options),
+            parse_flat_to_tree_reparsing(&key_vals, &options)
+        );
+    }
+
+    #[test]
+    fn test_parse_str_equivalence(key_vals in key_vals_strat()) {
+        let text = CCL::parse(key_vals).pretty();
+        prop_assert_eq!(
+            CCL::parse_str(&text),
+            KeyVal::parse(&text).map(CCL::parse)
+        );
+    }
+
+    #[test]
+    fn test_try_parse_tree_agrees_with_lenient(text in raw_text_strat()) {
+        let lenient = KeyVal::parse_tree(&text);
+        let strict = KeyVal::try_parse_tree(&text);
+        // Strict parsing only adds errors
+        prop_assert!(strict.is_err() || strict == lenient);
+        prop_assert!(lenient.is_ok() || strict.is_err());
+        if let Ok(key_vals) = KeyVal::parse(&text) {
+            prop_assert_eq!(
+                KeyVal::try_parse_flat_to_tree(&key_vals).is_ok(),
+                KeyVal::try_parse_tree(&text).is_ok()
+            );
+        }
+    }
+}
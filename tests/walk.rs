@@ -0,0 +1,159 @@
+use ccl_rs::parser::CCL;
+use ccl_rs::walk::Visitor;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+database =
+    port = 5432
+    hosts =
+        = db1
+        = db2
+debug =
+"#;
+
+    fn config() -> CCL {
+        CCL::parse_str(CONFIG).unwrap()
+    }
+
+    #[test]
+    fn test_iter_paths() {
+        let ccl = config();
+        let paths = ccl
+            .iter_paths()
+            .map(|(path, _)| path.join("="))
+            .collect::<Vec<_>>();
+        insta::assert_debug_snapshot!(paths, @r#"
+        [
+            "database",
+            "database=hosts",
+            "database=hosts=",
+            "database=hosts==db1",
+            "database=hosts==db2",
+            "database=port",
+            "database=port=5432",
+            "debug",
+        ]
+        "#);
+        let (path, hosts) = ccl.iter_paths().nth(1).unwrap();
+        assert_eq!(path, ["database", "hosts"]);
+        assert_eq!(hosts, ccl.get(&["database", "hosts"]).unwrap());
+    }
+
+    #[test]
+    fn test_leaves() {
+        let ccl = config();
+        let leaves = ccl.leaves().collect::<Vec<_>>();
+        assert_eq!(
+            leaves,
+            [
+                vec!["database", "hosts", "", "db1"],
+                vec!["database", "hosts", "", "db2"],
+                vec!["database", "port", "5432"],
+                vec!["debug"],
+            ]
+        );
+        assert_eq!(CCL::parse_str("").unwrap().leaves().count(), 0);
+    }
+
+    #[test]
+    fn test_visitor() {
+        #[derive(Default)]
+        struct Trace(Vec<String>);
+
+        impl Visitor for Trace {
+            fn enter(&mut self, path: &[&str], _ccl: &CCL) {
+                self.0.push(format!("enter {}", path.join("=")));
+            }
+
+            fn leave(&mut self, path: &[&str], ccl: &CCL) {
+                let CCL(map) = ccl;
+                self.0.push(format!(
+                    "leave {} ({})",
+                    path.join("="),
+                    map.len()
+                ));
+            }
+        }
+
+        let mut trace = Trace::default();
+        config().walk(&mut trace);
+        insta::assert_debug_snapshot!(trace.0, @r#"
+        [
+            "enter database",
+            "enter database=hosts",
+            "enter database=hosts=",
+            "enter database=hosts==db1",
+            "leave database=hosts==db1 (0)",
+            "enter database=hosts==db2",
+            "leave database=hosts==db2 (0)",
+            "leave database=hosts= (2)",
+            "leave database=hosts (1)",
+            "enter database=port",
+            "enter database=port=5432",
+            "leave database=port=5432 (0)",
+            "leave database=port (1)",
+            "leave database (2)",
+            "enter debug",
+            "leave debug (0)",
+        ]
+        "#);
+    }
+
+    #[test]
+    fn test_walk_mut() {
+        let mut ccl = config();
+        let mut visited = Vec::new();
+        ccl.walk_mut(|path, ccl| {
+            visited.push(path.join("="));
+            // Entries added here are walked as well
+            if path == ["debug"] {
+                ccl.insert_path(&["on"]);
+            }
+            if path.last().is_some_and(|key| key == "hosts") {
+                ccl.remove_path(&[""]);
+            }
+        });
+        insta::assert_snapshot!(ccl.pretty(), @r"
+        database =
+          hosts =
+          port =
+            5432 =
+        debug =
+          on =
+        ");
+        insta::assert_debug_snapshot!(visited, @r#"
+        [
+            "database",
+            "database=hosts",
+            "database=port",
+            "database=port=5432",
+            "debug",
+            "debug=on",
+        ]
+        "#);
+    }
+
+    #[test]
+    fn test_deep_nesting() {
+        let depth = 10_000;
+        let keys = (0..depth).map(|i| format!("k{}", i)).collect::<Vec<_>>();
+        let path = keys.iter().map(String::as_str).collect::<Vec<_>>();
+        let mut ccl = CCL(Default::default());
+        ccl.insert_path(&path);
+
+        assert_eq!(ccl.iter_paths().count(), depth);
+        assert_eq!(ccl.leaves().collect::<Vec<_>>(), [path]);
+        let mut count = 0;
+        ccl.walk_mut(|_, _| count += 1);
+        assert_eq!(count, depth);
+
+        // Dropping a `CCL` recurses, so take it apart from the top down
+        let mut next = Some(ccl);
+        while let Some(CCL(map)) = next {
+            next = map.into_values().next();
+        }
+    }
+}